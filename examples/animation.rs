@@ -187,6 +187,10 @@ impl Example for App {
                                 value: self.opacity,
                             }
                         ],
+                        colors: vec![],
+                        ints: vec![],
+                        points: vec![],
+                        removed: vec![],
                     },
                 );
                 txn.generate_frame();
@@ -887,3 +887,48 @@ fn test_cst_translation_rotate() {
 
     test_pt(100.0, 0.0, &cst, child1, root, 0.0, -100.0);
 }
+
+#[test]
+fn test_cst_global_transform_applies_once() {
+    // Nested reference frames under a global scale
+    let mut cst = ClipScrollTree::new();
+
+    let root = add_reference_frame(
+        &mut cst,
+        None,
+        LayoutTransform::identity(),
+        LayoutVector2D::zero(),
+    );
+
+    let child1 = add_reference_frame(
+        &mut cst,
+        Some(root),
+        LayoutTransform::create_translation(100.0, 0.0, 0.0),
+        LayoutVector2D::zero(),
+    );
+
+    let child2 = add_reference_frame(
+        &mut cst,
+        Some(child1),
+        LayoutTransform::create_translation(0.0, 50.0, 0.0),
+        LayoutVector2D::zero(),
+    );
+
+    let mut properties = SceneProperties::new();
+    properties.set_global_transform(LayoutTransform::create_scale(2.0, 2.0, 1.0));
+    properties.flush_pending_updates();
+    cst.update_tree(WorldPoint::zero(), &properties);
+
+    let world_pt = |node| {
+        cst.get_world_transform(node)
+            .into_transform()
+            .transform_point2d(&LayoutPoint::new(10.0, 10.0))
+            .unwrap()
+    };
+    assert_eq!(world_pt(root), WorldPoint::new(20.0, 20.0));
+    assert_eq!(world_pt(child1), WorldPoint::new(220.0, 20.0));
+    assert_eq!(world_pt(child2), WorldPoint::new(220.0, 120.0));
+
+    // Relative transforms below the root don't include it.
+    test_pt(100.0, 100.0, &cst, child2, root, 200.0, 150.0);
+}
@@ -9,7 +9,8 @@ use api::{FilterOp, FilterPrimitive, FontInstanceKey, GlyphInstance, GlyphOption
 use api::{IframeDisplayItem, ImageKey, ImageRendering, ItemRange, ColorDepth};
 use api::{LineOrientation, LineStyle, NinePatchBorderSource, PipelineId};
 use api::{PropertyBinding, ReferenceFrame, ReferenceFrameKind, ScrollFrameDisplayItem, ScrollSensitivity};
-use api::{Shadow, SpaceAndClipInfo, SpatialId, StackingContext, StickyFrameDisplayItem};
+use api::{Shadow, SpaceAndClipInfo, SpatialId, StackingContext, StackingContextTransform};
+use api::StickyFrameDisplayItem;
 use api::{ClipMode, PrimitiveKeyKind, TransformStyle, YuvColorSpace, YuvData, TempFilterData};
 use api::units::*;
 use crate::clip::{ClipChainId, ClipRegion, ClipItemKey, ClipStore};
@@ -19,7 +20,7 @@ use crate::glyph_rasterizer::FontInstance;
 use crate::hit_test::{HitTestingItem, HitTestingScene};
 use crate::image::simplify_repeated_primitive;
 use crate::intern::Interner;
-use crate::internal_types::{FastHashMap, FastHashSet, LayoutPrimitiveInfo, Filter};
+use crate::internal_types::{FastHashMap, FastHashSet, LayoutPrimitiveInfo, Filter, FilterChain};
 use crate::picture::{Picture3DContext, PictureCompositeMode, PicturePrimitive, PictureOptions};
 use crate::picture::{BlitReason, PrimitiveList, TileCacheInstance};
 use crate::prim_store::{PrimitiveInstance, PrimitiveSceneData};
@@ -35,9 +36,9 @@ use crate::prim_store::picture::{Picture, PictureCompositeKey, PictureKey};
 use crate::prim_store::text_run::TextRun;
 use crate::render_backend::{DocumentView};
 use crate::resource_cache::{FontInstanceMap, ImageRequest};
-use crate::scene::{Scene, StackingContextHelpers};
+use crate::scene::{Scene, SceneProperties, StackingContextHelpers};
 use crate::scene_builder::{DocumentStats, Interners};
-use crate::spatial_node::{StickyFrameInfo, ScrollFrameKind};
+use crate::spatial_node::{SpatialNode, SpatialNodeType, StickyFrameInfo, ScrollFrameKind};
 use std::{f32, mem, usize, ops};
 use std::collections::vec_deque::VecDeque;
 use std::sync::Arc;
@@ -202,6 +203,12 @@ impl NodeIdToIndexMapper {
     pub fn get_spatial_node_index(&self, id: SpatialId) -> SpatialNodeIndex {
         self.spatial_node_map[&id]
     }
+
+    /// Map a spatial id that is already mapped to a different index,
+    /// returning the index it was mapped to.
+    pub fn replace_spatial_node(&mut self, id: SpatialId, index: SpatialNodeIndex) -> SpatialNodeIndex {
+        self.spatial_node_map.insert(id, index).expect("bug: replacing an unmapped spatial node")
+    }
 }
 
 /// A structure that converts a serialized display list into a form that WebRender
@@ -211,6 +218,10 @@ pub struct DisplayListFlattener<'a> {
     /// The scene that we are currently flattening.
     scene: &'a Scene,
 
+    /// The properties that the bindings resolved at scene build time,
+    /// such as blend mode bindings, are resolved with.
+    properties: &'a SceneProperties,
+
     /// The ClipScrollTree that we are currently building during flattening.
     clip_scroll_tree: &'a mut ClipScrollTree,
 
@@ -268,6 +279,7 @@ pub struct DisplayListFlattener<'a> {
 impl<'a> DisplayListFlattener<'a> {
     pub fn create_frame_builder(
         scene: &Scene,
+        properties: &SceneProperties,
         clip_scroll_tree: &mut ClipScrollTree,
         font_instances: FontInstanceMap,
         view: &DocumentView,
@@ -279,7 +291,7 @@ impl<'a> DisplayListFlattener<'a> {
     ) -> FrameBuilder {
         // We checked that the root pipeline is available on the render backend.
         let root_pipeline_id = scene.root_pipeline_id.unwrap();
-        let root_pipeline = scene.pipelines.get(&root_pipeline_id).unwrap();
+        let root_pipeline = scene.get_pipeline(root_pipeline_id).unwrap();
 
         let background_color = root_pipeline
             .background_color
@@ -287,6 +299,7 @@ impl<'a> DisplayListFlattener<'a> {
 
         let mut flattener = DisplayListFlattener {
             scene,
+            properties,
             clip_scroll_tree,
             font_instances,
             config: *frame_builder_config,
@@ -334,7 +347,7 @@ impl<'a> DisplayListFlattener<'a> {
         );
 
         flattener.flatten_items(
-            &mut root_pipeline.display_list.iter(),
+            &mut root_pipeline.display_list().iter(),
             root_pipeline.pipeline_id,
             true,
         );
@@ -344,8 +357,7 @@ impl<'a> DisplayListFlattener<'a> {
         debug_assert!(flattener.sc_stack.is_empty());
 
         new_scene.root_pipeline_id = Some(root_pipeline_id);
-        new_scene.pipeline_epochs = scene.pipeline_epochs.clone();
-        new_scene.pipelines = scene.pipelines.clone();
+        new_scene.set_pipelines_from(scene);
 
         FrameBuilder::with_display_list_flattener(
             view.device_rect.size.into(),
@@ -806,13 +818,25 @@ impl<'a> DisplayListFlattener<'a> {
             return;
         }
 
-        let composition_operations = {
-            CompositeOps::new(
+        let composition_operations = match stacking_context.filter_datas_for_compositing(filter_datas) {
+            Ok(filter_datas) => CompositeOps::new(
                 stacking_context.filter_ops_for_compositing(filters),
-                stacking_context.filter_datas_for_compositing(filter_datas),
+                filter_datas,
                 stacking_context.filter_primitives_for_compositing(filter_primitives),
-                stacking_context.mix_blend_mode_for_compositing(),
-            )
+                stacking_context.resolve_mix_blend_mode_for_compositing(self.properties),
+            ),
+            Err(err) => {
+                // The filters and filter primitives that use the tables
+                // can't be applied without them, so leave them all out
+                // rather than apply some of them.
+                warn!("Dropping the filters of a stacking context with malformed filter data: {:?}", err);
+                CompositeOps::new(
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                    stacking_context.resolve_mix_blend_mode_for_compositing(self.properties),
+                )
+            }
         };
 
         let clip_chain_id = match stacking_context.clip_id {
@@ -867,7 +891,7 @@ impl<'a> DisplayListFlattener<'a> {
         spatial_node_index: SpatialNodeIndex,
     ) {
         let iframe_pipeline_id = info.pipeline_id;
-        let pipeline = match self.scene.pipelines.get(&iframe_pipeline_id) {
+        let pipeline = match self.scene.get_pipeline(iframe_pipeline_id) {
             Some(pipeline) => pipeline,
             None => {
                 debug_assert!(info.ignore_missing_pipeline);
@@ -913,7 +937,7 @@ impl<'a> DisplayListFlattener<'a> {
 
         self.rf_mapper.push_scope();
         self.flatten_items(
-            &mut pipeline.display_list.iter(),
+            &mut pipeline.display_list().iter(),
             pipeline.pipeline_id,
             true,
         );
@@ -1172,8 +1196,27 @@ impl<'a> DisplayListFlattener<'a> {
                 );
             }
             DisplayItem::PushStackingContext(ref info) => {
-                let space = self.get_space(&info.spatial_id);
+                let mut space = self.get_space(&info.spatial_id);
                 let mut subtraversal = item.sub_iter();
+
+                // A transformed stacking context gets an implicit reference
+                // frame, and its contents that are positioned by its spatial
+                // node are positioned by the reference frame instead.
+                let outer_space = match info.stacking_context.transform {
+                    Some(ref transform) => {
+                        let frame_index = self.push_stacking_context_reference_frame(
+                            space,
+                            pipeline_id,
+                            info.stacking_context.transform_style,
+                            transform,
+                        );
+                        self.rf_mapper.push_scope();
+                        space = frame_index;
+                        Some(self.id_to_index_mapper.replace_spatial_node(info.spatial_id, frame_index))
+                    }
+                    None => None,
+                };
+
                 self.flatten_stacking_context(
                     &mut subtraversal,
                     pipeline_id,
@@ -1186,6 +1229,11 @@ impl<'a> DisplayListFlattener<'a> {
                     info.is_backface_visible,
                     apply_pipeline_clip,
                 );
+
+                if let Some(outer_space) = outer_space {
+                    self.id_to_index_mapper.replace_spatial_node(info.spatial_id, outer_space);
+                    self.rf_mapper.pop_scope();
+                }
                 return Some(subtraversal);
             }
             DisplayItem::PushReferenceFrame(ref info) => {
@@ -1907,8 +1955,13 @@ impl<'a> DisplayListFlattener<'a> {
         }
 
         // For each filter, create a new image with that composite mode.
+        // Noop filters don't get a picture, and runs of literal opacities
+        // share one.
+        let mut filters = FilterChain::new(
+            mem::replace(&mut stacking_context.composite_ops.filters, Vec::new())
+        ).canonicalize();
         let mut current_filter_data_index = 0;
-        for filter in &mut stacking_context.composite_ops.filters {
+        for filter in &mut filters.filters {
             filter.sanitize();
 
             let composite_mode = Some(match *filter {
@@ -2163,6 +2216,32 @@ impl<'a> DisplayListFlattener<'a> {
         index
     }
 
+    /// Add the implicit reference frame of a transformed stacking context.
+    /// The reference frame is placed so that its local space is the space
+    /// that the stacking context's origin is in, which is the space that
+    /// the transform and its origin are given in.
+    fn push_stacking_context_reference_frame(
+        &mut self,
+        parent_index: SpatialNodeIndex,
+        pipeline_id: PipelineId,
+        transform_style: TransformStyle,
+        transform: &StackingContextTransform,
+    ) -> SpatialNodeIndex {
+        let current_offset = self.current_offset(parent_index);
+        let mut node = SpatialNode::new_reference_frame(
+            Some(parent_index),
+            transform_style,
+            transform.transform,
+            ReferenceFrameKind::Transform,
+            current_offset,
+            pipeline_id,
+        );
+        if let SpatialNodeType::ReferenceFrame(ref mut info) = node.node_type {
+            info.transform_origin = Some(transform.origin);
+        }
+        self.clip_scroll_tree.add_spatial_node(node)
+    }
+
     pub fn push_root(
         &mut self,
         pipeline_id: PipelineId,
@@ -2421,7 +2500,7 @@ impl<'a> DisplayListFlattener<'a> {
                         // blur radius is 0, the code in Picture::prepare_for_render will
                         // detect this and mark the picture to be drawn directly into the
                         // parent picture, which avoids an intermediate surface and blur.
-                        let mut blur_filter = Filter::Blur(std_deviation);
+                        let mut blur_filter = Filter::Blur(std_deviation, None);
                         blur_filter.sanitize();
                         let composite_mode = PictureCompositeMode::Filter(blur_filter);
                         let composite_mode_key = Some(composite_mode.clone()).into();
@@ -3324,3 +3403,365 @@ fn create_clip_prim_instance(
         spatial_node_index,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use api::{BuiltDisplayList, ColorF, ColorSpace, CommonItemProperties, ComponentTransferFuncType};
+    use api::{DisplayListBuilder, DynamicProperties, Epoch, FilterData, FilterOp, FilterPrimitive};
+    use api::{FilterPrimitiveKind, FloodPrimitive};
+    use api::{FontRenderMode, MixBlendMode, PipelineId, PropertyBinding, PropertyBindingKey};
+    use api::{PropertyValue, SpaceAndClipInfo, StackingContext, StackingContextTransform, MAX_BLUR_RADIUS};
+    use api::units::*;
+    use crate::clip_scroll_tree::{ClipScrollTree, SpatialNodeIndex};
+    use crate::frame_builder::{ChasePrimitive, FrameBuilder, FrameBuilderConfig};
+    use crate::internal_types::{FastHashMap, FastHashSet, Filter};
+    use crate::picture::PictureCompositeMode;
+    use crate::prim_store::PrimitiveInstanceKind;
+    use crate::render_backend::DocumentView;
+    use crate::scene::{Scene, ScenePipelineBuilder, SceneProperties};
+    use crate::scene_builder::{DocumentStats, Interners};
+    use std::sync::{Arc, RwLock};
+    use super::DisplayListFlattener;
+
+    /// Flatten a scene the way the scene builder does.
+    fn flatten(scene: &Scene, properties: &SceneProperties) -> (FrameBuilder, ClipScrollTree) {
+        let mut clip_scroll_tree = ClipScrollTree::new();
+        let view = DocumentView {
+            device_rect: DeviceIntRect::new(DeviceIntPoint::zero(), DeviceIntSize::new(100, 100)),
+            layer: 0,
+            pan: DeviceIntPoint::zero(),
+            device_pixel_ratio: 1.0,
+            page_zoom_factor: 1.0,
+            pinch_zoom_factor: 1.0,
+        };
+        let config = FrameBuilderConfig {
+            default_font_render_mode: FontRenderMode::Mono,
+            dual_source_blending_is_supported: false,
+            dual_source_blending_is_enabled: false,
+            chase_primitive: ChasePrimitive::Nothing,
+            enable_picture_caching: false,
+            testing: true,
+            gpu_supports_fast_clears: false,
+            gpu_supports_advanced_blend: false,
+            advanced_blend_is_coherent: false,
+            batch_lookback_count: 10,
+            background_color: None,
+        };
+        let frame_builder = DisplayListFlattener::create_frame_builder(
+            scene,
+            properties,
+            &mut clip_scroll_tree,
+            Arc::new(RwLock::new(FastHashMap::default())),
+            &view,
+            &FastHashSet::default(),
+            &config,
+            &mut Scene::new(),
+            &mut Interners::default(),
+            &DocumentStats::empty(),
+        );
+        (frame_builder, clip_scroll_tree)
+    }
+
+    /// Build a scene from the root pipeline's display list.
+    fn scene_with_root(pipeline_id: PipelineId, display_list: BuiltDisplayList) -> Scene {
+        let mut scene = Scene::new();
+        scene.set_root_pipeline_id(pipeline_id);
+        scene.add_pipeline(
+            ScenePipelineBuilder::new(pipeline_id)
+                .viewport_size(LayoutSize::new(200.0, 200.0))
+                .content_size(LayoutSize::new(200.0, 200.0))
+                .display_list(display_list)
+                .build(),
+            Epoch(0),
+        );
+        scene
+    }
+
+    /// Build a scene with a rectangle in a stacking context with `filters`
+    /// and `filter_primitives`.
+    fn filtered_scene(filters: &[FilterOp], filter_primitives: &[FilterPrimitive]) -> Scene {
+        let pipeline_id = PipelineId(0, 1);
+        let space_and_clip = SpaceAndClipInfo::root_scroll(pipeline_id);
+        let mut builder = DisplayListBuilder::new(pipeline_id, LayoutSize::new(200.0, 200.0));
+        builder.push_stacking_context_item(
+            LayoutPoint::zero(),
+            space_and_clip.spatial_id,
+            true,
+            StackingContext::default(),
+            filters,
+            &[],
+            filter_primitives,
+        );
+        let rect = LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(10.0, 10.0));
+        builder.push_rect(&CommonItemProperties::new(rect, space_and_clip), ColorF::WHITE);
+        builder.pop_stacking_context();
+        let (_, _, display_list) = builder.finalize();
+        scene_with_root(pipeline_id, display_list)
+    }
+
+    /// Flatten `scene`, and get the composite mode of each picture once
+    /// its properties are resolved with `properties`.
+    fn resolved_composite_modes(scene: &Scene, properties: &SceneProperties) -> Vec<PictureCompositeMode> {
+        let (mut frame_builder, _) = flatten(scene, &SceneProperties::new());
+        frame_builder.prim_store.pictures
+            .iter_mut()
+            .filter_map(|picture| {
+                picture.resolve_scene_properties(properties);
+                picture.requested_composite_mode.clone()
+            })
+            .collect()
+    }
+
+    /// Get the origin and spatial node of each rectangle, in order.
+    fn rectangles(frame_builder: &FrameBuilder) -> Vec<(LayoutPoint, SpatialNodeIndex)> {
+        frame_builder.prim_store.pictures
+            .iter()
+            .flat_map(|picture| picture.prim_list.prim_instances.iter())
+            .filter(|instance| match instance.kind {
+                PrimitiveInstanceKind::Rectangle { .. } => true,
+                _ => false,
+            })
+            .map(|instance| (instance.prim_origin, instance.spatial_node_index))
+            .collect()
+    }
+
+    #[test]
+    fn stacking_context_transform_gets_reference_frame() {
+        let pipeline_id = PipelineId(0, 1);
+        let key = PropertyBindingKey::new(1);
+        let size = LayoutSize::new(10.0, 10.0);
+
+        // A stacking context at (100, 0), transformed about its origin,
+        // holding a rectangle at its origin.
+        let mut builder = DisplayListBuilder::new(pipeline_id, LayoutSize::new(200.0, 200.0));
+        let space_and_clip = SpaceAndClipInfo::root_scroll(pipeline_id);
+        builder.push_transformed_stacking_context(
+            LayoutPoint::new(100.0, 0.0),
+            space_and_clip.spatial_id,
+            true,
+            StackingContextTransform {
+                transform: PropertyBinding::Binding(key, LayoutTransform::identity()),
+                origin: PropertyBinding::Value(LayoutPoint::new(100.0, 0.0)),
+            },
+        );
+        let rect = LayoutRect::new(LayoutPoint::zero(), size);
+        builder.push_rect(&CommonItemProperties::new(rect, space_and_clip), ColorF::WHITE);
+        builder.pop_stacking_context();
+        // Items after the stacking context are back in the root scroll node.
+        let rect = LayoutRect::new(LayoutPoint::new(0.0, 50.0), size);
+        builder.push_rect(&CommonItemProperties::new(rect, space_and_clip), ColorF::WHITE);
+        let (_, _, display_list) = builder.finalize();
+
+        let scene = scene_with_root(pipeline_id, display_list);
+
+        let (frame_builder, mut clip_scroll_tree) = flatten(&scene, &SceneProperties::new());
+        let rects = rectangles(&frame_builder);
+        assert_eq!(rects.len(), 2);
+        let (transformed_origin, transformed_space) = rects[0];
+        let (plain_origin, plain_space) = rects[1];
+        assert_ne!(transformed_space, plain_space);
+        assert_eq!(
+            clip_scroll_tree.spatial_nodes[transformed_space.0 as usize].parent,
+            Some(plain_space),
+        );
+        assert_eq!(plain_origin, LayoutPoint::new(0.0, 50.0));
+
+        let corner = |clip_scroll_tree: &ClipScrollTree| -> WorldPoint {
+            clip_scroll_tree
+                .get_world_transform(transformed_space)
+                .into_transform()
+                .transform_point2d(&(transformed_origin + size))
+                .unwrap()
+        };
+
+        // Until the binding has a value, the contents are only offset.
+        let mut properties = SceneProperties::new();
+        clip_scroll_tree.update_tree(WorldPoint::zero(), &properties);
+        assert_eq!(corner(&clip_scroll_tree), WorldPoint::new(110.0, 10.0));
+
+        // Scaling about (100, 0) leaves the stacking context's origin in
+        // place and doubles the rectangle.
+        properties.set_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key, value: LayoutTransform::create_scale(2.0, 2.0, 1.0) }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        clip_scroll_tree.update_tree(WorldPoint::zero(), &properties);
+        assert_eq!(corner(&clip_scroll_tree), WorldPoint::new(120.0, 20.0));
+    }
+
+    #[test]
+    fn mix_blend_mode_binding_is_resolved() {
+        let pipeline_id = PipelineId(0, 1);
+        let key = PropertyBindingKey::new(1);
+        let space_and_clip = SpaceAndClipInfo::root_scroll(pipeline_id);
+
+        // A backdrop, and a stacking context blended onto it.
+        let rect = LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(10.0, 10.0));
+        let mut builder = DisplayListBuilder::new(pipeline_id, LayoutSize::new(200.0, 200.0));
+        builder.push_rect(&CommonItemProperties::new(rect, space_and_clip), ColorF::BLACK);
+        builder.push_stacking_context_item(
+            LayoutPoint::zero(),
+            space_and_clip.spatial_id,
+            true,
+            StackingContext {
+                mix_blend_mode: MixBlendMode::Multiply,
+                mix_blend_mode_binding: Some(key),
+                ..StackingContext::default()
+            },
+            &[],
+            &[],
+            &[],
+        );
+        builder.push_rect(&CommonItemProperties::new(rect, space_and_clip), ColorF::WHITE);
+        builder.pop_stacking_context();
+        let (_, _, display_list) = builder.finalize();
+        let scene = scene_with_root(pipeline_id, display_list);
+
+        let blend_modes = |properties: &SceneProperties| -> Vec<MixBlendMode> {
+            let (frame_builder, _) = flatten(&scene, properties);
+            frame_builder.prim_store.pictures
+                .iter()
+                .filter_map(|picture| match picture.requested_composite_mode {
+                    Some(PictureCompositeMode::MixBlend(mode)) => Some(mode),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // Without a value, the static blend mode is used.
+        let mut properties = SceneProperties::new();
+        assert_eq!(blend_modes(&properties), vec![MixBlendMode::Multiply]);
+
+        properties.set_properties(DynamicProperties {
+            ints: vec![PropertyValue { key, value: MixBlendMode::Screen as i32 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        assert_eq!(blend_modes(&properties), vec![MixBlendMode::Screen]);
+
+        // A binding to the normal mode needs no blend at all.
+        properties.set_properties(DynamicProperties {
+            ints: vec![PropertyValue { key, value: MixBlendMode::Normal as i32 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        assert!(blend_modes(&properties).is_empty());
+    }
+
+    #[test]
+    fn flood_color_binding_is_resolved() {
+        let key = PropertyBindingKey::new(1);
+        let flood = FilterPrimitive {
+            kind: FilterPrimitiveKind::Flood(FloodPrimitive { color: ColorF::BLACK, color_binding: Some(key) }),
+            color_space: ColorSpace::Srgb,
+        };
+        let scene = filtered_scene(&[], &[flood]);
+        let flood_colors = |properties: &SceneProperties| -> Vec<ColorF> {
+            resolved_composite_modes(&scene, properties)
+                .into_iter()
+                .filter_map(|mode| match mode {
+                    PictureCompositeMode::SvgFilter(primitives, _) => Some(primitives),
+                    _ => None,
+                })
+                .flat_map(|primitives| primitives.into_iter())
+                .filter_map(|primitive| match primitive.kind {
+                    FilterPrimitiveKind::Flood(flood) => Some(flood.color),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let mut properties = SceneProperties::new();
+        assert_eq!(flood_colors(&properties), vec![ColorF::BLACK]);
+
+        // Out of range values are clamped, like static flood colors.
+        properties.set_properties(DynamicProperties {
+            colors: vec![PropertyValue { key, value: ColorF::new(2.0, 0.0, 0.0, 1.0) }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        assert_eq!(flood_colors(&properties), vec![ColorF::new(1.0, 0.0, 0.0, 1.0)]);
+    }
+
+    #[test]
+    fn animated_blur_radius_is_resolved() {
+        let key = PropertyBindingKey::new(1);
+        let scene = filtered_scene(&[FilterOp::AnimatedBlur(PropertyBinding::Binding(key, 0.0), 2.0)], &[]);
+        let blur_radii = |properties: &SceneProperties| -> Vec<f32> {
+            resolved_composite_modes(&scene, properties)
+                .into_iter()
+                .filter_map(|mode| match mode {
+                    PictureCompositeMode::Filter(Filter::Blur(radius, Some(..))) => Some(radius),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // Until the binding has a value, the radius from the scene is used.
+        let mut properties = SceneProperties::new();
+        assert_eq!(blur_radii(&properties), vec![2.0]);
+
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key, value: 5.0 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        assert_eq!(blur_radii(&properties), vec![5.0]);
+
+        // Animated radii are clamped like static ones.
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key, value: MAX_BLUR_RADIUS * 2.0 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        assert_eq!(blur_radii(&properties), vec![MAX_BLUR_RADIUS]);
+    }
+
+    #[test]
+    fn identical_filter_datas_share_a_handle() {
+        let pipeline_id = PipelineId(0, 1);
+        let space_and_clip = SpaceAndClipInfo::root_scroll(pipeline_id);
+        let table = |values: Vec<f32>| FilterData {
+            func_r_type: ComponentTransferFuncType::Table,
+            r_values: values,
+            func_g_type: ComponentTransferFuncType::Identity,
+            g_values: Vec::new(),
+            func_b_type: ComponentTransferFuncType::Identity,
+            b_values: Vec::new(),
+            func_a_type: ComponentTransferFuncType::Identity,
+            a_values: Vec::new(),
+        };
+
+        // Three stacking contexts, the first two with identical tables.
+        let mut builder = DisplayListBuilder::new(pipeline_id, LayoutSize::new(200.0, 200.0));
+        let rect = LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(10.0, 10.0));
+        for values in &[vec![1.0, 0.5, 0.0], vec![1.0, 0.5, 0.0], vec![0.0, 0.5, 1.0]] {
+            builder.push_stacking_context_item(
+                LayoutPoint::zero(),
+                space_and_clip.spatial_id,
+                true,
+                StackingContext::default(),
+                &[FilterOp::ComponentTransfer],
+                &[table(values.clone())],
+                &[],
+            );
+            builder.push_rect(&CommonItemProperties::new(rect, space_and_clip), ColorF::WHITE);
+            builder.pop_stacking_context();
+        }
+        let (_, _, display_list) = builder.finalize();
+        let scene = scene_with_root(pipeline_id, display_list);
+
+        let (frame_builder, _) = flatten(&scene, &SceneProperties::new());
+        let handles: Vec<_> = frame_builder.prim_store.pictures
+            .iter()
+            .filter_map(|picture| match picture.requested_composite_mode {
+                Some(PictureCompositeMode::ComponentTransferFilter(handle)) => Some(handle.uid()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(handles.len(), 3);
+        assert_eq!(handles[0], handles[1]);
+        assert_ne!(handles[0], handles[2]);
+    }
+}
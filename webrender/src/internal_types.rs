@@ -41,7 +41,9 @@ const OPACITY_EPSILON: f32 = 0.001;
 #[cfg_attr(feature = "replay", derive(Deserialize))]
 pub enum Filter {
     Identity,
-    Blur(f32),
+    /// The blur radius, and the animated property it is resolved from each
+    /// frame, if it is bound to one.
+    Blur(f32, Option<api::PropertyBindingKey<f32>>),
     Brightness(f32),
     Contrast(f32),
     Grayscale(f32),
@@ -63,7 +65,7 @@ impl Filter {
     /// are sensible.
     pub fn sanitize(&mut self) {
         match self {
-            Filter::Blur(ref mut radius) => {
+            Filter::Blur(ref mut radius, _) => {
                 *radius = radius.min(MAX_BLUR_RADIUS);
             }
             Filter::DropShadows(ref mut stack) => {
@@ -103,7 +105,7 @@ impl Filter {
     pub fn is_noop(&self) -> bool {
         match *self {
             Filter::Identity => false, // this is intentional
-            Filter::Blur(length) => length == 0.0,
+            Filter::Blur(length, _) => length == 0.0,
             Filter::Brightness(amount) => amount == 1.0,
             Filter::Contrast(amount) => amount == 1.0,
             Filter::Grayscale(amount) => amount == 0.0,
@@ -142,7 +144,14 @@ impl From<FilterOp> for Filter {
     fn from(op: FilterOp) -> Self {
         match op {
             FilterOp::Identity => Filter::Identity,
-            FilterOp::Blur(r) => Filter::Blur(r),
+            FilterOp::Blur(r) => Filter::Blur(r, None),
+            FilterOp::AnimatedBlur(binding, r) => {
+                let key = match binding {
+                    api::PropertyBinding::Binding(key, _) => Some(key),
+                    api::PropertyBinding::Value(..) => None,
+                };
+                Filter::Blur(r, key)
+            }
             FilterOp::Brightness(b) => Filter::Brightness(b),
             FilterOp::Contrast(c) => Filter::Contrast(c),
             FilterOp::Grayscale(g) => Filter::Grayscale(g),
@@ -161,6 +170,72 @@ impl From<FilterOp> for Filter {
     }
 }
 
+/// An ordered list of filters, as applied to a stacking context.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FilterChain {
+    pub filters: Vec<Filter>,
+}
+
+impl FilterChain {
+    pub fn new(filters: Vec<Filter>) -> Self {
+        FilterChain {
+            filters,
+        }
+    }
+
+    /// Return a visually equivalent chain with noop filters removed and
+    /// runs of adjacent literal opacity filters merged into one. Bound
+    /// opacity filters are always kept, since their amount can change
+    /// from frame to frame.
+    pub fn canonicalize(&self) -> FilterChain {
+        let mut filters: Vec<Filter> = Vec::with_capacity(self.filters.len());
+
+        for filter in &self.filters {
+            match *filter {
+                Filter::Opacity(api::PropertyBinding::Value(..), amount) => {
+                    if amount >= 1.0 {
+                        continue;
+                    }
+                    if let Some(Filter::Opacity(api::PropertyBinding::Value(prev_value), prev_amount)) = filters.last_mut() {
+                        *prev_amount *= amount;
+                        *prev_value = *prev_amount;
+                        continue;
+                    }
+                    filters.push(Filter::Opacity(api::PropertyBinding::Value(amount), amount));
+                }
+                Filter::Opacity(api::PropertyBinding::Binding(..), _) => {
+                    filters.push(filter.clone());
+                }
+                _ if filter.is_noop() => {}
+                _ => {
+                    filters.push(filter.clone());
+                }
+            }
+        }
+
+        FilterChain {
+            filters,
+        }
+    }
+
+    /// Returns true if the chain has a blur whose radius is animated. While
+    /// it is animating, such a blur can be drawn with a cheaper, more
+    /// heavily downsampled approximation, switching back to full quality
+    /// once the binding stops changing.
+    pub fn has_animated_blur(&self) -> bool {
+        self.filters.iter().any(|filter| match *filter {
+            Filter::Blur(_, key) => key.is_some(),
+            _ => false,
+        })
+    }
+}
+
+impl From<Vec<Filter>> for FilterChain {
+    fn from(filters: Vec<Filter>) -> Self {
+        FilterChain::new(filters)
+    }
+}
+
 /// An ID for a texture that is owned by the `texture_cache` module.
 ///
 /// This can include atlases or standalone textures allocated via the texture
@@ -517,3 +592,47 @@ impl LayoutPrimitiveInfo {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use api::{PropertyBinding, PropertyBindingKey};
+
+    #[test]
+    fn filter_chain_canonicalize() {
+        let chain = FilterChain::new(vec![Filter::Blur(2.0, None), Filter::Sepia(0.5)]);
+        let with_noops = FilterChain::new(vec![
+            Filter::Brightness(1.0),
+            Filter::Blur(2.0, None),
+            Filter::Opacity(PropertyBinding::Value(1.0), 1.0),
+            Filter::Sepia(0.5),
+        ]);
+        assert_eq!(with_noops.canonicalize(), chain);
+
+        let split = FilterChain::new(vec![
+            Filter::Opacity(PropertyBinding::Value(0.5), 0.5),
+            Filter::Opacity(PropertyBinding::Value(0.5), 0.5),
+        ]);
+        assert_eq!(
+            split.canonicalize(),
+            FilterChain::new(vec![Filter::Opacity(PropertyBinding::Value(0.25), 0.25)]),
+        );
+
+        // Bound opacity is never dropped as a noop, even when it is
+        // currently fully opaque.
+        let key = PropertyBindingKey::new(1);
+        let opaque = FilterChain::new(vec![Filter::Opacity(PropertyBinding::Binding(key, 1.0), 1.0)]);
+        assert_eq!(opaque.canonicalize(), opaque);
+    }
+
+    #[test]
+    fn filter_chain_has_animated_blur() {
+        let bound = Filter::from(FilterOp::AnimatedBlur(PropertyBinding::Binding(PropertyBindingKey::new(1), 2.0), 2.0));
+        assert_eq!(bound, Filter::Blur(2.0, Some(PropertyBindingKey::new(1))));
+        assert!(FilterChain::new(vec![Filter::Sepia(0.5), bound]).has_animated_blur());
+
+        let literal = Filter::from(FilterOp::AnimatedBlur(PropertyBinding::Value(2.0), 2.0));
+        assert_eq!(literal, Filter::Blur(2.0, None));
+        assert!(!FilterChain::new(vec![literal, Filter::from(FilterOp::Blur(2.0))]).has_animated_blur());
+    }
+}
@@ -688,7 +688,7 @@ impl TileCacheInstance {
         let current_properties = frame_context.scene_properties.float_properties();
         let old_properties = mem::replace(&mut self.opacity_bindings, FastHashMap::default());
 
-        for (id, value) in current_properties {
+        for (id, value) in current_properties.iter() {
             let changed = match old_properties.get(id) {
                 Some(old_property) => !old_property.value.approx_eq(value),
                 None => true,
@@ -1583,7 +1583,7 @@ impl PictureCompositeMode {
         let mut result_rect = picture_rect;
         match self {
             PictureCompositeMode::Filter(filter) => match filter {
-                Filter::Blur(_) => {
+                Filter::Blur(..) => {
                     result_rect = picture_rect.inflate(inflation_factor, inflation_factor);
                 },
                 Filter::DropShadows(shadows) => {
@@ -1602,8 +1602,8 @@ impl PictureCompositeMode {
                     let output_rect = match primitive.kind {
                         FilterPrimitiveKind::Blur(ref primitive) => {
                             let input = primitive.input.to_index(cur_index).map(|index| output_rects[index]).unwrap_or(picture_rect);
-                            let inflation_factor = primitive.radius.round() * BLUR_SAMPLE_SCALE;
-                            input.inflate(inflation_factor, inflation_factor)
+                            let (radius_x, radius_y) = primitive.std_deviation();
+                            input.inflate(radius_x.round() * BLUR_SAMPLE_SCALE, radius_y.round() * BLUR_SAMPLE_SCALE)
                         }
                         FilterPrimitiveKind::DropShadow(ref primitive) => {
                             let inflation_factor = primitive.shadow.blur_radius.round() * BLUR_SAMPLE_SCALE;
@@ -2010,18 +2010,38 @@ impl PicturePrimitive {
         }
     }
 
-    fn resolve_scene_properties(&mut self, properties: &SceneProperties) -> bool {
+    pub fn resolve_scene_properties(&mut self, properties: &SceneProperties) -> bool {
         match self.requested_composite_mode {
             Some(PictureCompositeMode::Filter(ref mut filter)) => {
                 match *filter {
                     Filter::Opacity(ref binding, ref mut value) => {
                         *value = properties.resolve_float(binding);
                     }
+                    Filter::Blur(ref mut radius, Some(key)) => {
+                        let binding = PropertyBinding::Binding(key, *radius);
+                        *radius = properties.resolve_float(&binding).max(0.0);
+                    }
                     _ => {}
                 }
+                filter.sanitize();
 
                 filter.is_visible()
             }
+            Some(PictureCompositeMode::SvgFilter(ref mut primitives, _)) => {
+                for primitive in primitives {
+                    match primitive.kind {
+                        FilterPrimitiveKind::Blur(ref mut blur) => {
+                            *blur = properties.resolve_blur_primitive(blur);
+                        }
+                        FilterPrimitiveKind::Flood(ref mut flood) => {
+                            flood.color = properties.resolve_flood_color(flood);
+                        }
+                        _ => {}
+                    }
+                }
+
+                true
+            }
             _ => true,
         }
     }
@@ -2203,7 +2223,7 @@ impl PicturePrimitive {
                 let transform = map_pic_to_raster.get_transform();
 
                 let dep_info = match raster_config.composite_mode {
-                    PictureCompositeMode::Filter(Filter::Blur(blur_radius)) => {
+                    PictureCompositeMode::Filter(Filter::Blur(blur_radius, _)) => {
                         let blur_std_deviation = blur_radius * device_pixel_scale.0;
                         let scale_factors = scale_factors(&transform);
                         let blur_std_deviation = DeviceSize::new(
@@ -2827,7 +2847,7 @@ impl PicturePrimitive {
 
             // This inflation factor is to be applied to all primitives within the surface.
             let inflation_factor = match composite_mode {
-                PictureCompositeMode::Filter(Filter::Blur(blur_radius)) => {
+                PictureCompositeMode::Filter(Filter::Blur(blur_radius, _)) => {
                     // Only inflate if the caller hasn't already inflated
                     // the bounding rects for this filter.
                     if self.options.inflate_if_required {
@@ -2842,7 +2862,8 @@ impl PicturePrimitive {
                     let mut max = 0.0;
                     for primitive in primitives {
                         if let FilterPrimitiveKind::Blur(ref blur) = primitive.kind {
-                            max = f32::max(max, blur.radius * BLUR_SAMPLE_SCALE);
+                            let (radius_x, radius_y) = blur.std_deviation();
+                            max = f32::max(max, radius_x.max(radius_y) * BLUR_SAMPLE_SCALE);
                         }
                     }
                     max
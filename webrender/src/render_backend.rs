@@ -346,6 +346,10 @@ struct Document {
     /// without requiring the scene to be re-built.
     dynamic_properties: SceneProperties,
 
+    /// When the springs in dynamic_properties were last advanced, or None
+    /// if none are currently moving.
+    last_spring_step_time: Option<u64>,
+
     /// Track whether the last built frame is up to date or if it will need to be re-built
     /// before rendering again.
     frame_is_valid: bool,
@@ -391,6 +395,7 @@ impl Document {
             output_pipelines: FastHashSet::default(),
             hit_tester: None,
             dynamic_properties: SceneProperties::new(),
+            last_spring_step_time: None,
             frame_is_valid: false,
             hit_tester_is_valid: false,
             rendered_frame_is_valid: false,
@@ -409,6 +414,29 @@ impl Document {
         !self.view.device_rect.size.is_empty_or_negative()
     }
 
+    /// Advance the springs in dynamic_properties by the time since they
+    /// were last advanced, and invalidate the frame if any of them moved.
+    /// The first frame after a spring is set only starts the clock.
+    fn advance_springs(&mut self) {
+        if !self.dynamic_properties.has_springs() {
+            self.last_spring_step_time = None;
+            return;
+        }
+
+        let now = precise_time_ns();
+        let dt = match self.last_spring_step_time.replace(now) {
+            // Clamp the step so that a long pause between frames doesn't
+            // make the springs overshoot wildly.
+            Some(last) => (((now - last) as f64 / 1_000_000_000.0) as f32).min(MAX_SPRING_STEP),
+            None => return,
+        };
+
+        if self.dynamic_properties.advance_springs(dt) {
+            self.frame_is_valid = false;
+            self.hit_tester_is_valid = false;
+        }
+    }
+
     fn process_frame_msg(
         &mut self,
         message: FrameMsg,
@@ -506,6 +534,15 @@ impl Document {
                     }
                 }
             }
+            FrameMsg::SetSpringTarget { id, target, stiffness, damping } => {
+                self.dynamic_properties.set_spring_target(id, target, stiffness, damping);
+            }
+            FrameMsg::ClearSpring(id) => {
+                if self.dynamic_properties.clear_spring(id) {
+                    self.frame_is_valid = false;
+                    self.hit_tester_is_valid = false;
+                }
+            }
         }
 
         DocumentOps::nop()
@@ -534,7 +571,7 @@ impl Document {
                 gpu_cache,
                 self.stamp,
                 &mut self.clip_scroll_tree,
-                &self.scene.pipelines,
+                self.scene.pipelines(),
                 accumulated_scale_factor,
                 self.view.layer,
                 self.view.device_rect.origin,
@@ -587,7 +624,7 @@ impl Document {
     pub fn updated_pipeline_info(&mut self) -> PipelineInfo {
         let removed_pipelines = self.removed_pipelines.take_and_preallocate();
         PipelineInfo {
-            epochs: self.scene.pipeline_epochs.iter()
+            epochs: self.scene.pipeline_epochs().iter()
                 .map(|(&pipeline_id, &epoch)| ((pipeline_id, self.id), epoch)).collect(),
             removed_pipelines,
         }
@@ -630,6 +667,10 @@ impl Document {
         self.frame_is_valid = false;
         self.hit_tester_is_valid = false;
 
+        // Forget the values of bindings that the new scene no longer
+        // references, so that they don't pile up as content comes and goes.
+        self.dynamic_properties.retain_referenced(&built_scene.referenced_bindings);
+
         // Give the old frame builder a chance to destroy any resources.
         // Right now, all this does is build a hash map of any cached
         // surface tiles, that can be provided to the next frame builder.
@@ -671,6 +712,10 @@ impl DocumentOps {
 
 /// The unique id for WR resource identification.
 /// The namespace_id should start from 1.
+/// The longest time step, in seconds, that springs are advanced by in a
+/// single frame.
+const MAX_SPRING_STEP: f32 = 0.1;
+
 static NEXT_NAMESPACE_ID: AtomicUsize = AtomicUsize::new(1);
 
 #[cfg(any(feature = "capture", feature = "replay"))]
@@ -1141,10 +1186,9 @@ impl RenderBackend {
                             // notify the active recorder
                             if let Some(ref mut r) = self.recorder {
                                 let pipeline_id = doc.scene.root_pipeline_id.unwrap();
-                                let epoch =  doc.scene.pipeline_epochs[&pipeline_id];
-                                let pipeline = &doc.scene.pipelines[&pipeline_id];
+                                let (pipeline, epoch) = doc.scene.get_pipeline_and_epoch(pipeline_id).unwrap();
                                 let scene_msg = SceneMsg::SetDisplayList {
-                                    list_descriptor: pipeline.display_list.descriptor().clone(),
+                                    list_descriptor: pipeline.display_list().descriptor().clone(),
                                     epoch,
                                     pipeline_id,
                                     background: pipeline.background_color,
@@ -1157,7 +1201,7 @@ impl RenderBackend {
                                 r.write_payload(*frame_counter, &Payload::construct_data(
                                     epoch,
                                     pipeline_id,
-                                    pipeline.display_list.data(),
+                                    pipeline.display_list().data(),
                                 ));
                             }
                         }
@@ -1308,6 +1352,7 @@ impl RenderBackend {
                         view: doc.view.clone(),
                         font_instances: self.resource_cache.get_font_instances(),
                         output_pipelines: doc.output_pipelines.clone(),
+                        properties: doc.dynamic_properties.snapshot(),
                     });
                 }
             }
@@ -1423,11 +1468,16 @@ impl RenderBackend {
             scroll |= op.scroll;
         }
 
-        if doc.dynamic_properties.flush_pending_updates() {
+        if !doc.dynamic_properties.flush_pending_updates().is_empty() {
+            doc.scene.on_properties_changed(doc.dynamic_properties.last_change_set());
             doc.frame_is_valid = false;
             doc.hit_tester_is_valid = false;
         }
 
+        if render_frame {
+            doc.advance_springs();
+        }
+
         if !doc.can_render() {
             // TODO: this happens if we are building the first scene asynchronously and
             // scroll at the same time. we should keep track of the fact that we skipped
@@ -1611,9 +1661,9 @@ impl RenderBackend {
         for (_, doc) in &self.documents {
             let mut debug_doc = debug_server::TreeNode::new("document");
 
-            for (_, pipeline) in &doc.scene.pipelines {
+            for (_, pipeline) in doc.scene.iter_pipelines() {
                 let mut debug_dl = debug_server::TreeNode::new("display-list");
-                self.traverse_items(&mut pipeline.display_list.iter(), &mut debug_dl);
+                self.traverse_items(&mut pipeline.display_list().iter(), &mut debug_dl);
                 debug_doc.add_child(debug_dl);
             }
 
@@ -1656,6 +1706,7 @@ impl RenderBackend {
                 report.clip_stores += fb.clip_store.size_of(ops);
             }
             report.hit_testers += doc.hit_tester.size_of(ops);
+            report.display_lists += doc.scene.report_memory().total_bytes;
 
             doc.data_stores.report_memory(ops, &mut report)
         }
@@ -1858,6 +1909,7 @@ impl RenderBackend {
                 frame_builder: Some(FrameBuilder::empty()),
                 output_pipelines: FastHashSet::default(),
                 dynamic_properties: SceneProperties::new(),
+                last_spring_step_time: None,
                 hit_tester: None,
                 frame_is_valid: false,
                 hit_tester_is_valid: false,
@@ -1311,7 +1311,8 @@ impl RenderTask {
                     render_tasks.add(task)
                 }
                 FilterPrimitiveKind::Blur(ref blur) => {
-                    let blur_std_deviation = blur.radius * device_pixel_scale.0;
+                    let (radius_x, radius_y) = blur.std_deviation();
+                    let blur_std_deviation = DeviceSize::new(radius_x, radius_y) * device_pixel_scale.0;
                     let input_task_id = get_task_input(
                         &blur.input,
                         filter_primitives,
@@ -1331,7 +1332,7 @@ impl RenderTask {
                     );
 
                     RenderTask::new_blur(
-                        DeviceSize::new(blur_std_deviation, blur_std_deviation),
+                        blur_std_deviation,
                         render_tasks.add(svg_task),
                         render_tasks,
                         RenderTargetKind::Color,
@@ -2,12 +2,22 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{BuiltDisplayList, ColorF, DynamicProperties, Epoch};
-use api::{FilterOp, TempFilterData, FilterData, FilterPrimitive, ComponentTransferFuncType};
+use api::{BorderRadius, BuiltDisplayList, ColorF, DisplayItem, DynamicProperties, Epoch, PremultipliedColorF};
+use api::{AuxIter, FilterOp, TempFilterData, FilterData, FilterPrimitive, ComponentTransferFuncType};
+use api::{FilterPrimitiveInput, FilterPrimitiveKind, PropertyValue};
 use api::{PipelineId, PropertyBinding, PropertyBindingId, ItemRange, MixBlendMode, StackingContext};
-use api::units::{LayoutSize, LayoutTransform};
-use crate::internal_types::{FastHashMap, Filter};
-use std::sync::Arc;
+use api::{BlurPrimitive, FloodPrimitive, GradientStop, GradientStopBinding};
+use api::{DisplayListBuilder, DisplayListRoots, ExternalScrollId, IframeDisplayItem, ReferenceFrameKind};
+use api::{RasterSpace, ScrollSensitivity, SpaceAndClipInfo, SpatialId, TransformStyle};
+use api::units::{LayoutPoint, LayoutRect, LayoutSize, LayoutTransform, LayoutVector2D};
+use crate::internal_types::{FastHashMap, FastHashSet, Filter, FilterChain};
+use crate::util::{DecomposedTransform, MatrixHelpers};
+use std::any::Any;
+use std::cell::{Cell, Ref, RefCell};
+#[cfg(feature = "debug_properties")]
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::{Arc, Weak};
 
 /// Stores a map of the animated property bindings for the current display list. These
 /// can be used to animate the transform and/or opacity of a display list without
@@ -15,19 +25,98 @@ use std::sync::Arc;
 #[cfg_attr(feature = "capture", derive(Serialize))]
 #[cfg_attr(feature = "replay", derive(Deserialize))]
 pub struct SceneProperties {
-    transform_properties: FastHashMap<PropertyBindingId, LayoutTransform>,
-    float_properties: FastHashMap<PropertyBindingId, f32>,
+    resolved: RefCell<Arc<ResolvedProperties>>,
     current_properties: DynamicProperties,
     pending_properties: Option<DynamicProperties>,
+    /// The transform applied to the root reference frame, or None for the
+    /// identity. See `set_global_transform`.
+    #[cfg_attr(feature = "replay", serde(default))]
+    global_transform: Option<LayoutTransform>,
+    #[cfg_attr(feature = "replay", serde(default))]
+    pending_global_transform: Option<LayoutTransform>,
+    /// If true, flush_pending_updates doesn't rebuild the resolved maps,
+    /// and they are instead rebuilt by the first resolve after a flush.
+    deferred_rebuild: bool,
+    /// True if the resolved maps are out of date with respect to
+    /// current_properties.
+    resolved_dirty: Cell<bool>,
+    /// True if the resolved maps hold exactly what `rebuild_if_dirty`
+    /// builds from current_properties. A flush can then update the maps in
+    /// place with just the entries that changed, rather than rebuild them.
+    #[cfg_attr(any(feature = "capture", feature = "replay"), serde(skip))]
+    resolved_in_place: Cell<bool>,
+    /// Float bindings whose value is driven by a spring, which override
+    /// any value supplied for them in the property list.
+    springs: FastHashMap<PropertyBindingId, Spring>,
+    /// The bindings changed by the last flush that changed anything.
+    #[cfg_attr(any(feature = "capture", feature = "replay"), serde(skip))]
+    last_change_set: PropertyChangeSet,
+    /// Bumped by every flush and spring step, which are what
+    /// change resolved values.
+    #[cfg(feature = "debug_properties")]
+    generation: u64,
+    /// The generation at which each binding last changed value.
+    #[cfg(feature = "debug_properties")]
+    last_changed_generations: FastHashMap<PropertyBindingId, u64>,
+    /// The property lists installed by the most recent flushes that
+    /// changed anything, oldest first.
+    #[cfg(feature = "debug_properties")]
+    history: VecDeque<DynamicProperties>,
 }
 
+/// The state of a `SceneProperties` at some point, to return to with
+/// `SceneProperties::restore`. Cloning a snapshot doesn't copy anything.
+#[derive(Clone)]
+pub struct ScenePropertiesSnapshot {
+    resolved: Arc<ResolvedProperties>,
+    state: Arc<SavedPropertyState>,
+}
+
+#[derive(Clone)]
+struct SavedPropertyState {
+    current_properties: DynamicProperties,
+    pending_properties: Option<DynamicProperties>,
+    global_transform: Option<LayoutTransform>,
+    pending_global_transform: Option<LayoutTransform>,
+    springs: FastHashMap<PropertyBindingId, Spring>,
+}
+
+/// The number of flushed property lists kept for `SceneProperties::rewind`.
+#[cfg(feature = "debug_properties")]
+const PROPERTY_HISTORY_CAPACITY: usize = 32;
+
 impl SceneProperties {
     pub fn new() -> Self {
         SceneProperties {
-            transform_properties: FastHashMap::default(),
-            float_properties: FastHashMap::default(),
+            resolved: RefCell::new(Arc::new(ResolvedProperties::default())),
             current_properties: DynamicProperties::default(),
             pending_properties: None,
+            global_transform: None,
+            pending_global_transform: None,
+            deferred_rebuild: false,
+            resolved_dirty: Cell::new(false),
+            resolved_in_place: Cell::new(true),
+            springs: FastHashMap::default(),
+            last_change_set: PropertyChangeSet::default(),
+            #[cfg(feature = "debug_properties")]
+            generation: 0,
+            #[cfg(feature = "debug_properties")]
+            last_changed_generations: FastHashMap::default(),
+            #[cfg(feature = "debug_properties")]
+            history: VecDeque::with_capacity(PROPERTY_HISTORY_CAPACITY),
+        }
+    }
+
+    /// Enable or disable deferred mode. In deferred mode, flushing only
+    /// records the new property list, and the maps used to resolve
+    /// bindings are rebuilt lazily by the first resolve that follows.
+    /// This avoids redundant work when there are several flushes for
+    /// each time the properties are resolved. The resolved values are
+    /// the same in either mode.
+    pub fn set_deferred_rebuild(&mut self, deferred: bool) {
+        self.deferred_rebuild = deferred;
+        if !deferred {
+            self.rebuild_if_dirty();
         }
     }
 
@@ -36,50 +125,362 @@ impl SceneProperties {
         self.pending_properties = Some(properties);
     }
 
-    /// Add to the current property list for this display list.
+    /// Set a transform to apply to the whole document, e.g. to zoom it
+    /// without changing its display lists. It is applied once, after the
+    /// transform of the root reference frame, so that everything below the
+    /// root gets it exactly once. Resolving a binding doesn't include it.
+    /// Like property lists, it takes effect at the next flush, which
+    /// reports it as a change to the transforms. It isn't a binding, so it
+    /// isn't in the flush's change set. The identity removes it.
+    pub fn set_global_transform(&mut self, transform: LayoutTransform) {
+        self.pending_global_transform = Some(transform);
+    }
+
+    /// Get the global transform as of the last flush. See
+    /// `set_global_transform`.
+    pub fn global_transform(&self) -> LayoutTransform {
+        self.global_transform.unwrap_or_else(LayoutTransform::identity)
+    }
+
+    /// Add to the current property list for this display list. A binding
+    /// that is already in the pending list has its value replaced, so
+    /// adding the same bindings every frame doesn't grow the list.
     pub fn add_properties(&mut self, properties: DynamicProperties) {
         let mut pending_properties = self.pending_properties
             .take()
             .unwrap_or_default();
 
-        pending_properties.transforms.extend(properties.transforms);
-        pending_properties.floats.extend(properties.floats);
+        merge_dynamic_properties(&mut pending_properties, properties);
+
+        self.pending_properties = Some(pending_properties);
+    }
+
+    /// Update the values of the bindings in `properties`, keeping every
+    /// other binding of the current property list, and drop the bindings
+    /// in `properties.removed`. Unlike `add_properties`, this doesn't need
+    /// a full list to have been set first. The update applies on top of
+    /// the pending list if there is one, so that bindings set or dropped
+    /// earlier in the transaction stay that way, and on top of the current
+    /// list otherwise. If every value is the one the binding already has,
+    /// the next flush reports no change.
+    pub fn update_properties(&mut self, mut properties: DynamicProperties) {
+        let mut pending_properties = match self.pending_properties.take() {
+            Some(pending_properties) => pending_properties,
+            None => self.current_properties.clone(),
+        };
+
+        let removed = mem::replace(&mut properties.removed, Vec::new());
+        merge_dynamic_properties(&mut pending_properties, properties);
+
+        if !removed.is_empty() {
+            let removed: FastHashSet<_> = removed.into_iter().collect();
+            pending_properties.transforms.retain(|property| !removed.contains(&property.key.id));
+            pending_properties.floats.retain(|property| !removed.contains(&property.key.id));
+            pending_properties.colors.retain(|property| !removed.contains(&property.key.id));
+            pending_properties.ints.retain(|property| !removed.contains(&property.key.id));
+            pending_properties.points.retain(|property| !removed.contains(&property.key.id));
+        }
 
         self.pending_properties = Some(pending_properties);
     }
 
     /// Flush any pending updates to the scene properties. Returns
-    /// true if the properties have changed since the last flush
-    /// was called. This code allows properties to be changed by
+    /// the kinds of properties that have changed since the last flush
+    /// was called, which is empty if none have. This code allows properties to be changed by
     /// multiple set_properties and add_properties calls during a
     /// single transaction, and still correctly determine if any
     /// properties have changed. This can have significant power
     /// saving implications, allowing a frame build to be skipped
     /// if the properties haven't changed in many cases.
-    pub fn flush_pending_updates(&mut self) -> bool {
-        let mut properties_changed = false;
+    pub fn flush_pending_updates(&mut self) -> PropertyDirtyFlags {
+        self.flush_pending_updates_detailed().kinds
+    }
 
-        if let Some(ref pending_properties) = self.pending_properties {
-            if *pending_properties != self.current_properties {
-                self.transform_properties.clear();
-                self.float_properties.clear();
+    /// Like `flush_pending_updates`, but also returns the bindings whose
+    /// values changed, for invalidating only what depends on them. A
+    /// binding that is missing from the new property list counts as
+    /// changed, and one that is set to the value it already had doesn't.
+    pub fn flush_pending_updates_detailed(&mut self) -> ChangedProperties {
+        let mut changed = ChangedProperties::default();
+        #[cfg(feature = "debug_properties")]
+        {
+            self.generation += 1;
+        }
 
-                for property in &pending_properties.transforms {
-                    self.transform_properties
-                        .insert(property.key.id, property.value);
-                }
+        if let Some(transform) = self.pending_global_transform.take() {
+            let global_transform = if transform == LayoutTransform::identity() {
+                None
+            } else {
+                Some(transform)
+            };
+            if global_transform != self.global_transform {
+                self.global_transform = global_transform;
+                changed.kinds |= PropertyDirtyFlags::TRANSFORMS;
+            }
+        }
+
+        let pending_changed = match self.pending_properties {
+            Some(ref pending_properties) => *pending_properties != self.current_properties,
+            None => false,
+        };
+
+        if pending_changed {
+            // The flags, the change set and the in place update of the
+            // resolved maps all come from this one diff.
+            let pending_properties = self.pending_properties.clone().unwrap();
+            let diff = PropertiesDiff::between(&self.current_properties, &pending_properties);
+            changed.kinds |= diff.kinds();
+            changed.change_set = diff.change_set();
+            if !changed.change_set.is_empty() {
+                self.last_change_set = changed.change_set.clone();
+            }
+            #[cfg(feature = "debug_properties")]
+            for id in &changed.change_set.bindings {
+                self.last_changed_generations.insert(*id, self.generation);
+            }
+
+            if !self.update_resolved_in_place(&diff) {
+                self.resolved_dirty.set(true);
+            }
+            self.current_properties = pending_properties;
 
-                for property in &pending_properties.floats {
-                    self.float_properties
-                        .insert(property.key.id, property.value);
+            #[cfg(feature = "debug_properties")]
+            {
+                if self.history.len() == PROPERTY_HISTORY_CAPACITY {
+                    self.history.pop_front();
                 }
+                self.history.push_back(self.current_properties.clone());
+            }
+
+            if !self.deferred_rebuild {
+                self.rebuild_if_dirty();
+            }
+        }
+
+        changed
+    }
+
+    /// Get the bindings that changed value or were removed in the last
+    /// flush that reported a change.
+    pub fn last_change_set(&self) -> &PropertyChangeSet {
+        &self.last_change_set
+    }
+
+    /// Drive a float binding with a damped spring towards `target`. If the
+    /// binding isn't already driven by a spring, the spring starts at rest
+    /// at the binding's current value (or at `target` if it has none).
+    /// Otherwise the spring keeps its current value and velocity, and only
+    /// its target and parameters change. The spring's value overrides any
+    /// value supplied for the binding until `clear_spring` is called.
+    pub fn set_spring_target(
+        &mut self,
+        id: PropertyBindingId,
+        target: f32,
+        stiffness: f32,
+        damping: f32,
+    ) {
+        let current = self.resolved().floats.get(&id).cloned().unwrap_or(target);
+        let spring = self.springs.entry(id).or_insert(Spring {
+            value: current,
+            velocity: 0.0,
+            target,
+            stiffness,
+            damping,
+        });
+        spring.target = target;
+        spring.stiffness = stiffness;
+        spring.damping = damping;
+    }
 
-                self.current_properties = pending_properties.clone();
-                properties_changed = true;
+    /// Stop driving a float binding with a spring, so that it resolves to
+    /// the value in the property list again. Returns true if the binding
+    /// was driven by a spring, in which case the frame needs to be rebuilt.
+    pub fn clear_spring(&mut self, id: PropertyBindingId) -> bool {
+        if self.springs.remove(&id).is_none() {
+            return false;
+        }
+        self.resolved_dirty.set(true);
+        #[cfg(feature = "debug_properties")]
+        {
+            self.generation += 1;
+            self.last_changed_generations.insert(id, self.generation);
+        }
+        true
+    }
+
+    /// Returns true if any float binding is driven by a spring.
+    pub fn has_springs(&self) -> bool {
+        !self.springs.is_empty()
+    }
+
+    /// Advance all springs by `dt` seconds. Returns true if any spring
+    /// value changed, in which case the frame needs to be rebuilt, just as
+    /// when flush_pending_updates returns true. Springs that come within
+    /// SPRING_REST_EPSILON of their target, with a similarly small
+    /// velocity, snap to it and stop moving.
+    pub fn advance_springs(&mut self, dt: f32) -> bool {
+        self.rebuild_if_dirty();
+        #[cfg(feature = "debug_properties")]
+        {
+            self.generation += 1;
+        }
+        let mut changed = false;
+        for (_id, spring) in &mut self.springs {
+            if spring.advance(dt) {
+                #[cfg(feature = "debug_properties")]
+                self.last_changed_generations.insert(*_id, self.generation);
+                changed = true;
+            }
+        }
+
+        // Only touch the resolved maps if something moved, so that an
+        // outstanding snapshot isn't copied for nothing.
+        if changed {
+            let resolved = Arc::make_mut(self.resolved.get_mut());
+            for (id, spring) in &self.springs {
+                resolved.floats.insert(*id, spring.value);
             }
         }
 
-        properties_changed
+        changed
+    }
+
+    /// Rebuild the resolved maps from current_properties, if they are
+    /// out of date.
+    fn rebuild_if_dirty(&self) {
+        if !self.resolved_dirty.get() {
+            return;
+        }
+
+        // The maps are rebuilt in place unless a snapshot still shares
+        // them, in which case the snapshot keeps the old ones.
+        let mut resolved = self.resolved.borrow_mut();
+        if Arc::get_mut(&mut resolved).is_none() {
+            *resolved = Arc::new(ResolvedProperties::default());
+        }
+        let resolved = Arc::get_mut(&mut resolved).unwrap();
+        resolved.transforms.clear();
+        resolved.floats.clear();
+        resolved.colors.clear();
+        resolved.ints.clear();
+        resolved.points.clear();
+
+        for property in &self.current_properties.transforms {
+            resolved.transforms
+                .insert(property.key.id, property.value);
+        }
+
+        for property in &self.current_properties.floats {
+            resolved.floats
+                .insert(property.key.id, property.value);
+        }
+
+        for property in &self.current_properties.colors {
+            resolved.colors
+                .insert(property.key.id, property.value);
+        }
+
+        for property in &self.current_properties.ints {
+            resolved.ints
+                .insert(property.key.id, property.value);
+        }
+
+        for property in &self.current_properties.points {
+            resolved.points
+                .insert(property.key.id, property.value);
+        }
+
+        self.resolved_in_place.set(true);
+
+        for (id, spring) in &self.springs {
+            resolved.floats.insert(*id, spring.value);
+        }
+
+        self.resolved_dirty.set(false);
+    }
+
+    /// Apply `diff`, taken from current_properties to the new property
+    /// list, to the resolved maps in place, touching only the entries of
+    /// the bindings that were added, changed or removed. This is only
+    /// possible if the maps hold what a rebuild from current_properties
+    /// would give, and isn't done in deferred mode, which leaves all of
+    /// the work to the next resolve. Returns false, leaving everything as
+    /// is, otherwise, and the maps must then be rebuilt. The result is the
+    /// same as a rebuild from the new list.
+    fn update_resolved_in_place(&mut self, diff: &PropertiesDiff) -> bool {
+        if self.deferred_rebuild || self.resolved_dirty.get() || !self.resolved_in_place.get() {
+            return false;
+        }
+        if diff.kinds().is_empty() {
+            return true;
+        }
+
+        let resolved = Arc::make_mut(self.resolved.get_mut());
+        diff.transforms.apply(&mut resolved.transforms);
+        diff.floats.apply(&mut resolved.floats);
+        diff.colors.apply(&mut resolved.colors);
+        diff.ints.apply(&mut resolved.ints);
+        diff.points.apply(&mut resolved.points);
+
+        for (id, spring) in &self.springs {
+            resolved.floats.insert(*id, spring.value);
+        }
+        true
+    }
+
+    fn resolved(&self) -> Ref<Arc<ResolvedProperties>> {
+        self.rebuild_if_dirty();
+        self.resolved.borrow()
+    }
+
+    /// Get an immutable snapshot of the current value of each binding,
+    /// which can be sent to another thread and resolved against without
+    /// locking. Later flushes and spring steps don't change the snapshot:
+    /// while it is alive, the next change to the resolved values copies
+    /// them instead of updating them in place. Holding on to snapshots
+    /// therefore costs a copy of the maps per change, plus the memory of
+    /// every snapshot still alive, so they should be dropped once the
+    /// frame they were taken for is built.
+    pub fn snapshot_arc(&self) -> Arc<ResolvedProperties> {
+        Arc::clone(&self.resolved())
+    }
+
+    /// Record the current and pending property lists, the global
+    /// transform, the springs and the resolved values, so that they can be
+    /// reinstated with `restore`. The resolved values are shared with the
+    /// properties, as with `snapshot_arc`, and the rest is copied.
+    pub fn snapshot(&self) -> ScenePropertiesSnapshot {
+        ScenePropertiesSnapshot {
+            resolved: self.snapshot_arc(),
+            state: Arc::new(SavedPropertyState {
+                current_properties: self.current_properties.clone(),
+                pending_properties: self.pending_properties.clone(),
+                global_transform: self.global_transform,
+                pending_global_transform: self.pending_global_transform,
+                springs: self.springs.clone(),
+            }),
+        }
+    }
+
+    /// Reinstate the state recorded by `snapshot`, so that bindings
+    /// resolve as they did when it was taken and the next flush applies
+    /// the updates that were pending then. Like `rewind`, this isn't
+    /// reported as a change by any flush, and the change set of the last
+    /// flush, the deferred rebuild mode and the debug history are left as
+    /// they are.
+    pub fn restore(&mut self, snapshot: ScenePropertiesSnapshot) {
+        let state = Arc::try_unwrap(snapshot.state).unwrap_or_else(|state| (*state).clone());
+        self.current_properties = state.current_properties;
+        self.pending_properties = state.pending_properties;
+        self.global_transform = state.global_transform;
+        self.pending_global_transform = state.pending_global_transform;
+        self.springs = state.springs;
+
+        // The snapshot's maps may hold spring values, so the next
+        // change rebuilds them.
+        *self.resolved.get_mut() = snapshot.resolved;
+        self.resolved_dirty.set(false);
+        self.resolved_in_place.set(false);
     }
 
     /// Get the current value for a transform property.
@@ -87,15 +488,126 @@ impl SceneProperties {
         &self,
         property: &PropertyBinding<LayoutTransform>,
     ) -> LayoutTransform {
-        match *property {
-            PropertyBinding::Value(value) => value,
-            PropertyBinding::Binding(ref key, v) => {
-                self.transform_properties
-                    .get(&key.id)
-                    .cloned()
-                    .unwrap_or(v)
-            }
+        self.resolved().resolve_layout_transform(property)
+    }
+
+    /// Get the current value for a transform property applied about an
+    /// origin, which is translate(origin) * transform * translate(-origin),
+    /// with both bindings resolved.
+    pub fn resolve_transform_about_origin(
+        &self,
+        transform: &PropertyBinding<LayoutTransform>,
+        origin: &PropertyBinding<LayoutPoint>,
+    ) -> LayoutTransform {
+        let origin = self.resolve_point(origin);
+        LayoutTransform::create_translation(-origin.x, -origin.y, 0.0)
+            .post_mul(&self.resolve_layout_transform(transform))
+            .post_mul(&LayoutTransform::create_translation(origin.x, origin.y, 0.0))
+    }
+
+    /// Get the current value for a transform property, along with whether
+    /// it has a real value: false if it is a binding with no value yet, so
+    /// that the result is its default. Hit testing uses this to ignore
+    /// nodes whose animated transform hasn't arrived. Literal values count
+    /// as real.
+    pub fn try_resolve_layout_transform(
+        &self,
+        property: &PropertyBinding<LayoutTransform>,
+    ) -> (LayoutTransform, bool) {
+        self.resolved().try_resolve_layout_transform(property)
+    }
+
+    /// Resolve each of `bindings` as `resolve_layout_transform` would,
+    /// replacing the contents of `out` with the results in order.
+    pub fn resolve_layout_transforms(
+        &self,
+        bindings: &[PropertyBinding<LayoutTransform>],
+        out: &mut Vec<LayoutTransform>,
+    ) {
+        let resolved = self.resolved();
+        out.clear();
+        out.extend(bindings.iter().map(|binding| resolved.resolve_layout_transform(binding)));
+    }
+
+    /// Resolve a transform property once and apply it to each of `points`,
+    /// writing the results to the same positions in `out`, which should be
+    /// the same length as `points`; only as many points as both slices
+    /// hold are transformed. Each result is the one
+    /// `LayoutTransform::transform_point2d` gives for the point. Returns
+    /// false if any point falls behind the viewer, where that would give
+    /// None; the values written for those points are meaningless.
+    pub fn resolve_and_transform_points(
+        &self,
+        property: &PropertyBinding<LayoutTransform>,
+        points: &[LayoutPoint],
+        out: &mut [LayoutPoint],
+    ) -> bool {
+        debug_assert_eq!(points.len(), out.len());
+        let m = self.resolve_layout_transform(property);
+
+        // Branch free, so that the loop can be vectorized.
+        let mut all_projected = true;
+        for (point, out) in points.iter().zip(out.iter_mut()) {
+            let w = point.x * m.m14 + point.y * m.m24 + m.m44;
+            let x = point.x * m.m11 + point.y * m.m21 + m.m41;
+            let y = point.x * m.m12 + point.y * m.m22 + m.m42;
+            *out = LayoutPoint::new(x / w, y / w);
+            all_projected &= w > 0.0;
+        }
+        all_projected
+    }
+
+    /// Get the product of a chain of transform properties, in the order
+    /// they would be written in a CSS transform list: the result is
+    /// `bindings[0] * bindings[1] * ... * bindings[n - 1]`, so the last
+    /// transform is the first to be applied to a point. Bindings that
+    /// have no value yet use their default. An empty chain is the
+    /// identity.
+    pub fn resolve_transform_chain(
+        &self,
+        bindings: &[PropertyBinding<LayoutTransform>],
+    ) -> LayoutTransform {
+        bindings.iter().fold(LayoutTransform::identity(), |chain, binding| {
+            chain.pre_mul(&self.resolve_layout_transform(binding))
+        })
+    }
+
+    /// Get the current value for a transform property, with its X and Y
+    /// translation rounded to the nearest device pixel at `device_scale`
+    /// device pixels per layout pixel. Snapping only happens when the
+    /// transform is an axis-aligned scale and translation: if it rotates,
+    /// skews, has any 3D component or a perspective, snapping would make
+    /// content shimmer as it moves, so the transform is returned as is.
+    /// It is also returned as is for a non-positive `device_scale`.
+    pub fn resolve_layout_transform_snapped(
+        &self,
+        property: &PropertyBinding<LayoutTransform>,
+        device_scale: f32,
+    ) -> LayoutTransform {
+        let mut transform = self.resolve_layout_transform(property);
+
+        let is_scale_translation =
+            transform.m12 == 0.0 && transform.m13 == 0.0 &&
+            transform.m21 == 0.0 && transform.m23 == 0.0 &&
+            transform.m31 == 0.0 && transform.m32 == 0.0 &&
+            !transform.has_perspective_component();
+        if is_scale_translation && device_scale > 0.0 {
+            transform.m41 = (transform.m41 * device_scale).round() / device_scale;
+            transform.m42 = (transform.m42 * device_scale).round() / device_scale;
         }
+
+        transform
+    }
+
+    /// Get the current value for a transform property, split into its
+    /// translation, rotation and scale. Returns None if the resolved
+    /// matrix can't be decomposed (e.g. it contains shear or perspective).
+    /// See DecomposedTransform::from_transform for the tolerances used.
+    pub fn resolve_layout_transform_decomposed(
+        &self,
+        property: &PropertyBinding<LayoutTransform>,
+    ) -> Option<DecomposedTransform> {
+        DecomposedTransform::from_transform(&self.resolve_layout_transform(property))
     }
 
     /// Get the current value for a float property.
@@ -103,170 +615,6086 @@ impl SceneProperties {
         &self,
         property: &PropertyBinding<f32>
     ) -> f32 {
-        match *property {
-            PropertyBinding::Value(value) => value,
-            PropertyBinding::Binding(ref key, v) => {
-                self.float_properties
-                    .get(&key.id)
-                    .cloned()
-                    .unwrap_or(v)
+        self.resolved().resolve_float(property)
+    }
+
+    /// Resolve each of `bindings` as `resolve_float` would, replacing the
+    /// contents of `out` with the results in order.
+    pub fn resolve_floats(&self, bindings: &[PropertyBinding<f32>], out: &mut Vec<f32>) {
+        let resolved = self.resolved();
+        out.clear();
+        out.extend(bindings.iter().map(|binding| resolved.resolve_float(binding)));
+    }
+
+    /// Get the current value for a float property driven over `[0, 1]`,
+    /// quantized into `steps` equal buckets so that movement within a
+    /// bucket doesn't change the result. The value is clamped to `[0, 1]`
+    /// first. Bucket `i` covers `[i / steps, (i + 1) / steps)`, i.e. the
+    /// value is floored into a bucket, with 1.0 falling in the last one.
+    /// Each bucket resolves to `i / (steps - 1)`, so the first and last
+    /// buckets resolve to exactly 0 and 1. A single step always resolves
+    /// to 0, and zero steps leave the clamped value unquantized.
+    pub fn resolve_float_stepped(
+        &self,
+        property: &PropertyBinding<f32>,
+        steps: u32,
+    ) -> f32 {
+        let value = self.resolve_float(property).max(0.0).min(1.0);
+        match steps {
+            0 => value,
+            1 => 0.0,
+            _ => {
+                let bucket = ((value * steps as f32) as u32).min(steps - 1);
+                bucket as f32 / (steps - 1) as f32
             }
         }
     }
 
-    pub fn float_properties(&self) -> &FastHashMap<PropertyBindingId, f32> {
-        &self.float_properties
+    /// Get the current value for a float property that loops, such as an
+    /// angle or a hue, wrapped into `[0, period)`. Negative values wrap
+    /// around to the top of the range, so -90 with a period of 360 is 270.
+    /// Wrapping keeps ever-growing animated values small enough not to
+    /// lose precision when they are used. A `period` that isn't positive
+    /// has no range to wrap into, so the value is returned as is.
+    pub fn resolve_float_wrapped(
+        &self,
+        property: &PropertyBinding<f32>,
+        period: f32,
+    ) -> f32 {
+        let value = self.resolve_float(property);
+        if period.is_nan() || period <= 0.0 {
+            return value;
+        }
+
+        let mut wrapped = value % period;
+        if wrapped < 0.0 {
+            wrapped += period;
+        }
+        // Adding the period to a tiny negative remainder can round up to
+        // the period itself, which is outside the range.
+        if wrapped >= period {
+            wrapped = 0.0;
+        }
+        wrapped
     }
-}
 
-/// A representation of the layout within the display port for a given document or iframe.
-#[cfg_attr(feature = "capture", derive(Serialize))]
-#[cfg_attr(feature = "replay", derive(Deserialize))]
-#[derive(Clone)]
-pub struct ScenePipeline {
-    pub pipeline_id: PipelineId,
-    pub viewport_size: LayoutSize,
-    pub content_size: LayoutSize,
-    pub background_color: Option<ColorF>,
-    pub display_list: BuiltDisplayList,
-}
+    /// Get the current corner radii of a rounded rect, from the eight
+    /// radius components in the order top-left width and height, then
+    /// top-right, bottom-left and bottom-right. Negative radii are clamped
+    /// to zero. Radii that overlap within the rect are not scaled down
+    /// here, since that depends on the rect size; that normalization is
+    /// left to `ensure_no_corner_overlap` when the clip is built.
+    pub fn resolve_border_radius(
+        &self,
+        radii: &[PropertyBinding<f32>; 8],
+    ) -> BorderRadius {
+        let component = |index: usize| self.resolve_float(&radii[index]).max(0.0);
+        let corner = |index: usize| LayoutSize::new(component(index), component(index + 1));
 
-/// A complete representation of the layout bundling visible pipelines together.
-#[cfg_attr(feature = "capture", derive(Serialize))]
-#[cfg_attr(feature = "replay", derive(Deserialize))]
-#[derive(Clone)]
-pub struct Scene {
-    pub root_pipeline_id: Option<PipelineId>,
-    pub pipelines: FastHashMap<PipelineId, Arc<ScenePipeline>>,
-    pub pipeline_epochs: FastHashMap<PipelineId, Epoch>,
-}
+        BorderRadius {
+            top_left: corner(0),
+            top_right: corner(2),
+            bottom_left: corner(4),
+            bottom_right: corner(6),
+        }
+    }
 
-impl Scene {
-    pub fn new() -> Self {
-        Scene {
-            root_pipeline_id: None,
-            pipelines: FastHashMap::default(),
-            pipeline_epochs: FastHashMap::default(),
+    /// Get the current value for an opacity property, composed with the
+    /// opacity inherited from enclosing stacking contexts. The resolved
+    /// value is clamped to [0, 1] before being multiplied in, and a fully
+    /// transparent inherited opacity always resolves to 0.
+    pub fn resolve_opacity(
+        &self,
+        property: &PropertyBinding<f32>,
+        inherited: f32,
+    ) -> f32 {
+        if inherited <= 0.0 {
+            return 0.0;
         }
+
+        self.resolve_float(property).max(0.0).min(1.0) * inherited
     }
 
-    pub fn set_root_pipeline_id(&mut self, pipeline_id: PipelineId) {
-        self.root_pipeline_id = Some(pipeline_id);
+    /// Get the current value for an int property.
+    pub fn resolve_int(
+        &self,
+        property: &PropertyBinding<i32>
+    ) -> i32 {
+        self.resolved().resolve_int(property)
     }
 
-    pub fn set_display_list(
-        &mut self,
-        pipeline_id: PipelineId,
-        epoch: Epoch,
-        display_list: BuiltDisplayList,
-        background_color: Option<ColorF>,
-        viewport_size: LayoutSize,
-        content_size: LayoutSize,
-    ) {
-        let new_pipeline = ScenePipeline {
-            pipeline_id,
-            viewport_size,
-            content_size,
-            background_color,
-            display_list,
+    /// Get the current value for a point property.
+    pub fn resolve_point(
+        &self,
+        property: &PropertyBinding<LayoutPoint>
+    ) -> LayoutPoint {
+        self.resolved().resolve_point(property)
+    }
+
+    /// Get the current value for a color property.
+    pub fn resolve_color(
+        &self,
+        property: &PropertyBinding<ColorF>
+    ) -> ColorF {
+        self.resolved().resolve_color(property)
+    }
+
+    /// Get the current value for a color property, with the RGB components
+    /// multiplied by alpha. Each component is clamped to [0, 1] before the
+    /// multiplication, so colors slightly outside the unit range are
+    /// snapped to the edge of it rather than over or under saturating the
+    /// result. No quantization is applied. A fully transparent color always
+    /// resolves to (0, 0, 0, 0).
+    pub fn resolve_color_premultiplied(
+        &self,
+        property: &PropertyBinding<ColorF>
+    ) -> PremultipliedColorF {
+        let color = self.resolve_color(property);
+
+        ColorF::new(
+            color.r.max(0.0).min(1.0),
+            color.g.max(0.0).min(1.0),
+            color.b.max(0.0).min(1.0),
+            color.a.max(0.0).min(1.0),
+        ).premultiplied()
+    }
+
+    /// Get the current color of a flood filter primitive. The result is
+    /// clamped to the unit range, like a static flood color is when the
+    /// primitive is sanitized.
+    pub fn resolve_flood_color(&self, flood: &FloodPrimitive) -> ColorF {
+        let mut resolved = FloodPrimitive {
+            color: self.resolve_color(&flood.color_property()),
+            color_binding: None,
         };
+        resolved.sanitize();
+        resolved.color
+    }
 
-        self.pipelines.insert(pipeline_id, Arc::new(new_pipeline));
-        self.pipeline_epochs.insert(pipeline_id, epoch);
+    /// Get a blur filter primitive with its deviations along x and y
+    /// resolved, clamped to `[0, MAX_BLUR_RADIUS]`. The bindings are kept,
+    /// so the result can be resolved again in a later frame.
+    pub fn resolve_blur_primitive(&self, blur: &BlurPrimitive) -> BlurPrimitive {
+        let mut resolved = BlurPrimitive {
+            radius: self.resolve_float(&blur.radius_x_property()).max(0.0),
+            radius_y: Some(self.resolve_float(&blur.radius_y_property()).max(0.0)),
+            ..*blur
+        };
+        resolved.sanitize();
+        resolved
     }
 
-    pub fn remove_pipeline(&mut self, pipeline_id: PipelineId) {
-        if self.root_pipeline_id == Some(pipeline_id) {
-            self.root_pipeline_id = None;
+    /// Resolve a list of animated gradient stops. Offsets are clamped to
+    /// `[0, 1]`. If the animation has moved a stop past one of its
+    /// neighbours, the stops are re-sorted by offset; the sort is stable, so
+    /// stops that end up at the same offset keep their original order.
+    pub fn resolve_gradient_stops(&self, stops: &[GradientStopBinding]) -> Vec<GradientStop> {
+        let mut resolved: Vec<GradientStop> = stops
+            .iter()
+            .map(|stop| GradientStop {
+                offset: self.resolve_float(&stop.offset).max(0.0).min(1.0),
+                color: self.resolve_color(&stop.color),
+            })
+            .collect();
+        resolved.sort_by(|a, b| a.offset.partial_cmp(&b.offset).unwrap());
+        resolved
+    }
+
+    pub fn float_properties(&self) -> Ref<FastHashMap<PropertyBindingId, f32>> {
+        Ref::map(self.resolved(), |resolved| &resolved.floats)
+    }
+
+    /// Forget the flushed value of every binding that isn't in `live_ids`,
+    /// along with any spring driving it, so that bindings a scene stopped
+    /// referencing don't accumulate. `live_ids` is typically
+    /// `Scene::referenced_bindings` of the new scene, which the render
+    /// backend passes in when it swaps the scene in. Pending updates are
+    /// left alone.
+    pub fn retain_referenced(&mut self, live_ids: &FastHashSet<PropertyBindingId>) {
+        let properties = &mut self.current_properties;
+        let count = properties.transforms.len() + properties.floats.len() +
+            properties.colors.len() + properties.ints.len() + properties.points.len() +
+            self.springs.len();
+
+        properties.transforms.retain(|property| live_ids.contains(&property.key.id));
+        properties.floats.retain(|property| live_ids.contains(&property.key.id));
+        properties.colors.retain(|property| live_ids.contains(&property.key.id));
+        properties.ints.retain(|property| live_ids.contains(&property.key.id));
+        properties.points.retain(|property| live_ids.contains(&property.key.id));
+        self.springs.retain(|id, _| live_ids.contains(id));
+        #[cfg(feature = "debug_properties")]
+        self.last_changed_generations.retain(|id, _| live_ids.contains(id));
+
+        let retained = properties.transforms.len() + properties.floats.len() +
+            properties.colors.len() + properties.ints.len() + properties.points.len() +
+            self.springs.len();
+        if retained != count {
+            self.resolved_dirty.set(true);
         }
-        self.pipelines.remove(&pipeline_id);
-        self.pipeline_epochs.remove(&pipeline_id);
     }
 
-    pub fn update_epoch(&mut self, pipeline_id: PipelineId, epoch: Epoch) {
-        self.pipeline_epochs.insert(pipeline_id, epoch);
+    /// Get the bound transforms as of the last flush.
+    pub fn transform_properties(&self) -> Ref<FastHashMap<PropertyBindingId, LayoutTransform>> {
+        Ref::map(self.resolved(), |resolved| &resolved.transforms)
     }
 
-    pub fn has_root_pipeline(&self) -> bool {
-        if let Some(ref root_id) = self.root_pipeline_id {
-            return self.pipelines.contains_key(root_id);
+    /// Get the properties as of the last flush, not including any pending
+    /// updates.
+    pub fn current_properties(&self) -> &DynamicProperties {
+        &self.current_properties
+    }
+
+    /// Get the number of flushes and spring steps so far.
+    #[cfg(feature = "debug_properties")]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Get the generation at which the value of a binding last changed,
+    /// or None if it never has. Flushes that give a binding a new value or
+    /// remove it and spring steps both count as changes. Comparing
+    /// this against generation() distinguishes bindings that update every
+    /// frame from stale ones.
+    #[cfg(feature = "debug_properties")]
+    pub fn last_changed_generation(&self, id: PropertyBindingId) -> Option<u64> {
+        self.last_changed_generations.get(&id).cloned()
+    }
+
+    /// Get the number of flushed property lists that `rewind` can go back
+    /// to, including the current one.
+    #[cfg(feature = "debug_properties")]
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Reinstall the property list flushed `steps` changing flushes before
+    /// the most recent one, so that bindings resolve as they did when it
+    /// was live. Steps always count back from the most recent flush, not
+    /// from the previous rewind, and the history itself is left as is.
+    /// Pending updates are untouched and will replace the rewound state at
+    /// the next flush. Spring driven bindings keep their current spring
+    /// value. Returns false, changing nothing, if the history doesn't go
+    /// back that far.
+    #[cfg(feature = "debug_properties")]
+    pub fn rewind(&mut self, steps: usize) -> bool {
+        if steps >= self.history.len() {
+            return false;
         }
 
-        false
+        let index = self.history.len() - 1 - steps;
+        self.current_properties = self.history[index].clone();
+        self.resolved_dirty.set(true);
+        if !self.deferred_rebuild {
+            self.rebuild_if_dirty();
+        }
+        true
     }
-}
 
-pub trait StackingContextHelpers {
-    fn mix_blend_mode_for_compositing(&self) -> Option<MixBlendMode>;
-    fn filter_ops_for_compositing(
-        &self,
-        input_filters: ItemRange<FilterOp>,
-    ) -> Vec<Filter>;
-    fn filter_datas_for_compositing(
-        &self,
-        input_filter_datas: &[TempFilterData],
-    ) -> Vec<FilterData>;
-    fn filter_primitives_for_compositing(
+    /// Get the current value for a float property, and whether it has
+    /// changed since the last call with the same `marker`. The marker is
+    /// owned by the caller and is opaque: it should start out as zero, be
+    /// kept per call site, and only be passed back to this method, which
+    /// updates it on every call. A marker reports the changes counted by
+    /// `last_changed_generation` since it was last used, so a new marker
+    /// reports a change for any binding that has been given a value.
+    /// Literal values never report a change.
+    #[cfg(feature = "debug_properties")]
+    pub fn resolve_float_changed(
         &self,
-        input_filter_primitives: ItemRange<FilterPrimitive>,
-    ) -> Vec<FilterPrimitive>;
+        marker: &mut u64,
+        property: &PropertyBinding<f32>,
+    ) -> (f32, bool) {
+        let changed = match *property {
+            PropertyBinding::Value(..) => false,
+            PropertyBinding::Binding(ref key, _) => {
+                self.last_changed_generation(key.id)
+                    .map_or(false, |generation| generation > *marker)
+            }
+        };
+        *marker = self.generation;
+        (self.resolve_float(property), changed)
+    }
 }
 
-impl StackingContextHelpers for StackingContext {
-    fn mix_blend_mode_for_compositing(&self) -> Option<MixBlendMode> {
-        match self.mix_blend_mode {
-            MixBlendMode::Normal => None,
-            _ => Some(self.mix_blend_mode),
+/// Replace or append each of `values` into `into`, matching them by the
+/// binding id that `id` extracts.
+fn merge_by_id<T, F>(into: &mut Vec<T>, values: Vec<T>, id: F)
+where
+    F: Fn(&T) -> PropertyBindingId,
+{
+    if values.is_empty() {
+        return;
+    }
+
+    let mut indices = FastHashMap::default();
+    for (i, value) in into.iter().enumerate() {
+        indices.entry(id(value)).or_insert(i);
+    }
+    for value in values {
+        match indices.get(&id(&value)) {
+            Some(&i) => into[i] = value,
+            None => {
+                indices.insert(id(&value), into.len());
+                into.push(value);
+            }
         }
     }
+}
 
-    fn filter_ops_for_compositing(
+/// Replace or append the values of `properties`
+/// into `into`, by binding id. `properties.removed` is ignored.
+fn merge_dynamic_properties(into: &mut DynamicProperties, properties: DynamicProperties) {
+    merge_by_id(&mut into.transforms, properties.transforms, |property| property.key.id);
+    merge_by_id(&mut into.floats, properties.floats, |property| property.key.id);
+    merge_by_id(&mut into.colors, properties.colors, |property| property.key.id);
+    merge_by_id(&mut into.ints, properties.ints, |property| property.key.id);
+    merge_by_id(&mut into.points, properties.points, |property| property.key.id);
+}
+
+/// How the values of each kind of property differ between two property
+/// lists, by binding id. See `PropertiesDiff::between`.
+struct PropertiesDiff {
+    transforms: ValuesDiff<LayoutTransform>,
+    floats: ValuesDiff<f32>,
+    colors: ValuesDiff<ColorF>,
+    ints: ValuesDiff<i32>,
+    points: ValuesDiff<LayoutPoint>,
+}
+
+impl PropertiesDiff {
+    /// Diff the values of `new` against those of `old`.
+    fn between(old: &DynamicProperties, new: &DynamicProperties) -> Self {
+        PropertiesDiff {
+            transforms: ValuesDiff::between(&old.transforms, &new.transforms),
+            floats: ValuesDiff::between(&old.floats, &new.floats),
+            colors: ValuesDiff::between(&old.colors, &new.colors),
+            ints: ValuesDiff::between(&old.ints, &new.ints),
+            points: ValuesDiff::between(&old.points, &new.points),
+        }
+    }
+
+    /// Get the kinds of property with bindings that differ.
+    fn kinds(&self) -> PropertyDirtyFlags {
+        let mut flags = PropertyDirtyFlags::empty();
+        flags.set(PropertyDirtyFlags::TRANSFORMS, !self.transforms.is_empty());
+        flags.set(PropertyDirtyFlags::FLOATS, !self.floats.is_empty());
+        flags.set(PropertyDirtyFlags::COLORS, !self.colors.is_empty());
+        flags.set(PropertyDirtyFlags::INTS, !self.ints.is_empty());
+        flags.set(PropertyDirtyFlags::POINTS, !self.points.is_empty());
+        flags
+    }
+
+    /// Get the bindings that differ.
+    fn change_set(&self) -> PropertyChangeSet {
+        let mut bindings = FastHashSet::default();
+        self.transforms.add_ids(&mut bindings);
+        self.floats.add_ids(&mut bindings);
+        self.colors.add_ids(&mut bindings);
+        self.ints.add_ids(&mut bindings);
+        self.points.add_ids(&mut bindings);
+        PropertyChangeSet { bindings }
+    }
+
+}
+
+/// The bindings of one kind of property whose values differ between two
+/// lists. A binding listed more than once in a list has the last value it
+/// is given there, as in the resolved maps.
+struct ValuesDiff<T> {
+    /// The new values of the bindings that were added or changed value,
+    /// each listed once, in the order of the new list.
+    changed: Vec<PropertyValue<T>>,
+    /// The bindings only in the old list.
+    removed: Vec<PropertyBindingId>,
+}
+
+impl<T: Copy + PartialEq> ValuesDiff<T> {
+    fn between(old: &[PropertyValue<T>], new: &[PropertyValue<T>]) -> Self {
+        let old_values: FastHashMap<_, _> = old
+            .iter()
+            .map(|property| (property.key.id, property.value))
+            .collect();
+
+        // Walk the new list backwards, so that the last value of each
+        // binding is the one compared.
+        let mut new_ids = FastHashSet::default();
+        let mut changed: Vec<_> = new
+            .iter()
+            .rev()
+            .filter(|property| {
+                new_ids.insert(property.key.id) &&
+                    old_values.get(&property.key.id) != Some(&property.value)
+            })
+            .cloned()
+            .collect();
+        changed.reverse();
+
+        // Adding each removed binding to the set lists it only once.
+        let mut removed = Vec::new();
+        for property in old {
+            if new_ids.insert(property.key.id) {
+                removed.push(property.key.id);
+            }
+        }
+
+        ValuesDiff { changed, removed }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    fn add_ids(&self, ids: &mut FastHashSet<PropertyBindingId>) {
+        ids.extend(self.changed.iter().map(|property| property.key.id));
+        ids.extend(self.removed.iter().cloned());
+    }
+
+    /// Bring a resolved map built from the old list up to date with the
+    /// new one.
+    fn apply(&self, map: &mut FastHashMap<PropertyBindingId, T>) {
+        for id in &self.removed {
+            map.remove(id);
+        }
+        for property in &self.changed {
+            map.insert(property.key.id, property.value);
+        }
+    }
+}
+
+bitflags! {
+    /// The kinds of property whose values changed in a flush. Flags from
+    /// several flushes can be accumulated with `|=`.
+    #[derive(Default)]
+    pub struct PropertyDirtyFlags: u8 {
+        const TRANSFORMS = 1 << 0;
+        const FLOATS = 1 << 1;
+        const COLORS = 1 << 2;
+        const INTS = 1 << 3;
+        const POINTS = 1 << 4;
+    }
+}
+
+/// What changed in a flush, as returned by
+/// `SceneProperties::flush_pending_updates_detailed`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChangedProperties {
+    /// The kinds of property that changed.
+    pub kinds: PropertyDirtyFlags,
+    /// The bindings that changed value or were removed. Changes that
+    /// aren't to a binding, like a new global transform, are only in
+    /// `kinds`.
+    pub change_set: PropertyChangeSet,
+}
+
+impl ChangedProperties {
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+}
+
+/// The property bindings whose values changed between two property lists,
+/// including bindings that were removed.
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PropertyChangeSet {
+    pub bindings: FastHashSet<PropertyBindingId>,
+}
+
+impl PropertyChangeSet {
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+}
+
+/// The distance from its target, and the speed, below which a spring is
+/// considered to be at rest.
+const SPRING_REST_EPSILON: f32 = 1.0e-4;
+
+/// The state of a damped spring driving a float binding.
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[derive(Clone, Copy, Debug)]
+struct Spring {
+    value: f32,
+    velocity: f32,
+    target: f32,
+    stiffness: f32,
+    damping: f32,
+}
+
+impl Spring {
+    /// Integrate the spring over `dt` seconds with semi-implicit Euler,
+    /// which is stable for the small time steps of a frame. Returns true
+    /// if the value changed.
+    fn advance(&mut self, dt: f32) -> bool {
+        let old_value = self.value;
+
+        let acceleration = -self.stiffness * (self.value - self.target) - self.damping * self.velocity;
+        self.velocity += acceleration * dt;
+        self.value += self.velocity * dt;
+
+        if (self.value - self.target).abs() < SPRING_REST_EPSILON &&
+            self.velocity.abs() < SPRING_REST_EPSILON {
+            self.value = self.target;
+            self.velocity = 0.0;
+        }
+
+        self.value != old_value
+    }
+}
+
+/// The current value of each animated property binding, keyed by id. See
+/// `SceneProperties::snapshot_arc`.
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[derive(Clone, Default)]
+pub struct ResolvedProperties {
+    transforms: FastHashMap<PropertyBindingId, LayoutTransform>,
+    floats: FastHashMap<PropertyBindingId, f32>,
+    colors: FastHashMap<PropertyBindingId, ColorF>,
+    ints: FastHashMap<PropertyBindingId, i32>,
+    points: FastHashMap<PropertyBindingId, LayoutPoint>,
+}
+
+impl ResolvedProperties {
+    pub fn resolve_layout_transform(
         &self,
-        input_filters: ItemRange<FilterOp>,
-    ) -> Vec<Filter> {
-        // TODO(gw): Now that we resolve these later on,
-        //           we could probably make it a bit
-        //           more efficient than cloning these here.
-        let mut filters = vec![];
-        for filter in input_filters {
-            filters.push(filter.into());
+        property: &PropertyBinding<LayoutTransform>,
+    ) -> LayoutTransform {
+        self.try_resolve_layout_transform(property).0
+    }
+
+    /// See `SceneProperties::try_resolve_layout_transform`.
+    pub fn try_resolve_layout_transform(
+        &self,
+        property: &PropertyBinding<LayoutTransform>,
+    ) -> (LayoutTransform, bool) {
+        match *property {
+            PropertyBinding::Value(value) => (value, true),
+            PropertyBinding::Binding(ref key, v) => {
+                match self.transforms.get(&key.id) {
+                    Some(&value) => (value, true),
+                    None => (v, false),
+                }
+            }
         }
-        filters
     }
 
-    fn filter_datas_for_compositing(
+    pub fn resolve_float(
         &self,
-        input_filter_datas: &[TempFilterData],
-    ) -> Vec<FilterData> {
-        // TODO(gw): Now that we resolve these later on,
-        //           we could probably make it a bit
-        //           more efficient than cloning these here.
-        let mut filter_datas = vec![];
-        for temp_filter_data in input_filter_datas {
-            let func_types : Vec<ComponentTransferFuncType> = temp_filter_data.func_types.iter().collect();
-            debug_assert!(func_types.len() == 4);
-            filter_datas.push( FilterData {
-                func_r_type: func_types[0],
-                r_values: temp_filter_data.r_values.iter().collect(),
-                func_g_type: func_types[1],
-                g_values: temp_filter_data.g_values.iter().collect(),
-                func_b_type: func_types[2],
-                b_values: temp_filter_data.b_values.iter().collect(),
-                func_a_type: func_types[3],
-                a_values: temp_filter_data.a_values.iter().collect(),
-            });
+        property: &PropertyBinding<f32>,
+    ) -> f32 {
+        match *property {
+            PropertyBinding::Value(value) => value,
+            PropertyBinding::Binding(ref key, v) => {
+                self.floats.get(&key.id).cloned().unwrap_or(v)
+            }
         }
-        filter_datas
     }
 
-    fn filter_primitives_for_compositing(
+    pub fn resolve_color(
         &self,
-        input_filter_primitives: ItemRange<FilterPrimitive>,
-    ) -> Vec<FilterPrimitive> {
-        // Resolve these in the flattener?
-        // TODO(gw): Now that we resolve these later on,
-        //           we could probably make it a bit
-        //           more efficient than cloning these here.
-        input_filter_primitives.iter().map(|primitive| primitive.into()).collect()
+        property: &PropertyBinding<ColorF>,
+    ) -> ColorF {
+        match *property {
+            PropertyBinding::Value(value) => value,
+            PropertyBinding::Binding(ref key, v) => {
+                self.colors.get(&key.id).cloned().unwrap_or(v)
+            }
+        }
+    }
+
+    pub fn resolve_int(
+        &self,
+        property: &PropertyBinding<i32>,
+    ) -> i32 {
+        match *property {
+            PropertyBinding::Value(value) => value,
+            PropertyBinding::Binding(ref key, v) => {
+                self.ints.get(&key.id).cloned().unwrap_or(v)
+            }
+        }
+    }
+
+    pub fn resolve_point(
+        &self,
+        property: &PropertyBinding<LayoutPoint>,
+    ) -> LayoutPoint {
+        match *property {
+            PropertyBinding::Value(value) => value,
+            PropertyBinding::Binding(ref key, v) => {
+                self.points.get(&key.id).cloned().unwrap_or(v)
+            }
+        }
+    }
+}
+
+/// A representation of the layout within the display port for a given document or iframe.
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[derive(Clone)]
+pub struct ScenePipeline {
+    pub pipeline_id: PipelineId,
+    pub viewport_size: LayoutSize,
+    pub content_size: LayoutSize,
+    pub background_color: Option<ColorF>,
+    display_list: BuiltDisplayList,
+    /// Opaque embedder data attached to this pipeline. This is not
+    /// included in captures.
+    #[cfg_attr(any(feature = "capture", feature = "replay"), serde(skip))]
+    metadata: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl ScenePipeline {
+    pub fn display_list(&self) -> &BuiltDisplayList {
+        &self.display_list
+    }
+
+    pub fn set_display_list(&mut self, display_list: BuiltDisplayList) {
+        self.display_list = display_list;
+    }
+}
+
+/// Builds a `ScenePipeline` with named setters, to be added to a scene
+/// with `Scene::add_pipeline`. Unset fields default to zero sizes, no
+/// background color, an empty display list and no metadata.
+pub struct ScenePipelineBuilder {
+    pipeline_id: PipelineId,
+    viewport_size: LayoutSize,
+    content_size: LayoutSize,
+    background_color: Option<ColorF>,
+    display_list: Option<BuiltDisplayList>,
+    metadata: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl ScenePipelineBuilder {
+    pub fn new(pipeline_id: PipelineId) -> Self {
+        ScenePipelineBuilder {
+            pipeline_id,
+            viewport_size: LayoutSize::zero(),
+            content_size: LayoutSize::zero(),
+            background_color: None,
+            display_list: None,
+            metadata: None,
+        }
+    }
+
+    pub fn viewport_size(mut self, viewport_size: LayoutSize) -> Self {
+        self.viewport_size = viewport_size;
+        self
+    }
+
+    pub fn content_size(mut self, content_size: LayoutSize) -> Self {
+        self.content_size = content_size;
+        self
+    }
+
+    pub fn background_color(mut self, background_color: Option<ColorF>) -> Self {
+        self.background_color = background_color;
+        self
+    }
+
+    pub fn display_list(mut self, display_list: BuiltDisplayList) -> Self {
+        self.display_list = Some(display_list);
+        self
+    }
+
+    pub fn metadata(mut self, metadata: Option<Arc<dyn Any + Send + Sync>>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Build the pipeline.
+    pub fn build(self) -> ScenePipeline {
+        let (pipeline_id, content_size) = (self.pipeline_id, self.content_size);
+        let display_list = self.display_list.unwrap_or_else(|| {
+            DisplayListBuilder::new(pipeline_id, content_size).finalize().2
+        });
+        ScenePipeline {
+            pipeline_id: self.pipeline_id,
+            viewport_size: self.viewport_size,
+            content_size: self.content_size,
+            background_color: self.background_color,
+            display_list,
+            metadata: self.metadata,
+        }
+    }
+}
+
+/// The version of the format written by `Scene::serialize_versioned`. This
+/// must be bumped, and a migration added to `Scene::migrate`, whenever a
+/// change to `Scene` or `ScenePipeline` changes the serialized form.
+///
+/// - 1: The initial version.
+/// - 2: Added `ScenePipeline::item_count`.
+/// - 3: Removed `ScenePipeline::item_count`, making it the same as 1.
+pub const SCENE_SCHEMA_VERSION: u32 = 3;
+
+/// An error reading a scene written by `Scene::serialize_versioned`.
+#[cfg(feature = "replay")]
+#[derive(Debug)]
+pub enum SceneSchemaError {
+    /// The data is too short to hold a schema version.
+    MissingVersion,
+    /// The schema version is newer than this build understands, or there
+    /// is no migration from it.
+    UnsupportedVersion(u32),
+    /// The data doesn't match the schema it claims to have.
+    Malformed(String),
+}
+
+#[cfg(feature = "replay")]
+impl ::std::fmt::Display for SceneSchemaError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            SceneSchemaError::MissingVersion => write!(f, "the data has no schema version"),
+            SceneSchemaError::UnsupportedVersion(version) => write!(
+                f,
+                "expected schema version 1 to {}, found version {}",
+                SCENE_SCHEMA_VERSION,
+                version,
+            ),
+            SceneSchemaError::Malformed(ref err) => write!(f, "the data is malformed: {}", err),
+        }
+    }
+}
+
+/// An error hot-reloading a pipeline's display list with
+/// `Scene::hot_reload_pipeline`.
+#[cfg(feature = "replay")]
+#[derive(Debug)]
+pub enum HotReloadError {
+    /// The scene has no pipeline with this id to reload.
+    UnknownPipeline(PipelineId),
+    /// The display list file couldn't be read.
+    Io(::std::io::Error),
+    /// The file isn't a serialized display list.
+    Malformed(String),
+}
+
+/// An error importing a captured scene with `Scene::import_capture`.
+#[cfg(feature = "replay")]
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The capture couldn't be read.
+    Io(::std::io::Error),
+    /// The capture isn't a scene written by `Scene::serialize_versioned`
+    /// that this build can read.
+    Schema(SceneSchemaError),
+    /// Offsetting this pipeline's id would overflow it.
+    IdOverflow(PipelineId),
+}
+
+#[cfg(feature = "replay")]
+impl ::std::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            CaptureError::Io(ref err) => write!(f, "can't read the capture: {}", err),
+            CaptureError::Schema(ref err) => write!(f, "can't load the capture: {}", err),
+            CaptureError::IdOverflow(pipeline_id) => {
+                write!(f, "offsetting pipeline {:?} overflows its id", pipeline_id)
+            }
+        }
+    }
+}
+
+/// The form of a scene written by `Scene::serialize_structure`.
+#[cfg(any(feature = "capture", feature = "replay"))]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+struct SceneStructure {
+    root_pipeline_id: Option<PipelineId>,
+    pipelines: Vec<PipelineStructure>,
+    pipeline_epochs: FastHashMap<PipelineId, Epoch>,
+}
+
+/// A pipeline in a `SceneStructure`, with its display list summarized.
+#[cfg(any(feature = "capture", feature = "replay"))]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+struct PipelineStructure {
+    pipeline_id: PipelineId,
+    viewport_size: LayoutSize,
+    content_size: LayoutSize,
+    background_color: Option<ColorF>,
+    display_list_len: usize,
+    display_list_hash: u64,
+    item_count: usize,
+}
+
+/// Receives notifications of changes to a scene's pipelines. Each one is
+/// sent once the scene has been fully updated, so the observer sees a
+/// consistent scene. All methods do nothing by default.
+pub trait SceneObserver: Send + Sync {
+    /// A display list was set for a pipeline that wasn't in the scene.
+    fn on_pipeline_added(&self, _pipeline_id: PipelineId, _epoch: Epoch) {}
+    /// A pipeline was removed. The epoch is the one it had, or
+    /// `Epoch::invalid()` if it had none.
+    fn on_pipeline_removed(&self, _pipeline_id: PipelineId, _epoch: Epoch) {}
+    /// The display list of a pipeline already in the scene was replaced.
+    fn on_pipeline_updated(&self, _pipeline_id: PipelineId, _epoch: Epoch) {}
+    /// The root pipeline changed, either because a new one was set or
+    /// because the root was removed.
+    fn on_root_changed(&self, _root_pipeline_id: Option<PipelineId>) {}
+}
+
+/// What `Scene::add_pipeline` did with a pipeline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PipelineUpdate {
+    /// The pipeline was added, or its content replaced.
+    Applied,
+    /// The pipeline already had identical content, so only its epoch was
+    /// updated.
+    Unchanged,
+    /// The epoch policy ignored the update, so the scene is untouched.
+    RejectedStale,
+}
+
+/// How a scene checks the epochs installed for its pipelines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EpochPolicy {
+    /// Epochs may be installed in any order.
+    Free,
+    /// Installing an epoch older than the pipeline's current one is
+    /// ignored, so that a stale update arriving after a newer one doesn't
+    /// overwrite it. Resubmitting the current epoch is allowed, since
+    /// embedders that don't version their display lists send the same
+    /// epoch every time. `set_display_list` returns
+    /// `PipelineUpdate::RejectedStale` and `update_epoch` returns false
+    /// for the updates they ignore. Use `Scene::reset_epoch` for a
+    /// deliberate reset. The scene builder uses this policy for its
+    /// documents.
+    RejectStale,
+}
+
+/// A complete representation of the layout bundling visible pipelines together.
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct Scene {
+    pub root_pipeline_id: Option<PipelineId>,
+    pipelines: FastHashMap<PipelineId, Arc<ScenePipeline>>,
+    pipeline_epochs: FastHashMap<PipelineId, Epoch>,
+    /// Bumped by every call to on_properties_changed with a non-empty
+    /// change set.
+    #[cfg_attr(any(feature = "capture", feature = "replay"), serde(skip))]
+    property_generation: u64,
+    #[cfg_attr(any(feature = "capture", feature = "replay"), serde(skip, default = "default_epoch_policy"))]
+    epoch_policy: EpochPolicy,
+    /// Not carried over to clones, which would otherwise report changes
+    /// made to a scene the observer doesn't know about.
+    #[cfg_attr(any(feature = "capture", feature = "replay"), serde(skip))]
+    observer: Option<Box<dyn SceneObserver>>,
+    /// The data derived from each pipeline's display list, computed on
+    /// demand. Each entry holds a weak reference to the pipeline it was
+    /// computed for, so that replacing or mutating the pipeline makes it
+    /// stale.
+    #[cfg_attr(any(feature = "capture", feature = "replay"), serde(skip))]
+    derived_data: RefCell<FastHashMap<PipelineId, (Weak<ScenePipeline>, PipelineDerivedData)>>,
+}
+
+impl Clone for Scene {
+    fn clone(&self) -> Self {
+        Scene {
+            root_pipeline_id: self.root_pipeline_id,
+            pipelines: self.pipelines.clone(),
+            pipeline_epochs: self.pipeline_epochs.clone(),
+            property_generation: self.property_generation,
+            epoch_policy: self.epoch_policy,
+            observer: None,
+            derived_data: self.derived_data.clone(),
+        }
+    }
+}
+
+/// The data `Scene` caches for each pipeline's display list. See
+/// `Scene::with_derived_data`.
+#[derive(Clone, Default)]
+struct PipelineDerivedData {
+    bindings: Option<Arc<FastHashSet<PropertyBindingId>>>,
+}
+
+#[cfg(feature = "replay")]
+fn default_epoch_policy() -> EpochPolicy {
+    EpochPolicy::Free
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene {
+            root_pipeline_id: None,
+            pipelines: FastHashMap::default(),
+            pipeline_epochs: FastHashMap::default(),
+            property_generation: 0,
+            epoch_policy: EpochPolicy::Free,
+            observer: None,
+            derived_data: RefCell::new(FastHashMap::default()),
+        }
+    }
+
+    /// Set the observer to notify when `set_display_list`,
+    /// `remove_pipeline` and `set_root_pipeline_id` change the scene,
+    /// replacing any previous one. Clones of the scene start without an
+    /// observer.
+    pub fn set_observer(&mut self, observer: Box<dyn SceneObserver>) {
+        self.observer = Some(observer);
+    }
+
+    /// Set how `set_display_list` and `update_epoch` check the epochs they
+    /// install.
+    pub fn set_epoch_policy(&mut self, policy: EpochPolicy) {
+        self.epoch_policy = policy;
+    }
+
+    /// Check an epoch about to be installed for a pipeline. Returns false
+    /// if the policy is `RejectStale` and the epoch is older than the
+    /// current one, in which case it must not be installed.
+    fn check_epoch(&self, pipeline_id: PipelineId, epoch: Epoch) -> bool {
+        match self.epoch_policy {
+            EpochPolicy::Free => true,
+            EpochPolicy::RejectStale => {
+                match self.pipeline_epochs.get(&pipeline_id) {
+                    Some(current) => epoch >= *current,
+                    None => true,
+                }
+            }
+        }
+    }
+
+    /// Invalidate any scene-side state derived from property values. This
+    /// must be called whenever the document's `SceneProperties` flush
+    /// reports a change, with `SceneProperties::last_change_set`.
+    ///
+    /// The contract for caches of property-derived state is:
+    ///
+    /// - Caches owned by `SceneProperties`, such as the resolved maps, are
+    ///   invalidated by the properties themselves whenever their flushed state changes.
+    /// - Caches owned by the scene record the `property_generation` they
+    ///   were computed at, and are stale once it has moved on. Caches that
+    ///   can tell which bindings they depend on may instead be cleared
+    ///   selectively here, using the bindings in `changed`.
+    ///
+    /// An empty change set invalidates nothing.
+    pub fn on_properties_changed(&mut self, changed: &PropertyChangeSet) {
+        if changed.is_empty() {
+            return;
+        }
+        self.property_generation = self.property_generation.wrapping_add(1);
+    }
+
+    /// Get the number of property changes the scene has been told about,
+    /// for keying caches of property-derived state.
+    pub fn property_generation(&self) -> u64 {
+        self.property_generation
+    }
+
+    /// Serialize the scene, prefixed with `SCENE_SCHEMA_VERSION` as a
+    /// little-endian u32, so that it can still be read back by later
+    /// versions of the crate with `deserialize_versioned`.
+    #[cfg(feature = "capture")]
+    pub fn serialize_versioned(&self) -> Result<Vec<u8>, ron::ser::Error> {
+        let payload = ron::ser::to_string(self)?;
+        let mut bytes = Vec::with_capacity(4 + payload.len());
+        bytes.extend_from_slice(&SCENE_SCHEMA_VERSION.to_le_bytes());
+        bytes.extend_from_slice(payload.as_bytes());
+        Ok(bytes)
+    }
+
+    /// Write the structure of the scene, without the display lists: the
+    /// root pipeline, the epochs, and each pipeline's sizes and background
+    /// color, with its display list summarized by its size in bytes and a
+    /// hash of its contents. This is much smaller than a full capture, and
+    /// is meant for structural diffing. See `deserialize_structure`.
+    #[cfg(feature = "capture")]
+    pub fn serialize_structure<W: ::std::io::Write>(&self, w: &mut W) -> ::std::io::Result<()> {
+        let mut pipelines: Vec<PipelineStructure> = self.pipelines
+            .values()
+            .map(|pipeline| {
+                PipelineStructure {
+                    pipeline_id: pipeline.pipeline_id,
+                    viewport_size: pipeline.viewport_size,
+                    content_size: pipeline.content_size,
+                    background_color: pipeline.background_color,
+                    display_list_len: pipeline.display_list.data().len(),
+                    display_list_hash: hash_display_list(&pipeline.display_list),
+                    item_count: count_display_list_items(&pipeline.display_list),
+                }
+            })
+            .collect();
+        pipelines.sort_by_key(|pipeline| (pipeline.pipeline_id.0, pipeline.pipeline_id.1));
+
+        let structure = SceneStructure {
+            root_pipeline_id: self.root_pipeline_id,
+            pipelines,
+            pipeline_epochs: self.pipeline_epochs.clone(),
+        };
+        let text = ron::ser::to_string(&structure)
+            .map_err(|err| ::std::io::Error::new(::std::io::ErrorKind::Other, format!("{:?}", err)))?;
+        w.write_all(text.as_bytes())
+    }
+
+    /// Read back a scene written by `serialize_structure`. The pipelines
+    /// have empty display lists, so the scene can't be rendered; it is
+    /// only good for looking at the sizes, epochs and pipeline set.
+    #[cfg(feature = "replay")]
+    pub fn deserialize_structure<R: ::std::io::Read>(r: &mut R) -> Result<Scene, SceneSchemaError> {
+        let mut text = String::new();
+        r.read_to_string(&mut text)
+            .map_err(|err| SceneSchemaError::Malformed(err.to_string()))?;
+        let structure: SceneStructure = ron::de::from_str(&text)
+            .map_err(|err| SceneSchemaError::Malformed(format!("{:?}", err)))?;
+
+        let mut scene = Scene::new();
+        scene.root_pipeline_id = structure.root_pipeline_id;
+        scene.pipeline_epochs = structure.pipeline_epochs;
+        for pipeline in structure.pipelines {
+            scene.pipelines.insert(pipeline.pipeline_id, Arc::new(ScenePipeline {
+                pipeline_id: pipeline.pipeline_id,
+                viewport_size: pipeline.viewport_size,
+                content_size: pipeline.content_size,
+                background_color: pipeline.background_color,
+                display_list: BuiltDisplayList::default(),
+                    metadata: None,
+            }));
+        }
+        Ok(scene)
+    }
+
+    /// Read back a scene written by `serialize_versioned`. Scenes written
+    /// with an older schema are passed through `migrate`, and scenes
+    /// written with a newer one are rejected.
+    #[cfg(feature = "replay")]
+    pub fn deserialize_versioned(bytes: &[u8]) -> Result<Scene, SceneSchemaError> {
+        if bytes.len() < 4 {
+            return Err(SceneSchemaError::MissingVersion);
+        }
+        let version = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let payload = &bytes[4 ..];
+
+        if version == SCENE_SCHEMA_VERSION {
+            let payload = ::std::str::from_utf8(payload)
+                .map_err(|err| SceneSchemaError::Malformed(err.to_string()))?;
+            ron::de::from_str(payload)
+                .map_err(|err| SceneSchemaError::Malformed(format!("{:?}", err)))
+        } else if version < SCENE_SCHEMA_VERSION {
+            Scene::migrate(version, payload)
+        } else {
+            Err(SceneSchemaError::UnsupportedVersion(version))
+        }
+    }
+
+    /// Read a scene written by `serialize_versioned`, as with
+    /// `deserialize_versioned`, and add `id_offset` to the index of every
+    /// pipeline id in it, as with `map_pipeline_ids`, so that it can be
+    /// merged into a live scene whose pipelines in the same namespaces have
+    /// lower indices. Iframe references in the display lists are rewritten
+    /// too, so the imported scene is self-consistent.
+    #[cfg(feature = "replay")]
+    pub fn import_capture<R: ::std::io::Read>(r: &mut R, id_offset: u32) -> Result<Scene, CaptureError> {
+        let mut bytes = Vec::new();
+        r.read_to_end(&mut bytes).map_err(CaptureError::Io)?;
+        let mut scene = Scene::deserialize_versioned(&bytes).map_err(CaptureError::Schema)?;
+
+        // Check every id up front, so that map_pipeline_ids can't fail.
+        let referenced = scene.pipelines
+            .values()
+            .flat_map(|pipeline| iframe_references(&pipeline.display_list));
+        let overflowing = scene.pipelines
+            .keys()
+            .cloned()
+            .chain(scene.pipeline_epochs.keys().cloned())
+            .chain(scene.root_pipeline_id)
+            .chain(referenced)
+            .find(|pipeline_id| pipeline_id.1.checked_add(id_offset).is_none());
+        if let Some(pipeline_id) = overflowing {
+            return Err(CaptureError::IdOverflow(pipeline_id));
+        }
+
+        scene.map_pipeline_ids(|pipeline_id| PipelineId(pipeline_id.0, pipeline_id.1 + id_offset));
+        Ok(scene)
+    }
+
+    /// Convert the payload of a scene serialized with an older schema
+    /// version. Add a case here when bumping `SCENE_SCHEMA_VERSION`.
+    #[cfg(feature = "replay")]
+    pub fn migrate(version: u32, bytes: &[u8]) -> Result<Scene, SceneSchemaError> {
+        match version {
+            1 | 2 => {
+                // Version 1 is the same as the current one, and version 2
+                // only adds item counts, which are ignored.
+                let payload = ::std::str::from_utf8(bytes)
+                    .map_err(|err| SceneSchemaError::Malformed(err.to_string()))?;
+                ron::de::from_str(payload)
+                    .map_err(|err| SceneSchemaError::Malformed(format!("{:?}", err)))
+            }
+            _ => Err(SceneSchemaError::UnsupportedVersion(version)),
+        }
+    }
+
+    /// Replace the display list of an existing pipeline with one read from
+    /// a file holding a ron-serialized `BuiltDisplayList`, keeping the
+    /// pipeline's sizes, background color and metadata. The pipeline is
+    /// left untouched if the file can't be read. Returns the pipeline's
+    /// new epoch.
+    #[cfg(feature = "replay")]
+    pub fn hot_reload_pipeline(
+        &mut self,
+        pipeline_id: PipelineId,
+        path: &::std::path::Path,
+    ) -> Result<Epoch, HotReloadError> {
+        if !self.pipelines.contains_key(&pipeline_id) {
+            return Err(HotReloadError::UnknownPipeline(pipeline_id));
+        }
+
+        let contents = ::std::fs::read_to_string(path).map_err(HotReloadError::Io)?;
+        let display_list: BuiltDisplayList = ron::de::from_str(&contents)
+            .map_err(|err| HotReloadError::Malformed(format!("{}: {:?}", path.display(), err)))?;
+
+        Arc::make_mut(self.pipelines.get_mut(&pipeline_id).unwrap()).set_display_list(display_list);
+        self.bump_epoch(pipeline_id);
+
+        Ok(self.pipeline_epochs[&pipeline_id])
+    }
+
+    pub fn set_root_pipeline_id(&mut self, pipeline_id: PipelineId) {
+        let changed = self.root_pipeline_id != Some(pipeline_id);
+        self.root_pipeline_id = Some(pipeline_id);
+        if let Some(ref observer) = self.observer {
+            if changed {
+                observer.on_root_changed(self.root_pipeline_id);
+            }
+        }
+    }
+
+    /// Replace the display list of the root pipeline, whichever pipeline
+    /// that currently is, as `set_display_list` would. Fails if there is
+    /// no root pipeline.
+    pub fn replace_root_display_list(
+        &mut self,
+        epoch: Epoch,
+        display_list: BuiltDisplayList,
+        background_color: Option<ColorF>,
+        viewport_size: LayoutSize,
+        content_size: LayoutSize,
+    ) -> Result<(), NoRootError> {
+        let root_pipeline_id = self.root_pipeline_id.ok_or(NoRootError)?;
+        self.set_display_list(
+            root_pipeline_id,
+            epoch,
+            display_list,
+            background_color,
+            viewport_size,
+            content_size,
+        );
+        Ok(())
+    }
+
+    /// Replace the display list of a pipeline. Any metadata attached to
+    /// the pipeline is dropped; use `set_display_list_preserving_metadata`
+    /// to keep it.
+    ///
+    /// This is a positional shorthand for building the pipeline with a
+    /// `ScenePipelineBuilder` and passing it to `add_pipeline`, and returns
+    /// the same value.
+    pub fn set_display_list(
+        &mut self,
+        pipeline_id: PipelineId,
+        epoch: Epoch,
+        display_list: BuiltDisplayList,
+        background_color: Option<ColorF>,
+        viewport_size: LayoutSize,
+        content_size: LayoutSize,
+    ) -> PipelineUpdate {
+        let pipeline = ScenePipelineBuilder::new(pipeline_id)
+            .viewport_size(viewport_size)
+            .content_size(content_size)
+            .background_color(background_color)
+            .display_list(display_list)
+            .build();
+        self.add_pipeline(pipeline, epoch)
+    }
+
+    /// Like `set_display_list`, but keeps any metadata attached to the
+    /// pipeline.
+    pub fn set_display_list_preserving_metadata(
+        &mut self,
+        pipeline_id: PipelineId,
+        epoch: Epoch,
+        display_list: BuiltDisplayList,
+        background_color: Option<ColorF>,
+        viewport_size: LayoutSize,
+        content_size: LayoutSize,
+    ) -> PipelineUpdate {
+        let metadata = self.pipeline_metadata(pipeline_id);
+        let pipeline = ScenePipelineBuilder::new(pipeline_id)
+            .viewport_size(viewport_size)
+            .content_size(content_size)
+            .background_color(background_color)
+            .display_list(display_list)
+            .metadata(metadata)
+            .build();
+        self.add_pipeline(pipeline, epoch)
+    }
+
+    /// Add a pipeline built with `ScenePipelineBuilder` at `epoch`,
+    /// replacing any existing pipeline with the same id. The epoch is
+    /// passed separately because the scene, not the pipeline, tracks it.
+    ///
+    /// If the pipeline already has an identical display list, sizes,
+    /// background color and metadata, only the epoch is updated and the
+    /// existing `Arc<ScenePipeline>` is kept.
+    pub fn add_pipeline(&mut self, pipeline: ScenePipeline, epoch: Epoch) -> PipelineUpdate {
+        let pipeline_id = pipeline.pipeline_id;
+        if !self.check_epoch(pipeline_id, epoch) {
+            return PipelineUpdate::RejectedStale;
+        }
+
+        if let Some(existing) = self.pipelines.get(&pipeline_id) {
+            let same_metadata = match (&existing.metadata, &pipeline.metadata) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            };
+            let unchanged = existing.viewport_size == pipeline.viewport_size &&
+                existing.content_size == pipeline.content_size &&
+                existing.background_color == pipeline.background_color &&
+                same_metadata &&
+                existing.display_list.data() == pipeline.display_list.data();
+            if unchanged {
+                self.pipeline_epochs.insert(pipeline_id, epoch);
+                if let Some(ref observer) = self.observer {
+                    observer.on_pipeline_updated(pipeline_id, epoch);
+                }
+                return PipelineUpdate::Unchanged;
+            }
+        }
+
+        let added = self.pipelines.insert(pipeline_id, Arc::new(pipeline)).is_none();
+        self.pipeline_epochs.insert(pipeline_id, epoch);
+
+        if let Some(ref observer) = self.observer {
+            if added {
+                observer.on_pipeline_added(pipeline_id, epoch);
+            } else {
+                observer.on_pipeline_updated(pipeline_id, epoch);
+            }
+        }
+        PipelineUpdate::Applied
+    }
+
+    pub fn remove_pipeline(&mut self, pipeline_id: PipelineId) {
+        let was_root = self.root_pipeline_id == Some(pipeline_id);
+        if was_root {
+            self.root_pipeline_id = None;
+        }
+        let removed = self.pipelines.remove(&pipeline_id).is_some();
+        let epoch = self.pipeline_epochs.remove(&pipeline_id);
+
+        if let Some(ref observer) = self.observer {
+            if removed {
+                observer.on_pipeline_removed(pipeline_id, epoch.unwrap_or(Epoch::invalid()));
+            }
+            if was_root {
+                observer.on_root_changed(None);
+            }
+        }
+    }
+
+    /// Remove a pipeline along with the pipelines it embeds, directly or
+    /// through other iframes, that nothing else keeps alive. A descendant
+    /// is kept if it is still reachable from the root, or from any other
+    /// pipeline left in the scene, without going through the removed
+    /// pipeline. Each removal notifies the observer as `remove_pipeline`
+    /// does, the descendants in pipeline id order. Returns the ids of the removed pipelines, which is empty if
+    /// `pipeline_id` isn't in the scene.
+    pub fn remove_pipeline_subtree(&mut self, pipeline_id: PipelineId) -> FastHashSet<PipelineId> {
+        let mut removed = FastHashSet::default();
+        if !self.pipelines.contains_key(&pipeline_id) {
+            return removed;
+        }
+
+        let mut descendants = FastHashSet::default();
+        let mut stack = self.iframe_pipeline_ids(&self.pipelines[&pipeline_id]);
+        while let Some(child) = stack.pop() {
+            if child != pipeline_id && descendants.insert(child) {
+                stack.extend(self.iframe_pipeline_ids(&self.pipelines[&child]));
+            }
+        }
+
+        // Everything reachable from the root or from a pipeline outside the
+        // subtree survives, except the pipeline being removed.
+        let mut alive = FastHashSet::default();
+        let mut stack: Vec<PipelineId> = self.pipelines
+            .keys()
+            .filter(|id| **id != pipeline_id && !descendants.contains(*id))
+            .cloned()
+            .chain(self.root_pipeline_id.filter(|root| *root != pipeline_id))
+            .collect();
+        while let Some(id) = stack.pop() {
+            if id != pipeline_id && self.pipelines.contains_key(&id) && alive.insert(id) {
+                stack.extend(self.iframe_pipeline_ids(&self.pipelines[&id]));
+            }
+        }
+
+        let mut orphans: Vec<PipelineId> = descendants
+            .into_iter()
+            .filter(|id| !alive.contains(id))
+            .collect();
+        orphans.sort_by_key(|id| (id.0, id.1));
+
+        self.remove_pipeline(pipeline_id);
+        removed.insert(pipeline_id);
+        for orphan in orphans {
+            self.remove_pipeline(orphan);
+            removed.insert(orphan);
+        }
+        removed
+    }
+
+    /// Install an epoch for a pipeline. Returns false if the epoch policy
+    /// made it ignore the epoch.
+    pub fn update_epoch(&mut self, pipeline_id: PipelineId, epoch: Epoch) -> bool {
+        if !self.check_epoch(pipeline_id, epoch) {
+            return false;
+        }
+        self.pipeline_epochs.insert(pipeline_id, epoch);
+        true
+    }
+
+    /// Install an epoch for a pipeline even if it is lower than the
+    /// current one, for a deliberate reset under `EpochPolicy::RejectStale`.
+    pub fn reset_epoch(&mut self, pipeline_id: PipelineId, epoch: Epoch) {
+        self.pipeline_epochs.insert(pipeline_id, epoch);
+    }
+
+    /// Returns true if the scene has a display list for the pipeline.
+    pub fn contains_pipeline(&self, pipeline_id: PipelineId) -> bool {
+        self.pipelines.contains_key(&pipeline_id)
+    }
+
+    /// Drop the epoch bookkeeping older than `threshold` that no longer
+    /// describes a pipeline in the scene: the epochs recorded for pipelines
+    /// that have no display list. This keeps the scene, and captures of
+    /// it, from growing over long sessions.
+    ///
+    /// Live pipelines are never removed. Those whose current epoch is
+    /// older than `threshold` are returned, in pipeline id order, as
+    /// candidates for eviction; it is up to the caller to remove them.
+    pub fn prune_epochs_older_than(&mut self, threshold: Epoch) -> Vec<PipelineId> {
+        let pipelines = &self.pipelines;
+        self.pipeline_epochs.retain(|pipeline_id, epoch| {
+            pipelines.contains_key(pipeline_id) || *epoch >= threshold
+        });
+
+        let mut stale: Vec<PipelineId> = self.pipeline_epochs
+            .iter()
+            .filter(|&(_, epoch)| *epoch < threshold)
+            .map(|(pipeline_id, _)| *pipeline_id)
+            .collect();
+        stale.sort_by_key(|pipeline_id| (pipeline_id.0, pipeline_id.1));
+        stale
+    }
+
+    /// Bump the epoch of a pipeline without changing its content, to force
+    /// it to be rebuilt. Notifies the observer of an update. Returns the
+    /// new epoch, or None if there is no such pipeline.
+    pub fn touch_pipeline(&mut self, pipeline_id: PipelineId) -> Option<Epoch> {
+        if !self.pipelines.contains_key(&pipeline_id) {
+            return None;
+        }
+
+        self.bump_epoch(pipeline_id);
+        let epoch = self.pipeline_epochs[&pipeline_id];
+        if let Some(ref observer) = self.observer {
+            observer.on_pipeline_updated(pipeline_id, epoch);
+        }
+        Some(epoch)
+    }
+
+    /// Get the current epoch of a pipeline, or None if it has never been
+    /// given one. Being the root pipeline doesn't imply having an epoch.
+    pub fn epoch_of(&self, pipeline_id: PipelineId) -> Option<Epoch> {
+        self.pipeline_epochs.get(&pipeline_id).cloned()
+    }
+
+    /// Get a pipeline, or None if there is no such pipeline.
+    pub fn get_pipeline(&self, pipeline_id: PipelineId) -> Option<&Arc<ScenePipeline>> {
+        self.pipelines.get(&pipeline_id)
+    }
+
+    /// Get a pipeline along with its current epoch, or None if there is no
+    /// such pipeline. A pipeline that has never been given an epoch is
+    /// returned with `Epoch::invalid()`.
+    pub fn get_pipeline_and_epoch(&self, pipeline_id: PipelineId) -> Option<(&Arc<ScenePipeline>, Epoch)> {
+        let pipeline = self.pipelines.get(&pipeline_id)?;
+        let epoch = self.epoch_of(pipeline_id).unwrap_or(Epoch::invalid());
+        Some((pipeline, epoch))
+    }
+
+    /// Exchange the content of two pipelines, keeping each under its
+    /// original id so that references to them elsewhere stay valid. The
+    /// epochs of both pipelines are bumped. Returns false, leaving the
+    /// scene untouched, if either pipeline is absent. The display lists
+    /// are rewritten with `BuiltDisplayList::map_pipeline_ids`, so that
+    /// their spatial and clip ids name the pipeline they now belong to,
+    /// and iframes of either pipeline follow its content to the other id.
+    pub fn swap_pipelines(&mut self, a: PipelineId, b: PipelineId) -> bool {
+        if !self.pipelines.contains_key(&a) || !self.pipelines.contains_key(&b) {
+            return false;
+        }
+
+        if a != b {
+            let old_a = self.pipelines.remove(&a).unwrap();
+            let old_b = self.pipelines.remove(&b).unwrap();
+            let swap_id = |pipeline_id| {
+                if pipeline_id == a {
+                    b
+                } else if pipeline_id == b {
+                    a
+                } else {
+                    pipeline_id
+                }
+            };
+            // The content of `from` under the id of `to`.
+            let moved = |from: &ScenePipeline, to: &ScenePipeline| {
+                let mut pipeline = from.clone();
+                pipeline.pipeline_id = to.pipeline_id;
+                pipeline.set_display_list(from.display_list.map_pipeline_ids(&swap_id));
+                Arc::new(pipeline)
+            };
+            self.pipelines.insert(a, moved(&old_b, &old_a));
+            self.pipelines.insert(b, moved(&old_a, &old_b));
+        }
+
+        self.bump_epoch(a);
+        if a != b {
+            self.bump_epoch(b);
+        }
+
+        true
+    }
+
+    fn bump_epoch(&mut self, pipeline_id: PipelineId) {
+        let epoch = self.pipeline_epochs
+            .entry(pipeline_id)
+            .or_insert(Epoch(0));
+        epoch.0 = epoch.0.wrapping_add(1);
+        if *epoch == Epoch::invalid() {
+            *epoch = Epoch(0);
+        }
+    }
+
+    /// Replace every pipeline id in the scene with `f(pipeline_id)`, for
+    /// moving a scene into another id namespace. This rewrites the keys of
+    /// `pipelines` and `pipeline_epochs`, the root pipeline, and each
+    /// pipeline's display list, including the ids embedded in its spatial
+    /// and clip ids and the pipelines referenced by its iframes. `f` must
+    /// map distinct ids to distinct ids.
+    pub fn map_pipeline_ids<F: Fn(PipelineId) -> PipelineId>(&mut self, f: F) {
+        self.root_pipeline_id = self.root_pipeline_id.map(&f);
+
+        let pipelines = mem::replace(&mut self.pipelines, FastHashMap::default());
+        for (pipeline_id, pipeline) in pipelines {
+            let display_list = pipeline.display_list.map_pipeline_ids(&f);
+            let new_pipeline = ScenePipeline {
+                pipeline_id: f(pipeline.pipeline_id),
+                viewport_size: pipeline.viewport_size,
+                content_size: pipeline.content_size,
+                background_color: pipeline.background_color,
+                    display_list,
+                metadata: pipeline.metadata.clone(),
+            };
+            self.pipelines.insert(f(pipeline_id), Arc::new(new_pipeline));
+        }
+
+        let pipeline_epochs = mem::replace(&mut self.pipeline_epochs, FastHashMap::default());
+        self.pipeline_epochs = pipeline_epochs
+            .into_iter()
+            .map(|(pipeline_id, epoch)| (f(pipeline_id), epoch))
+            .collect();
+    }
+
+    /// Attach embedder data to a pipeline, replacing any that was already
+    /// attached. The data survives epoch updates, but is dropped when the
+    /// display list is replaced (see `set_display_list`). Does nothing if
+    /// there is no such pipeline.
+    pub fn set_pipeline_metadata(
+        &mut self,
+        pipeline_id: PipelineId,
+        metadata: Option<Arc<dyn Any + Send + Sync>>,
+    ) {
+        if let Some(pipeline) = self.pipelines.get_mut(&pipeline_id) {
+            Arc::make_mut(pipeline).metadata = metadata;
+        }
+    }
+
+    /// Get the embedder data attached to a pipeline. Use `Arc::downcast`
+    /// to get back the concrete type:
+    ///
+    /// ```rust,ignore
+    /// let tab_id = scene.pipeline_metadata(pipeline_id)
+    ///     .and_then(|metadata| metadata.downcast::<TabId>().ok());
+    /// ```
+    pub fn pipeline_metadata(
+        &self,
+        pipeline_id: PipelineId,
+    ) -> Option<Arc<dyn Any + Send + Sync>> {
+        self.pipelines
+            .get(&pipeline_id)
+            .and_then(|pipeline| pipeline.metadata.clone())
+    }
+
+    /// Get the entry for a pipeline for in-place update or insertion.
+    /// Unlike manipulating `pipelines` directly, the entry also keeps
+    /// `pipeline_epochs` in sync with the pipeline that is modified
+    /// or inserted.
+    ///
+    /// ```rust,ignore
+    /// scene.pipeline_entry(pipeline_id)
+    ///     .and_modify(epoch, |pipeline| {
+    ///         pipeline.background_color = Some(ColorF::WHITE);
+    ///     })
+    ///     .or_insert_with(epoch, || {
+    ///         ScenePipelineBuilder::new(pipeline_id)
+    ///             .viewport_size(viewport_size)
+    ///             .content_size(content_size)
+    ///             .background_color(Some(ColorF::WHITE))
+    ///             .display_list(display_list)
+    ///             .build()
+    ///     });
+    /// ```
+    pub fn pipeline_entry(&mut self, pipeline_id: PipelineId) -> PipelineEntry {
+        PipelineEntry {
+            scene: self,
+            pipeline_id,
+        }
+    }
+
+    /// Repair `pipeline_epochs` if it has drifted out of sync with
+    /// `pipelines`, by dropping epochs that have no matching pipeline and
+    /// inserting `default` for pipelines that are missing an epoch. This
+    /// is a recovery tool, not something that's needed in normal operation.
+    pub fn rebuild_epoch_map(&mut self, default: Epoch) -> EpochMapRepair {
+        let mut repair = EpochMapRepair::default();
+
+        let pipelines = &self.pipelines;
+        let removed = &mut repair.removed;
+        self.pipeline_epochs.retain(|pipeline_id, _| {
+            let keep = pipelines.contains_key(pipeline_id);
+            if !keep {
+                removed.push(*pipeline_id);
+            }
+            keep
+        });
+
+        for pipeline_id in self.pipelines.keys() {
+            if !self.pipeline_epochs.contains_key(pipeline_id) {
+                self.pipeline_epochs.insert(*pipeline_id, default);
+                repair.inserted.push(*pipeline_id);
+            }
+        }
+
+        repair
+    }
+
+    /// Get the pipelines of the scene by id. Pipelines are added, replaced
+    /// and removed through the methods of the scene, which keep their
+    /// epochs in sync.
+    pub fn pipelines(&self) -> &FastHashMap<PipelineId, Arc<ScenePipeline>> {
+        &self.pipelines
+    }
+
+    /// Get the epoch of each pipeline that has been given one.
+    pub fn pipeline_epochs(&self) -> &FastHashMap<PipelineId, Epoch> {
+        &self.pipeline_epochs
+    }
+
+    /// Replace the pipelines and epochs of this scene with those of
+    /// `other`, sharing its pipelines rather than copying them.
+    pub fn set_pipelines_from(&mut self, other: &Scene) {
+        self.pipelines = other.pipelines.clone();
+        self.pipeline_epochs = other.pipeline_epochs.clone();
+    }
+
+    /// Iterate over all pipelines in the scene.
+    pub fn iter_pipelines(&self) -> impl Iterator<Item = (PipelineId, &ScenePipeline)> {
+        self.pipelines
+            .iter()
+            .map(|(pipeline_id, pipeline)| (*pipeline_id, &**pipeline))
+    }
+
+    /// Iterate over the ids of all pipelines in the scene, in the same
+    /// (unspecified) order as `iter_pipelines`.
+    pub fn pipeline_ids(&self) -> impl Iterator<Item = PipelineId> + '_ {
+        self.pipelines.keys().cloned()
+    }
+
+    /// Iterate mutably over all pipelines in the scene. Each pipeline is
+    /// made unique with `Arc::make_mut` as it's yielded, so pipelines that
+    /// are shared with another scene (e.g. one that was cloned for a frame
+    /// build) get deep-copied, which can noticeably increase memory usage.
+    /// Epochs are left untouched; callers that change what a pipeline
+    /// renders are responsible for bumping them with `update_epoch`.
+    pub fn iter_pipelines_mut(&mut self) -> impl Iterator<Item = (PipelineId, &mut ScenePipeline)> {
+        self.pipelines
+            .iter_mut()
+            .map(|(pipeline_id, pipeline)| (*pipeline_id, Arc::make_mut(pipeline)))
+    }
+
+    /// Get the pipelines that may draw inside `dirty`, which is in the
+    /// coordinate space of the root pipeline (i.e. the window, since the
+    /// root pipeline's bounds are the window). Only pipelines reachable
+    /// from the root via iframes are considered.
+    ///
+    /// This is conservative and may over-include pipelines. An iframe's
+    /// position is only tracked through iframes placed directly in the
+    /// root reference frame of their parent pipeline. Iframes inside
+    /// scroll frames or transformed reference frames, and everything
+    /// nested in them, are always included, since their position depends
+    /// on state that isn't known until frame building.
+    pub fn pipelines_intersecting(&self, dirty: LayoutRect) -> Vec<PipelineId> {
+        let mut result = Vec::new();
+        let root_pipeline = match self.root_pipeline_id.and_then(|id| self.pipelines.get(&id)) {
+            Some(pipeline) => pipeline,
+            None => return result,
+        };
+
+        let mut included = FastHashSet::default();
+        let mut visiting = FastHashSet::default();
+        let root_rect = LayoutRect::new(LayoutPoint::zero(), root_pipeline.viewport_size);
+        if root_rect.intersects(&dirty) {
+            included.insert(root_pipeline.pipeline_id);
+            result.push(root_pipeline.pipeline_id);
+        }
+        self.collect_intersecting_iframes(
+            root_pipeline,
+            Some(LayoutVector2D::zero()),
+            &dirty,
+            &mut visiting,
+            &mut included,
+            &mut result,
+        );
+
+        result
+    }
+
+    fn collect_intersecting_iframes(
+        &self,
+        pipeline: &ScenePipeline,
+        offset: Option<LayoutVector2D>,
+        dirty: &LayoutRect,
+        visiting: &mut FastHashSet<PipelineId>,
+        included: &mut FastHashSet<PipelineId>,
+        result: &mut Vec<PipelineId>,
+    ) {
+        // Guard against malformed scenes where an iframe refers back to
+        // one of its ancestors.
+        if !visiting.insert(pipeline.pipeline_id) {
+            return;
+        }
+
+        for_each_iframe(&pipeline.display_list, &mut (), |_, info| {
+            let iframe_pipeline = match self.pipelines.get(&info.pipeline_id) {
+                Some(iframe_pipeline) => iframe_pipeline,
+                None => return,
+            };
+
+            let iframe_offset = match offset {
+                Some(offset) if info.space_and_clip.spatial_id.is_root_reference_frame() => {
+                    Some(offset + info.bounds.origin.to_vector())
+                }
+                _ => None,
+            };
+            let intersects = match (offset, iframe_offset) {
+                (Some(offset), Some(_)) => info.bounds.translate(&offset).intersects(dirty),
+                _ => true,
+            };
+
+            if intersects && included.insert(info.pipeline_id) {
+                result.push(info.pipeline_id);
+            }
+
+            self.collect_intersecting_iframes(
+                iframe_pipeline,
+                iframe_offset,
+                dirty,
+                visiting,
+                included,
+                result,
+            );
+        });
+
+        visiting.remove(&pipeline.pipeline_id);
+    }
+
+    /// Get the property bindings referenced by a pipeline's display list.
+    /// The set is cached until the pipeline is replaced or mutated.
+    pub fn pipeline_bindings(&self, pipeline_id: PipelineId) -> Option<Arc<FastHashSet<PropertyBindingId>>> {
+        let pipeline = self.pipelines.get(&pipeline_id)?;
+        Some(self.with_derived_data(pipeline_id, pipeline, |derived| {
+            let bindings = derived.bindings.get_or_insert_with(|| {
+                let mut bindings = FastHashSet::default();
+                collect_display_list_bindings(&pipeline.display_list, &mut bindings);
+                Arc::new(bindings)
+            });
+            Arc::clone(bindings)
+        }))
+    }
+
+    /// Get the union of `pipeline_bindings` over every pipeline in the
+    /// scene, for `SceneProperties::retain_referenced`.
+    pub fn referenced_bindings(&self) -> FastHashSet<PropertyBindingId> {
+        self.prune_derived_data();
+
+        let mut referenced = FastHashSet::default();
+        for pipeline_id in self.pipelines.keys() {
+            referenced.extend(self.pipeline_bindings(*pipeline_id).unwrap().iter().cloned());
+        }
+        referenced
+    }
+
+    /// Call `f` with the cached data derived from `pipeline`, after
+    /// clearing it if the pipeline has been replaced or mutated since it
+    /// was cached.
+    fn with_derived_data<F, R>(&self, pipeline_id: PipelineId, pipeline: &Arc<ScenePipeline>, f: F) -> R
+    where
+        F: FnOnce(&mut PipelineDerivedData) -> R,
+    {
+        let mut derived_data = self.derived_data.borrow_mut();
+        let entry = derived_data
+            .entry(pipeline_id)
+            .or_insert_with(|| (Weak::new(), PipelineDerivedData::default()));
+        if !Weak::ptr_eq(&entry.0, &Arc::downgrade(pipeline)) {
+            *entry = (Arc::downgrade(pipeline), PipelineDerivedData::default());
+        }
+        f(&mut entry.1)
+    }
+
+    /// Drop the cached data for pipelines that have left the scene.
+    fn prune_derived_data(&self) {
+        self.derived_data.borrow_mut().retain(|pipeline_id, _| self.pipelines.contains_key(pipeline_id));
+    }
+
+    /// Report the bytes used by each pipeline in the scene: its display
+    /// list data, plus the entries for it in the pipeline and epoch maps.
+    /// Pipelines that only have an epoch are reported with just the epoch
+    /// entries.
+    pub fn report_memory(&self) -> SceneMemoryReport {
+        let mut report = SceneMemoryReport::default();
+        for (pipeline_id, pipeline) in &self.pipelines {
+            let bytes = mem::size_of::<(PipelineId, Arc<ScenePipeline>)>() +
+                mem::size_of::<ScenePipeline>() +
+                pipeline.display_list.data().len();
+            *report.pipelines.entry(*pipeline_id).or_insert(0) += bytes;
+        }
+
+        let epoch_entry_size = mem::size_of::<(PipelineId, Epoch)>();
+        for pipeline_id in self.pipeline_epochs.keys() {
+            *report.pipelines.entry(*pipeline_id).or_insert(0) += epoch_entry_size;
+        }
+
+        report.total_bytes = report.pipelines.values().sum();
+        report
+    }
+
+    /// Get the ids of the pipelines this pipeline embeds as iframes, in
+    /// display list order. Pipelines that aren't in the scene are skipped.
+    fn iframe_pipeline_ids(&self, pipeline: &ScenePipeline) -> Vec<PipelineId> {
+        let mut pipeline_ids = iframe_references(&pipeline.display_list);
+        pipeline_ids.retain(|pipeline_id| self.pipelines.contains_key(pipeline_id));
+        pipeline_ids
+    }
+
+    /// Get the pipelines referenced by the iframes in a pipeline's display
+    /// list, in display list order and without duplicates. Only direct
+    /// children are returned, including ones that aren't in the scene yet.
+    /// Returns nothing for an unknown pipeline.
+    pub fn iframe_children(&self, pipeline_id: PipelineId) -> Vec<PipelineId> {
+        match self.pipelines.get(&pipeline_id) {
+            Some(pipeline) => iframe_references(&pipeline.display_list),
+            None => Vec::new(),
+        }
+    }
+
+    /// Get the pipelines reachable from the root in back-to-front order: a
+    /// depth-first walk that yields each pipeline before the pipelines of
+    /// the iframes in its display list, which are visited in display list
+    /// order. A pipeline referenced by several iframes is yielded at each
+    /// of them. Iframes of pipelines that aren't in the scene are skipped,
+    /// as are iframes that would loop back to a pipeline being visited.
+    pub fn render_order(&self) -> Vec<PipelineId> {
+        let mut order = Vec::new();
+        if let Some(root_pipeline_id) = self.root_pipeline_id {
+            let mut visiting = Vec::new();
+            self.collect_render_order(root_pipeline_id, &mut visiting, &mut order);
+        }
+        order
+    }
+
+    fn collect_render_order(
+        &self,
+        pipeline_id: PipelineId,
+        visiting: &mut Vec<PipelineId>,
+        order: &mut Vec<PipelineId>,
+    ) {
+        let pipeline = match self.pipelines.get(&pipeline_id) {
+            Some(pipeline) => pipeline,
+            None => return,
+        };
+        if visiting.contains(&pipeline_id) {
+            return;
+        }
+
+        order.push(pipeline_id);
+        visiting.push(pipeline_id);
+        for_each_iframe(&pipeline.display_list, &mut (), |_, info| {
+            self.collect_render_order(info.pipeline_id, visiting, order);
+        });
+        visiting.pop();
+    }
+
+    /// Inline the display lists of the pipelines reachable from the root
+    /// into one display list for the root pipeline, for exporting to
+    /// formats that don't have nested documents. Each iframe is replaced by
+    /// the nodes the display list flattener creates for it, namely a clip
+    /// for its clip rect, a reference frame at its origin and a scroll
+    /// frame for the embedded pipeline's root scroll node, followed by the
+    /// embedded pipeline's items. Those keep their property bindings, so
+    /// the result renders like the nested scene against the same
+    /// `SceneProperties`. Iframes are skipped like they are by
+    /// `render_order`. The scroll frames made for iframes get external
+    /// scroll ids of the embedded pipeline that no other scroll frame in
+    /// the scene uses, so an iframe's first scroll frame keeps the id the
+    /// flattener would give it unless that's taken. Scroll frames inside a
+    /// pipeline that is embedded more than once keep their ids in each
+    /// copy. Returns None if the root pipeline isn't in the scene.
+    pub fn flatten(&self) -> Option<ScenePipeline> {
+        let root_pipeline_id = self.root_pipeline_id?;
+        let root = self.pipelines.get(&root_pipeline_id)?;
+
+        let mut builder = DisplayListBuilder::new(root_pipeline_id, root.content_size);
+        let mut visiting = vec![root_pipeline_id];
+        let mut scroll_ids = ExternalScrollIds::new(
+            self.pipelines.values().map(|pipeline| &pipeline.display_list),
+        );
+        self.inline_display_list(
+            &mut builder,
+            &root.display_list,
+            &DisplayListRoots::of_pipeline(root_pipeline_id),
+            &mut visiting,
+            &mut scroll_ids,
+        );
+        let (_, _, display_list) = builder.finalize();
+
+        Some(ScenePipelineBuilder::new(root_pipeline_id)
+            .viewport_size(root.viewport_size)
+            .content_size(root.content_size)
+            .background_color(root.background_color)
+            .display_list(display_list)
+            .build())
+    }
+
+    fn inline_display_list(
+        &self,
+        builder: &mut DisplayListBuilder,
+        display_list: &BuiltDisplayList,
+        roots: &DisplayListRoots,
+        visiting: &mut Vec<PipelineId>,
+        scroll_ids: &mut ExternalScrollIds,
+    ) {
+        builder.push_display_list(display_list, roots, |builder, iframe| {
+            let pipeline = match self.pipelines.get(&iframe.pipeline_id) {
+                Some(pipeline) if !visiting.contains(&iframe.pipeline_id) => pipeline,
+                _ => return,
+            };
+
+            let clip = builder.define_clip(&iframe.space_and_clip, iframe.clip_rect, None, None);
+            let reference_frame = builder.push_reference_frame(
+                iframe.bounds.origin,
+                iframe.space_and_clip.spatial_id,
+                TransformStyle::Flat,
+                PropertyBinding::Value(LayoutTransform::identity()),
+                ReferenceFrameKind::Transform,
+            );
+            let scroll_frame = builder.define_scroll_frame(
+                &SpaceAndClipInfo { spatial_id: reference_frame, clip_id: clip },
+                Some(scroll_ids.allocate(iframe.pipeline_id)),
+                LayoutRect::new(LayoutPoint::zero(), pipeline.content_size),
+                LayoutRect::new(LayoutPoint::zero(), iframe.bounds.size),
+                None,
+                None,
+                ScrollSensitivity::ScriptAndInputEvents,
+                LayoutVector2D::zero(),
+            );
+            // Like the flattener's pipeline clip chain, this makes the
+            // iframe clip apply to the pipeline's root clip chains.
+            let clip_chain_parent = builder.define_clip_chain(roots.clip_chain_parent, Some(clip));
+
+            visiting.push(iframe.pipeline_id);
+            self.inline_display_list(
+                builder,
+                &pipeline.display_list,
+                &DisplayListRoots {
+                    reference_frame,
+                    scroll_node: scroll_frame.spatial_id,
+                    clip,
+                    clip_chain_parent: Some(clip_chain_parent),
+                },
+                visiting,
+                scroll_ids,
+            );
+            visiting.pop();
+
+            builder.pop_reference_frame();
+        });
+    }
+
+    /// Get the bounds of each pipeline reachable from the root, in the
+    /// root pipeline's coordinate space. The root's bounds are its
+    /// viewport, and an embedded pipeline's bounds are those of the iframe
+    /// that embeds it, mapped through the reference frames and stacking
+    /// context offsets enclosing the iframe and the transforms of all the
+    /// iframes above it. Transforms are resolved with `properties`.
+    /// Scroll offsets and sticky positioning are not accounted for.
+    ///
+    /// A pipeline reachable by several paths gets the union of the bounds
+    /// found along each of them. Bounds that can't be projected into the
+    /// root's space, because they end up behind the viewer, are left out.
+    pub fn pipeline_bounds(&self, properties: &SceneProperties) -> FastHashMap<PipelineId, LayoutRect> {
+        let mut bounds = FastHashMap::default();
+        let root_pipeline_id = match self.root_pipeline_id {
+            Some(root_pipeline_id) => root_pipeline_id,
+            None => return bounds,
+        };
+        if let Some(root) = self.pipelines.get(&root_pipeline_id) {
+            bounds.insert(root_pipeline_id, LayoutRect::new(LayoutPoint::zero(), root.viewport_size));
+            let mut visiting = Vec::new();
+            self.collect_pipeline_bounds(
+                root_pipeline_id,
+                &LayoutTransform::identity(),
+                properties,
+                &mut visiting,
+                &mut bounds,
+            );
+        }
+        bounds
+    }
+
+    /// Add the bounds of the pipelines embedded by `pipeline_id`, whose
+    /// contents are mapped to the root's space by `to_root`.
+    fn collect_pipeline_bounds(
+        &self,
+        pipeline_id: PipelineId,
+        to_root: &LayoutTransform,
+        properties: &SceneProperties,
+        visiting: &mut Vec<PipelineId>,
+        bounds: &mut FastHashMap<PipelineId, LayoutRect>,
+    ) {
+        let pipeline = match self.pipelines.get(&pipeline_id) {
+            Some(pipeline) => pipeline,
+            None => return,
+        };
+        if visiting.contains(&pipeline_id) {
+            return;
+        }
+        visiting.push(pipeline_id);
+
+        let mut spaces = PipelineSpaces::new(pipeline_id, properties);
+        for_each_iframe(&pipeline.display_list, &mut spaces, |spaces, info| {
+            if !self.pipelines.contains_key(&info.pipeline_id) {
+                return;
+            }
+            let space = spaces.space(&info.space_and_clip.spatial_id).post_mul(to_root);
+            let origin = spaces.current_offset() + info.bounds.origin.to_vector();
+            let rect = LayoutRect::new(origin.to_point(), info.bounds.size);
+            if let Some(rect) = space.transform_rect(&rect) {
+                let entry = bounds.entry(info.pipeline_id).or_insert(rect);
+                *entry = entry.union(&rect);
+            }
+
+            let child_to_root = LayoutTransform::create_translation(origin.x, origin.y, 0.0)
+                .post_mul(&space);
+            self.collect_pipeline_bounds(info.pipeline_id, &child_to_root, properties, visiting, bounds);
+        });
+
+        visiting.pop();
+    }
+
+    /// Get the pipelines reachable from the root through iframes, including
+    /// the root itself. Pipelines that aren't in the scene are left out,
+    /// and iframes that loop back to a pipeline already found are only
+    /// followed once. Empty if `has_root_pipeline` is false. Pipelines
+    /// that aren't in the result can be removed without changing what the
+    /// scene renders.
+    pub fn reachable_pipelines(&self) -> FastHashSet<PipelineId> {
+        let mut reachable = FastHashSet::default();
+        if !self.has_root_pipeline() {
+            return reachable;
+        }
+
+        let mut stack = vec![self.root_pipeline_id.unwrap()];
+        while let Some(pipeline_id) = stack.pop() {
+            if reachable.insert(pipeline_id) {
+                stack.extend(self.iframe_pipeline_ids(&self.pipelines[&pipeline_id]));
+            }
+        }
+        reachable
+    }
+
+    /// Check that the root pipeline and every pipeline referenced by an
+    /// iframe are in the scene, reporting each one that isn't. Iframes that
+    /// are marked `ignore_missing_pipeline` are allowed to reference missing
+    /// pipelines. Errors are ordered by the id of the pipeline they were
+    /// found in, and then by display list order.
+    pub fn validate(&self) -> Result<(), Vec<SceneValidationError>> {
+        let mut errors = Vec::new();
+        if let Some(root_pipeline_id) = self.root_pipeline_id {
+            if !self.pipelines.contains_key(&root_pipeline_id) {
+                errors.push(SceneValidationError::MissingRoot(root_pipeline_id));
+            }
+        }
+
+        let mut pipeline_ids: Vec<PipelineId> = self.pipelines.keys().cloned().collect();
+        pipeline_ids.sort_by_key(|pipeline_id| (pipeline_id.0, pipeline_id.1));
+        for parent in pipeline_ids {
+            for_each_iframe(&self.pipelines[&parent].display_list, &mut (), |_, info| {
+                if !info.ignore_missing_pipeline && !self.pipelines.contains_key(&info.pipeline_id) {
+                    errors.push(SceneValidationError::MissingIframePipeline {
+                        parent,
+                        child: info.pipeline_id,
+                    });
+                }
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Split the scene into independent documents that can be built on
+    /// separate threads. The split is by reachability through iframes:
+    ///
+    /// - The first document is rooted at the scene's root pipeline, if it
+    ///   is in the scene, and holds every pipeline reachable from it.
+    /// - Each orphan pipeline, one that isn't reachable from the root and
+    ///   isn't embedded by any other pipeline, roots a further document
+    ///   holding the pipelines reachable from it. These are returned in
+    ///   pipeline id order.
+    /// - Orphans that are only embedded by each other through a reference
+    ///   loop are rooted at the lowest pipeline id in the loop.
+    ///
+    /// Every pipeline ends up in at least one document. A pipeline
+    /// reachable from several roots is shared, through its `Arc`, by each
+    /// of their documents. The property generation is carried over to
+    /// every document.
+    pub fn split_into_documents(&self) -> Vec<Scene> {
+        let mut pipeline_ids: Vec<PipelineId> = self.pipelines.keys().cloned().collect();
+        pipeline_ids.sort_by_key(|pipeline_id| (pipeline_id.0, pipeline_id.1));
+
+        let mut embedded = FastHashSet::default();
+        for pipeline in self.pipelines.values() {
+            for child in self.iframe_pipeline_ids(pipeline) {
+                if child != pipeline.pipeline_id {
+                    embedded.insert(child);
+                }
+            }
+        }
+
+        let mut roots = Vec::new();
+        if let Some(root_pipeline_id) = self.root_pipeline_id {
+            if self.pipelines.contains_key(&root_pipeline_id) {
+                roots.push(root_pipeline_id);
+            }
+        }
+        roots.extend(pipeline_ids.iter().filter(|pipeline_id| {
+            !embedded.contains(*pipeline_id) && Some(**pipeline_id) != self.root_pipeline_id
+        }));
+
+        let mut documents = Vec::new();
+        let mut placed = FastHashSet::default();
+        let mut loop_roots = pipeline_ids.iter();
+        loop {
+            let root = if !roots.is_empty() {
+                roots.remove(0)
+            } else {
+                match loop_roots.find(|pipeline_id| !placed.contains(*pipeline_id)) {
+                    Some(pipeline_id) => *pipeline_id,
+                    None => break,
+                }
+            };
+
+            let mut document = Scene::new();
+            document.root_pipeline_id = Some(root);
+            document.property_generation = self.property_generation;
+            let mut stack = vec![root];
+            while let Some(pipeline_id) = stack.pop() {
+                if document.pipelines.contains_key(&pipeline_id) {
+                    continue;
+                }
+                let pipeline = &self.pipelines[&pipeline_id];
+                document.pipelines.insert(pipeline_id, Arc::clone(pipeline));
+                if let Some(epoch) = self.pipeline_epochs.get(&pipeline_id) {
+                    document.pipeline_epochs.insert(pipeline_id, *epoch);
+                }
+                placed.insert(pipeline_id);
+                stack.extend(self.iframe_pipeline_ids(pipeline));
+            }
+            documents.push(document);
+        }
+
+        documents
+    }
+
+    /// Fold the pipelines and epochs of `other` into this scene, for
+    /// combining documents produced independently. This is the
+    /// counterpart to `split_into_documents`. Pipelines whose ids are only
+    /// in one of the scenes are kept as they are, and `policy` decides
+    /// what happens to ids that are in both. This scene's root pipeline is
+    /// kept; `other`'s root is only used if this scene has none, and
+    /// otherwise ends up as an orphan that the caller can embed.
+    ///
+    /// Returns the pipelines of `other` that were renamed under
+    /// `MergePolicy::Rename`, as (old id, new id) pairs sorted by old id.
+    /// Under `MergePolicy::Error` a collision leaves this scene untouched.
+    /// The observer, if any, is told about the pipelines added or replaced
+    /// and about the root changing.
+    pub fn merge(
+        &mut self,
+        mut other: Scene,
+        policy: MergePolicy,
+    ) -> Result<Vec<(PipelineId, PipelineId)>, PipelineCollision> {
+        let mut collisions: Vec<PipelineId> = other.pipelines
+            .keys()
+            .filter(|pipeline_id| self.pipelines.contains_key(pipeline_id))
+            .cloned()
+            .collect();
+        collisions.sort_by_key(|pipeline_id| (pipeline_id.0, pipeline_id.1));
+
+        let mut renames = Vec::new();
+        match policy {
+            MergePolicy::Error => {
+                if let Some(&pipeline_id) = collisions.first() {
+                    return Err(PipelineCollision(pipeline_id));
+                }
+            }
+            MergePolicy::PreferNewer | MergePolicy::PreferIncoming => {
+                // Drop the pipelines of `other` that lose, so that the rest
+                // simply overwrite ours below.
+                for pipeline_id in &collisions {
+                    let ours = self.pipeline_epochs.get(pipeline_id);
+                    let theirs = other.pipeline_epochs.get(pipeline_id);
+                    let keep_ours = match policy {
+                        MergePolicy::PreferNewer => theirs <= ours,
+                        _ => theirs < ours,
+                    };
+                    if keep_ours {
+                        other.pipelines.remove(pipeline_id);
+                        other.pipeline_epochs.remove(pipeline_id);
+                    }
+                }
+            }
+            MergePolicy::Rename => {
+                // Give each colliding pipeline the next id in its namespace
+                // that neither scene uses.
+                let mut taken: FastHashSet<PipelineId> = self.pipelines
+                    .keys()
+                    .chain(other.pipelines.keys())
+                    .cloned()
+                    .collect();
+                for &pipeline_id in &collisions {
+                    let mut renamed = PipelineId(pipeline_id.0, pipeline_id.1.wrapping_add(1));
+                    while taken.contains(&renamed) {
+                        renamed.1 = renamed.1.wrapping_add(1);
+                    }
+                    taken.insert(renamed);
+                    renames.push((pipeline_id, renamed));
+                }
+                if !renames.is_empty() {
+                    let map: FastHashMap<PipelineId, PipelineId> = renames.iter().cloned().collect();
+                    other.map_pipeline_ids(|pipeline_id| map.get(&pipeline_id).cloned().unwrap_or(pipeline_id));
+                }
+            }
+        }
+
+        let old_root = self.root_pipeline_id;
+        if self.root_pipeline_id.is_none() {
+            self.root_pipeline_id = other.root_pipeline_id;
+        }
+
+        let mut merged = Vec::with_capacity(other.pipelines.len());
+        for (pipeline_id, pipeline) in other.pipelines.drain() {
+            let added = self.pipelines.insert(pipeline_id, pipeline).is_none();
+            merged.push((pipeline_id, added));
+        }
+        merged.sort_by_key(|&(pipeline_id, _)| (pipeline_id.0, pipeline_id.1));
+        self.pipeline_epochs.extend(other.pipeline_epochs.drain());
+
+        if let Some(ref observer) = self.observer {
+            for (pipeline_id, added) in merged {
+                let epoch = self.pipeline_epochs.get(&pipeline_id).cloned().unwrap_or(Epoch::invalid());
+                if added {
+                    observer.on_pipeline_added(pipeline_id, epoch);
+                } else {
+                    observer.on_pipeline_updated(pipeline_id, epoch);
+                }
+            }
+            if old_root != self.root_pipeline_id {
+                observer.on_root_changed(self.root_pipeline_id);
+            }
+        }
+
+        Ok(renames)
+    }
+
+    /// Get the deepest level of iframe nesting, counting the root pipeline
+    /// as depth 0. If there is no root pipeline, every pipeline is treated
+    /// as a potential root. Returns an error, rather than recursing
+    /// forever, if the iframes form a reference loop.
+    pub fn max_nesting_depth(&self) -> Result<usize, CycleDetected> {
+        let roots: Vec<PipelineId> = match self.root_pipeline_id {
+            Some(root_pipeline_id) if self.pipelines.contains_key(&root_pipeline_id) => {
+                vec![root_pipeline_id]
+            }
+            Some(..) => Vec::new(),
+            None => self.pipelines.keys().cloned().collect(),
+        };
+
+        let mut depths = FastHashMap::default();
+        let mut visiting = FastHashSet::default();
+        let mut max_depth = 0;
+        for pipeline_id in roots {
+            let depth = self.nesting_depth_of(pipeline_id, &mut depths, &mut visiting)?;
+            max_depth = max_depth.max(depth);
+        }
+
+        Ok(max_depth)
+    }
+
+    /// Get the depth of the iframe tree below a pipeline, memoized in
+    /// `depths`.
+    fn nesting_depth_of(
+        &self,
+        pipeline_id: PipelineId,
+        depths: &mut FastHashMap<PipelineId, usize>,
+        visiting: &mut FastHashSet<PipelineId>,
+    ) -> Result<usize, CycleDetected> {
+        if let Some(depth) = depths.get(&pipeline_id) {
+            return Ok(*depth);
+        }
+        if !visiting.insert(pipeline_id) {
+            return Err(CycleDetected(pipeline_id));
+        }
+
+        let mut depth = 0;
+        for child_id in self.iframe_pipeline_ids(&self.pipelines[&pipeline_id]) {
+            depth = depth.max(self.nesting_depth_of(child_id, depths, visiting)? + 1);
+        }
+
+        visiting.remove(&pipeline_id);
+        depths.insert(pipeline_id, depth);
+        Ok(depth)
+    }
+
+    pub fn has_root_pipeline(&self) -> bool {
+        if let Some(ref root_id) = self.root_pipeline_id {
+            return self.pipelines.contains_key(root_id);
+        }
+
+        false
+    }
+}
+
+/// The memory used by the pipelines of a scene, as computed by
+/// `Scene::report_memory`.
+#[derive(Debug, Default)]
+pub struct SceneMemoryReport {
+    /// The sum of the bytes used by all pipelines.
+    pub total_bytes: usize,
+    /// The bytes used by each pipeline's display list and its entries in
+    /// the scene's maps.
+    pub pipelines: FastHashMap<PipelineId, usize>,
+}
+
+/// Count the items in a display list, not including the markers that
+/// carry auxiliary data.
+#[cfg(feature = "capture")]
+pub fn count_display_list_items(display_list: &BuiltDisplayList) -> usize {
+    let mut item_count = 0;
+    let mut iter = display_list.iter();
+    while iter.next().is_some() {
+        item_count += 1;
+    }
+    item_count
+}
+
+/// Hash the contents of a display list, for summarizing it in
+/// `Scene::serialize_structure`.
+#[cfg(feature = "capture")]
+pub fn hash_display_list(display_list: &BuiltDisplayList) -> u64 {
+    use fxhash::FxHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = FxHasher::default();
+    display_list.data().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Add the ids of the property bindings referenced by a display list's
+/// reference frames, stacking contexts and filters to `bindings`.
+pub fn collect_display_list_bindings(
+    display_list: &BuiltDisplayList,
+    bindings: &mut FastHashSet<PropertyBindingId>,
+) {
+    fn add<T>(bindings: &mut FastHashSet<PropertyBindingId>, property: &PropertyBinding<T>) {
+        if let PropertyBinding::Binding(ref key, _) = *property {
+            bindings.insert(key.id);
+        }
+    }
+
+    let mut iter = display_list.iter();
+    while let Some(item) = iter.next() {
+        match *item.item() {
+            DisplayItem::PushReferenceFrame(ref info) => {
+                add(bindings, &info.reference_frame.transform);
+            }
+            DisplayItem::PushStackingContext(ref info) => {
+                let stacking_context = &info.stacking_context;
+                if let Some(key) = stacking_context.mix_blend_mode_binding {
+                    bindings.insert(key.id);
+                }
+                if let Some(ref transform) = stacking_context.transform {
+                    add(bindings, &transform.transform);
+                    add(bindings, &transform.origin);
+                }
+                for filter in item.filters() {
+                    match filter {
+                        FilterOp::Opacity(binding, _) |
+                        FilterOp::AnimatedBlur(binding, _) => add(bindings, &binding),
+                        _ => {}
+                    }
+                }
+                for primitive in item.filter_primitives() {
+                    match primitive.kind {
+                        FilterPrimitiveKind::Flood(FloodPrimitive { color_binding: Some(key), .. }) => {
+                            bindings.insert(key.id);
+                        }
+                        FilterPrimitiveKind::Blur(ref blur) => {
+                            add(bindings, &blur.radius_x_property());
+                            add(bindings, &blur.radius_y_property());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Get the pipelines referenced by the iframes in a display list, in
+/// order and without duplicates.
+fn iframe_references(display_list: &BuiltDisplayList) -> Vec<PipelineId> {
+    let mut pipeline_ids = Vec::new();
+    for_each_iframe(display_list, &mut (), |_, info| {
+        if !pipeline_ids.contains(&info.pipeline_id) {
+            pipeline_ids.push(info.pipeline_id);
+        }
+    });
+    pipeline_ids
+}
+
+/// Hands out external scroll ids for the scroll frames `Scene::flatten`
+/// creates, so that none of them shares an id with another scroll frame.
+struct ExternalScrollIds {
+    used: FastHashSet<ExternalScrollId>,
+}
+
+impl ExternalScrollIds {
+    /// Reserve the ids of the scroll frames in `display_lists`.
+    fn new<'a, I: Iterator<Item = &'a BuiltDisplayList>>(display_lists: I) -> Self {
+        let mut used = FastHashSet::default();
+        for display_list in display_lists {
+            let mut iter = display_list.iter();
+            while let Some(item) = iter.next() {
+                if let DisplayItem::ScrollFrame(ref info) = *item.item() {
+                    used.extend(info.external_id);
+                }
+            }
+        }
+        ExternalScrollIds { used }
+    }
+
+    /// Get the lowest id for `pipeline_id` that isn't in use yet.
+    fn allocate(&mut self, pipeline_id: PipelineId) -> ExternalScrollId {
+        let mut id = ExternalScrollId(0, pipeline_id);
+        while !self.used.insert(id) {
+            id.0 += 1;
+        }
+        id
+    }
+}
+
+/// Something that follows the items of a display list as it's walked by
+/// `for_each_iframe`, e.g. to know where each iframe is placed.
+trait DisplayItemObserver {
+    fn observe(&mut self, item: &DisplayItem);
+}
+
+impl DisplayItemObserver for () {
+    fn observe(&mut self, _: &DisplayItem) {}
+}
+
+/// Call `f` with each iframe in a display list, in display list order.
+/// Every item is shown to `observer` first, and `f` is given the observer
+/// as it was when the iframe was reached. Pass `&mut ()` when only the
+/// iframes themselves are needed.
+fn for_each_iframe<O, F>(display_list: &BuiltDisplayList, observer: &mut O, mut f: F)
+where
+    O: DisplayItemObserver,
+    F: FnMut(&O, &IframeDisplayItem),
+{
+    let mut iter = display_list.iter();
+    while let Some(item) = iter.next() {
+        observer.observe(item.item());
+        if let DisplayItem::Iframe(ref info) = *item.item() {
+            f(observer, info);
+        }
+    }
+}
+
+/// The transform from each spatial node of a pipeline to its root
+/// reference frame, and the stacking context offsets within each reference
+/// frame, as tracked by the flattener. Transforms are resolved with
+/// `properties`. See `Scene::pipeline_bounds`.
+struct PipelineSpaces<'a> {
+    properties: &'a SceneProperties,
+    spaces: FastHashMap<SpatialId, LayoutTransform>,
+    offsets: Vec<Vec<LayoutVector2D>>,
+    /// For each open stacking context, the spatial node its transform
+    /// replaced and that node's previous transform, if it has one.
+    transformed: Vec<Option<(SpatialId, Option<LayoutTransform>)>>,
+}
+
+impl<'a> PipelineSpaces<'a> {
+    fn new(pipeline_id: PipelineId, properties: &'a SceneProperties) -> Self {
+        let mut spaces = FastHashMap::default();
+        spaces.insert(SpatialId::root_reference_frame(pipeline_id), LayoutTransform::identity());
+        PipelineSpaces {
+            properties,
+            spaces,
+            offsets: vec![vec![LayoutVector2D::zero()]],
+            transformed: Vec::new(),
+        }
+    }
+
+    /// The transform from a spatial node to the pipeline's root reference
+    /// frame. Unknown nodes are treated as the root.
+    fn space(&self, spatial_id: &SpatialId) -> LayoutTransform {
+        self.spaces
+            .get(spatial_id)
+            .cloned()
+            .unwrap_or_else(LayoutTransform::identity)
+    }
+
+    /// The offset of the innermost stacking context in the current
+    /// reference frame.
+    fn current_offset(&self) -> LayoutVector2D {
+        self.offsets
+            .last()
+            .and_then(|offsets| offsets.last())
+            .cloned()
+            .unwrap_or_else(LayoutVector2D::zero)
+    }
+
+    /// Leave the current reference frame. The pipeline's root reference
+    /// frame is never left, so that a display list with more pops than
+    /// pushes can't empty the stack.
+    fn pop_reference_frame(&mut self) {
+        if self.offsets.len() > 1 {
+            self.offsets.pop();
+        }
+    }
+}
+
+impl<'a> DisplayItemObserver for PipelineSpaces<'a> {
+    fn observe(&mut self, item: &DisplayItem) {
+        match *item {
+            DisplayItem::PushReferenceFrame(ref info) => {
+                let parent = self.space(&info.parent_spatial_id);
+                let origin = self.current_offset() + info.origin.to_vector();
+                let transform = self.properties
+                    .resolve_layout_transform(&info.reference_frame.transform)
+                    .post_translate(origin.to_3d())
+                    .post_mul(&parent);
+                self.spaces.insert(info.reference_frame.id, transform);
+                self.offsets.push(vec![LayoutVector2D::zero()]);
+            }
+            DisplayItem::PopReferenceFrame => {
+                self.pop_reference_frame();
+            }
+            DisplayItem::PushStackingContext(ref info) => {
+                // Like the flattener, give a transformed stacking context
+                // a reference frame that replaces its spatial node until
+                // it is popped.
+                let replaced = info.stacking_context.transform.as_ref().map(|transform| {
+                    let parent = self.space(&info.spatial_id);
+                    let space = self.properties
+                        .resolve_transform_about_origin(&transform.transform, &transform.origin)
+                        .post_translate(self.current_offset().to_3d())
+                        .post_mul(&parent);
+                    self.offsets.push(vec![LayoutVector2D::zero()]);
+                    (info.spatial_id, self.spaces.insert(info.spatial_id, space))
+                });
+                self.transformed.push(replaced);
+
+                let offset = self.current_offset() + info.origin.to_vector();
+                if let Some(offsets) = self.offsets.last_mut() {
+                    offsets.push(offset);
+                }
+            }
+            DisplayItem::PopStackingContext => {
+                if let Some(offsets) = self.offsets.last_mut() {
+                    offsets.pop();
+                }
+                if let Some(Some((spatial_id, parent))) = self.transformed.pop() {
+                    self.pop_reference_frame();
+                    match parent {
+                        Some(parent) => self.spaces.insert(spatial_id, parent),
+                        None => self.spaces.remove(&spatial_id),
+                    };
+                }
+            }
+            DisplayItem::ScrollFrame(ref info) => {
+                let parent = self.space(&info.parent_space_and_clip.spatial_id);
+                self.spaces.insert(info.scroll_frame_id, parent);
+            }
+            DisplayItem::StickyFrame(ref info) => {
+                let parent = self.space(&info.parent_spatial_id);
+                self.spaces.insert(info.id, parent);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The scene has no root pipeline.
+#[derive(Debug, PartialEq)]
+pub struct NoRootError;
+
+/// A reference to a pipeline that isn't in the scene, as reported by
+/// `Scene::validate`.
+#[derive(Debug, PartialEq)]
+pub enum SceneValidationError {
+    /// The root pipeline isn't in the scene.
+    MissingRoot(PipelineId),
+    /// An iframe in `parent` embeds `child`, which isn't in the scene.
+    MissingIframePipeline {
+        parent: PipelineId,
+        child: PipelineId,
+    },
+}
+
+/// An iframe reference loop, found while walking the iframe graph. Holds
+/// a pipeline that is part of the loop.
+#[derive(Debug, PartialEq)]
+pub struct CycleDetected(pub PipelineId);
+
+/// How `Scene::merge` handles pipeline ids that are in both scenes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MergePolicy {
+    /// Fail the merge, changing nothing.
+    Error,
+    /// Keep whichever pipeline has the higher epoch. Pipelines without an
+    /// epoch are older than any with one, and ties keep the existing one.
+    PreferNewer,
+    /// Replace the existing pipeline with the incoming one, unless the
+    /// incoming one has a lower epoch. Unlike `PreferNewer`, ties go to the
+    /// incoming pipeline.
+    PreferIncoming,
+    /// Keep both, giving the incoming pipeline an unused id in the same
+    /// namespace, and updating the iframes that reference it.
+    Rename,
+}
+
+/// A pipeline id that is in both scenes passed to `Scene::merge`, under
+/// `MergePolicy::Error`.
+#[derive(Debug, PartialEq)]
+pub struct PipelineCollision(pub PipelineId);
+
+/// The repairs made by `Scene::rebuild_epoch_map`.
+#[derive(Debug, Default)]
+pub struct EpochMapRepair {
+    /// Pipelines that had an epoch but no pipeline. Their epochs were dropped.
+    pub removed: Vec<PipelineId>,
+    /// Pipelines that had no epoch. They were given the default epoch.
+    pub inserted: Vec<PipelineId>,
+}
+
+impl EpochMapRepair {
+    /// Returns true if the epoch map was already consistent.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.inserted.is_empty()
+    }
+}
+
+/// A view into a single pipeline of a scene, which may be either present
+/// or absent. Obtained from `Scene::pipeline_entry`.
+pub struct PipelineEntry<'a> {
+    scene: &'a mut Scene,
+    pipeline_id: PipelineId,
+}
+
+impl<'a> PipelineEntry<'a> {
+    /// If the pipeline is present, modify it in place and set its epoch.
+    /// A pipeline that is shared with another scene is cloned first.
+    pub fn and_modify<F>(self, epoch: Epoch, f: F) -> Self
+    where
+        F: FnOnce(&mut ScenePipeline),
+    {
+        if let Some(pipeline) = self.scene.pipelines.get_mut(&self.pipeline_id) {
+            f(Arc::make_mut(pipeline));
+            self.scene.pipeline_epochs.insert(self.pipeline_id, epoch);
+        }
+        self
+    }
+
+    /// If the pipeline is absent, insert the result of `default` with
+    /// the given epoch. An existing pipeline and its epoch are left as
+    /// they are. Returns the pipeline in either case.
+    pub fn or_insert_with<F>(self, epoch: Epoch, default: F) -> &'a mut ScenePipeline
+    where
+        F: FnOnce() -> ScenePipeline,
+    {
+        let scene = self.scene;
+        let pipeline_id = self.pipeline_id;
+
+        if !scene.pipelines.contains_key(&pipeline_id) {
+            scene.pipeline_epochs.insert(pipeline_id, epoch);
+        }
+
+        let pipeline = scene.pipelines
+            .entry(pipeline_id)
+            .or_insert_with(|| Arc::new(default()));
+
+        Arc::make_mut(pipeline)
+    }
+}
+
+/// An error in the input graph of a list of SVG filter primitives.
+#[derive(Debug, PartialEq)]
+pub enum FilterGraphError {
+    /// The primitive at `primitive` takes the output of itself as an input.
+    SelfReference {
+        primitive: usize,
+    },
+    /// The primitive at `primitive` takes the output of the later
+    /// primitive at `input` as an input.
+    ForwardReference {
+        primitive: usize,
+        input: usize,
+    },
+}
+
+fn filter_primitive_inputs(kind: &FilterPrimitiveKind) -> [Option<FilterPrimitiveInput>; 2] {
+    match *kind {
+        FilterPrimitiveKind::Identity(ref primitive) => [Some(primitive.input), None],
+        FilterPrimitiveKind::Blend(ref primitive) => [Some(primitive.input1), Some(primitive.input2)],
+        FilterPrimitiveKind::Flood(..) => [None, None],
+        FilterPrimitiveKind::Blur(ref primitive) => [Some(primitive.input), None],
+        FilterPrimitiveKind::Opacity(ref primitive) => [Some(primitive.input), None],
+        FilterPrimitiveKind::ColorMatrix(ref primitive) => [Some(primitive.input), None],
+        FilterPrimitiveKind::DropShadow(ref primitive) => [Some(primitive.input), None],
+        FilterPrimitiveKind::ComponentTransfer(ref primitive) => [Some(primitive.input), None],
+    }
+}
+
+/// Check that every primitive only takes as inputs the source graphic or
+/// the outputs of primitives that precede it. This guarantees the input
+/// graph is acyclic and can be evaluated in order, in a single pass.
+pub fn validate_filter_primitives(
+    primitives: &[FilterPrimitive],
+) -> Result<(), FilterGraphError> {
+    validate_filter_primitive_iter(primitives.iter().cloned())
+}
+
+fn validate_filter_primitive_iter<I>(primitives: I) -> Result<(), FilterGraphError>
+where
+    I: Iterator<Item = FilterPrimitive>,
+{
+    for (index, primitive) in primitives.enumerate() {
+        for input in filter_primitive_inputs(&primitive.kind).iter().filter_map(|input| *input) {
+            match input.to_index(index) {
+                Some(input_index) if input_index == index => {
+                    return Err(FilterGraphError::SelfReference {
+                        primitive: index,
+                    });
+                }
+                Some(input_index) if input_index > index => {
+                    return Err(FilterGraphError::ForwardReference {
+                        primitive: index,
+                        input: input_index,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// One level of a chain of nested stacking contexts, as passed to
+/// `collapse_filter_stack`.
+pub struct FilterStackLevel<'a> {
+    pub stacking_context: &'a StackingContext,
+    pub spatial_id: SpatialId,
+    /// The filters of the stacking context, as returned by
+    /// `filter_ops_for_compositing`.
+    pub filters: Vec<Filter>,
+    /// The number of direct children of the stacking context.
+    pub child_count: usize,
+}
+
+/// The result of collapsing a chain of nested stacking contexts that only
+/// exist to add filters into a single one.
+#[derive(Debug, PartialEq)]
+pub struct CollapsedFilterStack {
+    /// The number of levels that were collapsed, starting from the
+    /// outermost. This is always at least 1 for a non-empty chain.
+    pub levels: usize,
+    /// The filters of the collapsed levels, innermost first, in the order
+    /// they apply.
+    pub filters: FilterChain,
+    /// The blend mode to composite the collapsed levels with, which is
+    /// that of the outermost level.
+    pub mix_blend_mode: Option<MixBlendMode>,
+}
+
+/// Collapse the leading part of a chain of nested stacking contexts,
+/// outermost first, into a single stacking context, so that one surface
+/// can be used for all of their filters. A level is folded into the one
+/// enclosing it only if the enclosing level has it as its only child, and
+/// the level itself:
+///
+/// - has no clip, since that would apply between the filters;
+/// - is in the same spatial node, so the filters apply in the same space;
+/// - has a normal blend mode, since it would blend with the backdrop
+///   inside the enclosing surface;
+/// - is flat and has the same raster space, so it would be isolated the
+///   same way.
+///
+/// The outermost level may have any clip and blend mode, since these
+/// apply after all the filters either way.
+pub fn collapse_filter_stack(levels: &[FilterStackLevel]) -> CollapsedFilterStack {
+    let outermost = match levels.first() {
+        Some(outermost) => outermost,
+        None => {
+            return CollapsedFilterStack {
+                levels: 0,
+                filters: FilterChain::default(),
+                mix_blend_mode: None,
+            };
+        }
+    };
+
+    let mut count = 1;
+    while count < levels.len() {
+        let parent = &levels[count - 1];
+        let level = &levels[count];
+        let collapsible = parent.child_count == 1 &&
+            level.stacking_context.clip_id.is_none() &&
+            level.spatial_id == parent.spatial_id &&
+            level.stacking_context.mix_blend_mode == MixBlendMode::Normal &&
+            level.stacking_context.transform_style == TransformStyle::Flat &&
+            level.stacking_context.raster_space == parent.stacking_context.raster_space;
+        if !collapsible {
+            break;
+        }
+        count += 1;
+    }
+
+    let filters = levels[.. count]
+        .iter()
+        .rev()
+        .flat_map(|level| level.filters.iter().cloned())
+        .collect();
+
+    CollapsedFilterStack {
+        levels: count,
+        filters: FilterChain::new(filters),
+        mix_blend_mode: outermost.stacking_context.mix_blend_mode_for_compositing(),
+    }
+}
+
+/// Opacities below this are treated as fully transparent, since they
+/// round to zero alpha in an 8 bit target.
+pub const INVISIBLE_OPACITY_EPSILON: f32 = 0.5 / 255.0;
+
+pub trait StackingContextHelpers {
+    /// Get the blend mode to composite with, ignoring any binding.
+    fn mix_blend_mode_for_compositing(&self) -> Option<MixBlendMode>;
+    /// Get the blend mode to composite with, resolving the blend mode
+    /// binding if there is one. Bindings that resolve to an invalid
+    /// index fall back to the static blend mode.
+    fn resolve_mix_blend_mode_for_compositing(
+        &self,
+        properties: &SceneProperties,
+    ) -> Option<MixBlendMode>;
+    /// Get the transform of the stacking context about its transform
+    /// origin, which is translate(origin) * transform * translate(-origin),
+    /// with both bindings resolved. This is the identity if the stacking
+    /// context has no transform.
+    fn effective_transform(&self, properties: &SceneProperties) -> LayoutTransform;
+    fn filter_ops_for_compositing(
+        &self,
+        input_filters: ItemRange<FilterOp>,
+    ) -> Vec<Filter>;
+    /// Like `filter_ops_for_compositing`, but converting the filters as
+    /// they are iterated rather than collecting them.
+    fn filter_ops_iter<'a>(
+        &self,
+        input_filters: ItemRange<'a, FilterOp>,
+    ) -> FilterOpIter<'a>;
+    /// Like `filter_ops_for_compositing`, but with `f` applied to the
+    /// scalar parameter of each filter that has one:
+    ///
+    /// - Blur: the radius. For a bound blur, `f` isn't applied to the value
+    ///   the binding later resolves to.
+    /// - Brightness, Contrast, Grayscale, Invert, Saturate, Sepia: the amount.
+    /// - HueRotate: the angle.
+    /// - Opacity: the amount. For a bound opacity, `f` is applied to the
+    ///   default and current amounts, but not to the value the binding
+    ///   later resolves to.
+    /// - DropShadows: the blur radius of each shadow.
+    ///
+    /// Identity, ColorMatrix, ComponentTransfer, Flood and the color space
+    /// conversions have no single scalar and are passed through unchanged.
+    /// The results are sanitized, so `f` can't push blurs past the limit.
+    fn map_filter_amounts<F: Fn(f32) -> f32>(
+        &self,
+        input_filters: ItemRange<FilterOp>,
+        f: F,
+    ) -> Vec<Filter>;
+    /// Returns true if an opacity filter resolves to less than
+    /// `INVISIBLE_OPACITY_EPSILON`, so that nothing in the stacking context
+    /// would be visible and the subtree can be skipped. This is only the
+    /// case if no later filter can bring back transparent content: a later
+    /// flood, color matrix or component transfer could make it visible
+    /// again. Filter primitives are not considered.
+    fn resolves_to_invisible(
+        &self,
+        input_filters: ItemRange<FilterOp>,
+        properties: &SceneProperties,
+    ) -> bool;
+    /// Returns false if the stacking context can be flattened into its
+    /// parent without being rendered to a surface of its own: it has a
+    /// normal blend mode once any binding is resolved, is flat, isn't a
+    /// picture cache or a local raster root, and each of its filters is a
+    /// noop, with bound opacities and blurs resolved. Anything else might
+    /// need isolation, so returns true. Filter primitives are not
+    /// considered, so a stacking context that has any needs a surface
+    /// regardless.
+    fn needs_surface(
+        &self,
+        input_filters: ItemRange<FilterOp>,
+        properties: &SceneProperties,
+    ) -> bool;
+    /// Get the component transfer tables of the stacking context. Fails if
+    /// any of them doesn't have exactly one function type per component.
+    fn filter_datas_for_compositing(
+        &self,
+        input_filter_datas: &[TempFilterData],
+    ) -> Result<Vec<FilterData>, FilterDataError>;
+    fn filter_primitives_for_compositing(
+        &self,
+        input_filter_primitives: ItemRange<FilterPrimitive>,
+    ) -> Vec<FilterPrimitive>;
+    /// Like `filter_primitives_for_compositing`, but without collecting
+    /// the primitives. The graph is validated up front, and the iterator
+    /// is empty if it's malformed.
+    fn filter_primitives_iter<'a>(
+        &self,
+        input_filter_primitives: ItemRange<'a, FilterPrimitive>,
+    ) -> FilterPrimitiveIter<'a>;
+    /// Like `filter_primitives_for_compositing`, but with the bound
+    /// deviations of blur primitives resolved, so that each blur carries
+    /// concrete deviations along x and y.
+    fn resolve_filter_primitives_for_compositing(
+        &self,
+        input_filter_primitives: ItemRange<FilterPrimitive>,
+        properties: &SceneProperties,
+    ) -> Vec<FilterPrimitive>;
+    /// Get the filters, component transfer tables and filter primitives of
+    /// the stacking context together, each converted as the per-kind
+    /// methods do, for passes that need to see all of them. Fails if any
+    /// table is malformed, as `filter_datas_for_compositing` does.
+    fn collect_filter_pipeline(
+        &self,
+        input_filters: ItemRange<FilterOp>,
+        input_filter_datas: &[TempFilterData],
+        input_filter_primitives: ItemRange<FilterPrimitive>,
+    ) -> Result<FilterPipeline, FilterDataError>;
+}
+
+/// All of the filters of a stacking context, in the order they are
+/// applied within each kind. See
+/// `StackingContextHelpers::collect_filter_pipeline`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FilterPipeline {
+    pub filters: Vec<Filter>,
+    pub filter_datas: Vec<FilterData>,
+    pub filter_primitives: Vec<FilterPrimitive>,
+}
+
+impl FilterPipeline {
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty() && self.filter_datas.is_empty() && self.filter_primitives.is_empty()
+    }
+}
+
+/// The filters of a stacking context, converted for compositing as they
+/// are iterated. See `StackingContextHelpers::filter_ops_iter`.
+pub struct FilterOpIter<'a> {
+    input: AuxIter<'a, FilterOp>,
+}
+
+impl<'a> Iterator for FilterOpIter<'a> {
+    type Item = Filter;
+
+    fn next(&mut self) -> Option<Filter> {
+        self.input.next().map(Filter::from)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+/// The filter primitives of a stacking context, or none if their graph is
+/// malformed. See `StackingContextHelpers::filter_primitives_iter`.
+pub struct FilterPrimitiveIter<'a> {
+    input: Option<AuxIter<'a, FilterPrimitive>>,
+}
+
+impl<'a> Iterator for FilterPrimitiveIter<'a> {
+    type Item = FilterPrimitive;
+
+    fn next(&mut self) -> Option<FilterPrimitive> {
+        self.input.as_mut()?.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.input {
+            Some(ref input) => input.size_hint(),
+            None => (0, Some(0)),
+        }
+    }
+}
+
+/// A component transfer table that doesn't have the four function types,
+/// one per component, that it needs. See
+/// `StackingContextHelpers::filter_datas_for_compositing`.
+#[derive(Debug, PartialEq)]
+pub struct FilterDataError {
+    /// The position of the table among the stacking context's tables.
+    pub index: usize,
+    /// The number of function types the table has.
+    pub func_type_count: usize,
+}
+
+impl StackingContextHelpers for StackingContext {
+    fn mix_blend_mode_for_compositing(&self) -> Option<MixBlendMode> {
+        match self.mix_blend_mode {
+            MixBlendMode::Normal => None,
+            _ => Some(self.mix_blend_mode),
+        }
+    }
+
+    fn resolve_mix_blend_mode_for_compositing(
+        &self,
+        properties: &SceneProperties,
+    ) -> Option<MixBlendMode> {
+        let mix_blend_mode = match self.mix_blend_mode_binding {
+            Some(key) => {
+                let binding = PropertyBinding::Binding(key, self.mix_blend_mode as i32);
+                MixBlendMode::from_index(properties.resolve_int(&binding))
+                    .unwrap_or(self.mix_blend_mode)
+            }
+            None => self.mix_blend_mode,
+        };
+
+        match mix_blend_mode {
+            MixBlendMode::Normal => None,
+            _ => Some(mix_blend_mode),
+        }
+    }
+
+    fn effective_transform(&self, properties: &SceneProperties) -> LayoutTransform {
+        let transform = match self.transform {
+            Some(ref transform) => transform,
+            None => return LayoutTransform::identity(),
+        };
+        properties.resolve_transform_about_origin(&transform.transform, &transform.origin)
+    }
+
+    fn filter_ops_for_compositing(
+        &self,
+        input_filters: ItemRange<FilterOp>,
+    ) -> Vec<Filter> {
+        self.filter_ops_iter(input_filters).collect()
+    }
+
+    fn filter_ops_iter<'a>(
+        &self,
+        input_filters: ItemRange<'a, FilterOp>,
+    ) -> FilterOpIter<'a> {
+        FilterOpIter { input: input_filters.iter() }
+    }
+
+    fn map_filter_amounts<F: Fn(f32) -> f32>(
+        &self,
+        input_filters: ItemRange<FilterOp>,
+        f: F,
+    ) -> Vec<Filter> {
+        let mut filters = self.filter_ops_for_compositing(input_filters);
+        for filter in &mut filters {
+            match *filter {
+                Filter::Blur(ref mut amount, _) |
+                Filter::Brightness(ref mut amount) |
+                Filter::Contrast(ref mut amount) |
+                Filter::Grayscale(ref mut amount) |
+                Filter::HueRotate(ref mut amount) |
+                Filter::Invert(ref mut amount) |
+                Filter::Saturate(ref mut amount) |
+                Filter::Sepia(ref mut amount) => {
+                    *amount = f(*amount);
+                }
+                Filter::Opacity(ref mut binding, ref mut amount) => {
+                    *binding = match *binding {
+                        PropertyBinding::Value(value) => PropertyBinding::Value(f(value)),
+                        PropertyBinding::Binding(key, value) => PropertyBinding::Binding(key, f(value)),
+                    };
+                    *amount = f(*amount);
+                }
+                Filter::DropShadows(ref mut shadows) => {
+                    for shadow in shadows {
+                        shadow.blur_radius = f(shadow.blur_radius);
+                    }
+                }
+                Filter::Identity |
+                Filter::ColorMatrix(..) |
+                Filter::SrgbToLinear |
+                Filter::LinearToSrgb |
+                Filter::ComponentTransfer |
+                Filter::Flood(..) => {}
+            }
+            filter.sanitize();
+        }
+        filters
+    }
+
+    fn resolves_to_invisible(
+        &self,
+        input_filters: ItemRange<FilterOp>,
+        properties: &SceneProperties,
+    ) -> bool {
+        let mut invisible = false;
+        for filter in input_filters {
+            match filter {
+                FilterOp::Opacity(binding, _) => {
+                    if properties.resolve_float(&binding) < INVISIBLE_OPACITY_EPSILON {
+                        invisible = true;
+                    }
+                }
+                FilterOp::Flood(..) |
+                FilterOp::ColorMatrix(..) |
+                FilterOp::ComponentTransfer => {
+                    invisible = false;
+                }
+                _ => {}
+            }
+        }
+        invisible
+    }
+
+    fn needs_surface(
+        &self,
+        input_filters: ItemRange<FilterOp>,
+        properties: &SceneProperties,
+    ) -> bool {
+        if self.transform_style != TransformStyle::Flat ||
+            self.cache_tiles ||
+            self.raster_space != RasterSpace::Screen ||
+            self.resolve_mix_blend_mode_for_compositing(properties).is_some()
+        {
+            return true;
+        }
+
+        input_filters.iter().any(|filter| {
+            match filter {
+                FilterOp::Opacity(binding, _) => properties.resolve_float(&binding) < 1.0,
+                FilterOp::AnimatedBlur(binding, _) => properties.resolve_float(&binding) != 0.0,
+                filter => !Filter::from(filter).is_noop(),
+            }
+        })
+    }
+
+    fn filter_datas_for_compositing(
+        &self,
+        input_filter_datas: &[TempFilterData],
+    ) -> Result<Vec<FilterData>, FilterDataError> {
+        // TODO(gw): Now that we resolve these later on,
+        //           we could probably make it a bit
+        //           more efficient than cloning these here.
+        let mut filter_datas = vec![];
+        for (index, temp_filter_data) in input_filter_datas.iter().enumerate() {
+            let func_types : Vec<ComponentTransferFuncType> = temp_filter_data.func_types.iter().collect();
+            if func_types.len() != 4 {
+                return Err(FilterDataError { index, func_type_count: func_types.len() });
+            }
+            filter_datas.push( FilterData {
+                func_r_type: func_types[0],
+                r_values: temp_filter_data.r_values.iter().collect(),
+                func_g_type: func_types[1],
+                g_values: temp_filter_data.g_values.iter().collect(),
+                func_b_type: func_types[2],
+                b_values: temp_filter_data.b_values.iter().collect(),
+                func_a_type: func_types[3],
+                a_values: temp_filter_data.a_values.iter().collect(),
+            });
+        }
+        Ok(filter_datas)
+    }
+
+    fn filter_primitives_for_compositing(
+        &self,
+        input_filter_primitives: ItemRange<FilterPrimitive>,
+    ) -> Vec<FilterPrimitive> {
+        self.filter_primitives_iter(input_filter_primitives).collect()
+    }
+
+    fn filter_primitives_iter<'a>(
+        &self,
+        input_filter_primitives: ItemRange<'a, FilterPrimitive>,
+    ) -> FilterPrimitiveIter<'a> {
+        // Drop the whole graph if it's malformed, rather than rendering
+        // garbage from unevaluated inputs.
+        if let Err(err) = validate_filter_primitive_iter(input_filter_primitives.iter()) {
+            warn!("Dropping invalid filter primitive graph: {:?}", err);
+            return FilterPrimitiveIter { input: None };
+        }
+
+        FilterPrimitiveIter { input: Some(input_filter_primitives.iter()) }
+    }
+
+    fn resolve_filter_primitives_for_compositing(
+        &self,
+        input_filter_primitives: ItemRange<FilterPrimitive>,
+        properties: &SceneProperties,
+    ) -> Vec<FilterPrimitive> {
+        let mut filter_primitives = self.filter_primitives_for_compositing(input_filter_primitives);
+        for primitive in &mut filter_primitives {
+            if let FilterPrimitiveKind::Blur(ref mut blur) = primitive.kind {
+                *blur = properties.resolve_blur_primitive(blur);
+            }
+        }
+        filter_primitives
+    }
+
+    fn collect_filter_pipeline(
+        &self,
+        input_filters: ItemRange<FilterOp>,
+        input_filter_datas: &[TempFilterData],
+        input_filter_primitives: ItemRange<FilterPrimitive>,
+    ) -> Result<FilterPipeline, FilterDataError> {
+        Ok(FilterPipeline {
+            filters: self.filter_ops_for_compositing(input_filters),
+            filter_datas: self.filter_datas_for_compositing(input_filter_datas)?,
+            filter_primitives: self.filter_primitives_for_compositing(input_filter_primitives),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use api::{BlendPrimitive, ClipId, ColorSpace, CommonItemProperties};
+    use api::{FilterPrimitiveInput, FilterPrimitiveKind, PluginBlendMode, PropertyBindingKey};
+    use api::{StackingContextTransform, MAX_BLUR_RADIUS};
+
+    fn test_pipeline_builder(pipeline_id: PipelineId) -> ScenePipelineBuilder {
+        let size = LayoutSize::new(100.0, 100.0);
+        ScenePipelineBuilder::new(pipeline_id)
+            .viewport_size(size)
+            .content_size(size)
+            .display_list(BuiltDisplayList::default())
+    }
+
+    fn test_pipeline(pipeline_id: PipelineId) -> ScenePipeline {
+        test_pipeline_builder(pipeline_id).build()
+    }
+
+    /// Builds a scene for a test, one pipeline at a time. Pipelines
+    /// default to those made by `test_pipeline`, at epoch 0.
+    struct SceneTestBuilder {
+        scene: Scene,
+        root: Option<PipelineId>,
+    }
+
+    impl SceneTestBuilder {
+        fn new() -> Self {
+            SceneTestBuilder {
+                scene: Scene::new(),
+                root: None,
+            }
+        }
+
+        fn root(mut self, pipeline_id: PipelineId) -> Self {
+            self.root = Some(pipeline_id);
+            self
+        }
+
+        fn pipeline(self, pipeline_id: PipelineId) -> PipelineTestBuilder {
+            PipelineTestBuilder {
+                scene_builder: self,
+                pipeline: test_pipeline_builder(pipeline_id),
+                epoch: Epoch(0),
+            }
+        }
+
+        /// Get the scene. Fails with the root pipeline id if a root was
+        /// declared but no pipeline was added for it.
+        fn build(mut self) -> Result<Scene, PipelineId> {
+            if let Some(root) = self.root {
+                if !self.scene.contains_pipeline(root) {
+                    return Err(root);
+                }
+                self.scene.set_root_pipeline_id(root);
+            }
+            Ok(self.scene)
+        }
+    }
+
+    /// Wraps a `ScenePipelineBuilder`, adding the pipeline to the scene
+    /// being built when done.
+    struct PipelineTestBuilder {
+        scene_builder: SceneTestBuilder,
+        pipeline: ScenePipelineBuilder,
+        epoch: Epoch,
+    }
+
+    impl PipelineTestBuilder {
+        fn display_list(mut self, display_list: BuiltDisplayList) -> Self {
+            self.pipeline = self.pipeline.display_list(display_list);
+            self
+        }
+
+        fn viewport(mut self, size: LayoutSize) -> Self {
+            self.pipeline = self.pipeline.viewport_size(size);
+            self
+        }
+
+        fn content(mut self, size: LayoutSize) -> Self {
+            self.pipeline = self.pipeline.content_size(size);
+            self
+        }
+
+        fn background(mut self, color: ColorF) -> Self {
+            self.pipeline = self.pipeline.background_color(Some(color));
+            self
+        }
+
+        fn epoch(mut self, epoch: Epoch) -> Self {
+            self.epoch = epoch;
+            self
+        }
+
+        fn add(self) -> SceneTestBuilder {
+            let mut scene_builder = self.scene_builder;
+            scene_builder.scene.add_pipeline(self.pipeline.build(), self.epoch);
+            scene_builder
+        }
+    }
+
+    /// Build a display list holding a single stacking context with the
+    /// given filters, and call `f` with the stacking context as read back.
+    fn with_stacking_context<F>(
+        filters: &[FilterOp],
+        filter_datas: &[FilterData],
+        filter_primitives: &[FilterPrimitive],
+        f: F,
+    ) where F: FnOnce(&StackingContext, ItemRange<FilterOp>, &[TempFilterData], ItemRange<FilterPrimitive>) {
+        let pipeline_id = PipelineId(0, 1);
+        let mut builder = DisplayListBuilder::new(pipeline_id, LayoutSize::new(100.0, 100.0));
+        builder.push_simple_stacking_context_with_filters(
+            LayoutPoint::zero(),
+            SpatialId::root_reference_frame(pipeline_id),
+            true,
+            filters,
+            filter_datas,
+            filter_primitives,
+        );
+        builder.pop_stacking_context();
+        let (_, _, display_list) = builder.finalize();
+
+        let mut iter = display_list.iter();
+        while let Some(item) = iter.next() {
+            if let DisplayItem::PushStackingContext(ref info) = *item.item() {
+                f(&info.stacking_context, item.filters(), item.filter_datas(), item.filter_primitives());
+                return;
+            }
+        }
+        panic!("no stacking context");
+    }
+
+    fn blur_primitive(input: FilterPrimitiveInput) -> FilterPrimitive {
+        FilterPrimitive {
+            kind: FilterPrimitiveKind::Blur(BlurPrimitive { input, radius: 2.0, ..BlurPrimitive::default() }),
+            color_space: ColorSpace::Srgb,
+        }
+    }
+
+    #[test]
+    fn pipeline_entry_keeps_epochs_in_sync() {
+        let mut scene = Scene::new();
+        let pipeline_id = PipelineId(0, 1);
+
+        // Modifying an absent pipeline is a no-op.
+        scene.pipeline_entry(pipeline_id)
+            .and_modify(Epoch(1), |pipeline| pipeline.background_color = Some(ColorF::WHITE));
+        assert!(scene.pipelines.is_empty());
+        assert!(scene.pipeline_epochs.is_empty());
+
+        scene.pipeline_entry(pipeline_id)
+            .or_insert_with(Epoch(1), || test_pipeline(pipeline_id));
+        assert!(scene.pipelines.contains_key(&pipeline_id));
+        assert_eq!(scene.pipeline_epochs[&pipeline_id], Epoch(1));
+
+        let pipeline = scene.pipeline_entry(pipeline_id)
+            .and_modify(Epoch(2), |pipeline| pipeline.background_color = Some(ColorF::WHITE))
+            .or_insert_with(Epoch(3), || test_pipeline(pipeline_id));
+        assert_eq!(pipeline.background_color, Some(ColorF::WHITE));
+        assert_eq!(scene.pipeline_epochs[&pipeline_id], Epoch(2));
+    }
+
+    #[test]
+    fn resolve_color_premultiplied() {
+        let key = PropertyBindingKey::new(1);
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            colors: vec![PropertyValue { key, value: ColorF::new(1.2, 0.5, -0.1, 0.5) }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+
+        let color = properties.resolve_color_premultiplied(&PropertyBinding::Binding(key, ColorF::BLACK));
+        assert_eq!(color, PremultipliedColorF { r: 0.5, g: 0.25, b: 0.0, a: 0.5 });
+
+        let transparent = PropertyBinding::Value(ColorF::new(1.0, 1.0, 1.0, 0.0));
+        assert_eq!(properties.resolve_color_premultiplied(&transparent), PremultipliedColorF::TRANSPARENT);
+    }
+
+    #[test]
+    fn rebuild_epoch_map_repairs_desync() {
+        let mut scene = Scene::new();
+        let a = PipelineId(0, 1);
+        let b = PipelineId(0, 2);
+        let stale = PipelineId(0, 3);
+
+        // The scene's methods keep the maps in sync, so write them
+        // directly, as a capture from an older build might have them.
+        scene.pipelines.insert(a, Arc::new(test_pipeline(a)));
+        scene.pipelines.insert(b, Arc::new(test_pipeline(b)));
+        scene.pipeline_epochs.insert(a, Epoch(5));
+        scene.pipeline_epochs.insert(stale, Epoch(2));
+
+        let repair = scene.rebuild_epoch_map(Epoch(0));
+        assert_eq!(repair.removed, vec![stale]);
+        assert_eq!(repair.inserted, vec![b]);
+        assert_eq!(scene.pipeline_epochs.len(), 2);
+        assert_eq!(scene.pipeline_epochs[&a], Epoch(5));
+        assert_eq!(scene.pipeline_epochs[&b], Epoch(0));
+
+        assert!(scene.rebuild_epoch_map(Epoch(0)).is_empty());
+    }
+
+    #[test]
+    fn validate_filter_primitive_graph() {
+        let valid = [
+            blur_primitive(FilterPrimitiveInput::Original),
+            blur_primitive(FilterPrimitiveInput::Previous),
+            FilterPrimitive {
+                kind: FilterPrimitiveKind::Blend(BlendPrimitive {
+                    input1: FilterPrimitiveInput::OutputOfPrimitiveIndex(0),
+                    input2: FilterPrimitiveInput::Previous,
+                    mode: MixBlendMode::Multiply,
+                }),
+                color_space: ColorSpace::Srgb,
+            },
+        ];
+        assert_eq!(validate_filter_primitives(&valid), Ok(()));
+
+        let forward = [
+            blur_primitive(FilterPrimitiveInput::OutputOfPrimitiveIndex(1)),
+            blur_primitive(FilterPrimitiveInput::Original),
+        ];
+        assert_eq!(
+            validate_filter_primitives(&forward),
+            Err(FilterGraphError::ForwardReference { primitive: 0, input: 1 }),
+        );
+
+        let self_reference = [
+            blur_primitive(FilterPrimitiveInput::Original),
+            blur_primitive(FilterPrimitiveInput::OutputOfPrimitiveIndex(1)),
+        ];
+        assert_eq!(
+            validate_filter_primitives(&self_reference),
+            Err(FilterGraphError::SelfReference { primitive: 1 }),
+        );
+    }
+
+    #[test]
+    fn invalid_filter_primitive_graph_is_dropped() {
+        let primitives = [
+            blur_primitive(FilterPrimitiveInput::Original),
+            blur_primitive(FilterPrimitiveInput::OutputOfPrimitiveIndex(1)),
+        ];
+        with_stacking_context(&[], &[], &primitives, |sc, _, _, input_primitives| {
+            assert!(sc.filter_primitives_for_compositing(input_primitives).is_empty());
+        });
+
+        let primitives = [
+            blur_primitive(FilterPrimitiveInput::Original),
+            blur_primitive(FilterPrimitiveInput::Previous),
+        ];
+        with_stacking_context(&[], &[], &primitives, |sc, _, _, input_primitives| {
+            assert_eq!(sc.filter_primitives_for_compositing(input_primitives).len(), 2);
+        });
+    }
+
+    #[test]
+    fn iter_pipelines_mut_copies_on_write() {
+        let pipeline_id = PipelineId(0, 1);
+        let mut scene = Scene::new();
+        scene.add_pipeline(test_pipeline(pipeline_id), Epoch(0));
+        scene.update_epoch(pipeline_id, Epoch(1));
+        let shared = scene.clone();
+
+        for (id, pipeline) in scene.iter_pipelines_mut() {
+            assert_eq!(id, pipeline_id);
+            pipeline.background_color = Some(ColorF::WHITE);
+        }
+
+        assert_eq!(scene.pipelines[&pipeline_id].background_color, Some(ColorF::WHITE));
+        assert_eq!(shared.pipelines[&pipeline_id].background_color, None);
+        assert_eq!(scene.pipeline_epochs[&pipeline_id], Epoch(1));
+        assert_eq!(scene.iter_pipelines().count(), 1);
+    }
+
+    #[test]
+    fn pipeline_ids() {
+        let mut scene = Scene::new();
+        assert_eq!(scene.pipeline_ids().count(), 0);
+
+        for index in 1 .. 4 {
+            let pipeline_id = PipelineId(0, index);
+            scene.add_pipeline(test_pipeline(pipeline_id), Epoch(0));
+        }
+        let ids: Vec<PipelineId> = scene.pipeline_ids().collect();
+        let iterated: Vec<PipelineId> = scene.iter_pipelines().map(|(pipeline_id, _)| pipeline_id).collect();
+        assert_eq!(ids, iterated);
+        assert_eq!(ids.len(), 3);
+    }
+
+    #[test]
+    fn deferred_rebuild_resolves_same_values() {
+        let key = PropertyBindingKey::new(1);
+        let binding = PropertyBinding::Binding(key, 0.0);
+
+        let mut properties = SceneProperties::new();
+        properties.set_deferred_rebuild(true);
+
+        for value in &[0.25, 0.5, 0.75] {
+            properties.set_properties(DynamicProperties {
+                floats: vec![PropertyValue { key, value: *value }],
+                ..DynamicProperties::default()
+            });
+            assert!(!properties.flush_pending_updates().is_empty());
+            assert!(properties.resolved_dirty.get());
+        }
+
+        assert_eq!(properties.resolve_float(&binding), 0.75);
+        assert!(!properties.resolved_dirty.get());
+        assert_eq!(properties.float_properties().get(&key.id), Some(&0.75));
+
+        properties.set_properties(DynamicProperties::default());
+        assert!(!properties.flush_pending_updates().is_empty());
+        properties.set_deferred_rebuild(false);
+        assert!(!properties.resolved_dirty.get());
+        assert_eq!(properties.resolve_float(&binding), 0.0);
+    }
+
+    #[test]
+    fn pipeline_metadata_lifetime() {
+        let pipeline_id = PipelineId(0, 1);
+        let size = LayoutSize::new(100.0, 100.0);
+        let mut scene = Scene::new();
+        let set_display_list = |scene: &mut Scene, preserve_metadata| {
+            let set = if preserve_metadata {
+                Scene::set_display_list_preserving_metadata
+            } else {
+                Scene::set_display_list
+            };
+            set(scene, pipeline_id, Epoch(0), BuiltDisplayList::default(), None, size, size);
+        };
+
+        set_display_list(&mut scene, false);
+        scene.set_pipeline_metadata(pipeline_id, Some(Arc::new(42u32)));
+        scene.update_epoch(pipeline_id, Epoch(1));
+        let metadata = scene.pipeline_metadata(pipeline_id).unwrap();
+        assert_eq!(*metadata.downcast::<u32>().unwrap(), 42);
+
+        set_display_list(&mut scene, true);
+        assert!(scene.pipeline_metadata(pipeline_id).is_some());
+
+        set_display_list(&mut scene, false);
+        assert!(scene.pipeline_metadata(pipeline_id).is_none());
+    }
+
+    #[test]
+    fn resolve_opacity() {
+        let key = PropertyBindingKey::new(1);
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key, value: 1.5 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+
+        let bound = PropertyBinding::Binding(key, 0.0);
+        assert_eq!(properties.resolve_opacity(&bound, 0.5), 0.5);
+        assert_eq!(properties.resolve_opacity(&PropertyBinding::Value(0.5), 0.5), 0.25);
+        assert_eq!(properties.resolve_opacity(&PropertyBinding::Value(-1.0), 1.0), 0.0);
+        assert_eq!(properties.resolve_opacity(&bound, 0.0), 0.0);
+    }
+
+    #[test]
+    fn swap_pipelines() {
+        let a = PipelineId(0, 1);
+        let b = PipelineId(0, 2);
+        let c = PipelineId(0, 3);
+        let mut scene = Scene::new();
+        let mut pipeline_a = iframe_pipeline(a, &[c]);
+        pipeline_a.background_color = Some(ColorF::WHITE);
+        scene.add_pipeline(pipeline_a, Epoch(0));
+        scene.add_pipeline(test_pipeline(b), Epoch(0));
+        scene.update_epoch(a, Epoch(1));
+        scene.update_epoch(b, Epoch(5));
+
+        assert!(!scene.swap_pipelines(a, c));
+        assert_eq!(scene.pipeline_epochs[&a], Epoch(1));
+
+        assert!(scene.swap_pipelines(a, b));
+        assert_eq!(scene.pipelines[&a].pipeline_id, a);
+        assert_eq!(scene.pipelines[&a].background_color, None);
+        assert_eq!(scene.pipelines[&b].pipeline_id, b);
+        assert_eq!(scene.pipelines[&b].background_color, Some(ColorF::WHITE));
+        assert_eq!(scene.pipeline_epochs[&a], Epoch(2));
+        assert_eq!(scene.pipeline_epochs[&b], Epoch(6));
+
+        // The display list that moved to `b` names `b` in its ids, and
+        // still embeds `c`.
+        let mut iframes = 0;
+        let mut iter = scene.pipelines[&b].display_list.iter();
+        while let Some(item) = iter.next() {
+            if let DisplayItem::Iframe(ref info) = *item.item() {
+                assert_eq!(info.pipeline_id, c);
+                assert_eq!(info.space_and_clip.spatial_id.pipeline_id(), b);
+                assert_eq!(info.space_and_clip.clip_id.pipeline_id(), b);
+                iframes += 1;
+            }
+        }
+        assert_eq!(iframes, 1);
+    }
+
+    #[test]
+    fn pipelines_intersecting() {
+        let root = PipelineId(0, 1);
+        let inside = PipelineId(0, 2);
+        let outside = PipelineId(0, 3);
+        let scrolled = PipelineId(0, 4);
+        let nested = PipelineId(0, 5);
+        let size = LayoutSize::new(100.0, 100.0);
+        let root_space = SpaceAndClipInfo {
+            spatial_id: SpatialId::root_reference_frame(root),
+            clip_id: ClipId::root(root),
+        };
+
+        let mut builder = DisplayListBuilder::new(root, size);
+        let bounds = LayoutRect::new(LayoutPoint::new(0.0, 0.0), LayoutSize::new(50.0, 50.0));
+        builder.push_iframe(bounds, bounds, &root_space, inside, false);
+        let bounds = LayoutRect::new(LayoutPoint::new(50.0, 50.0), LayoutSize::new(50.0, 50.0));
+        builder.push_iframe(bounds, bounds, &root_space, outside, false);
+        builder.push_iframe(bounds, bounds, &SpaceAndClipInfo::root_scroll(root), scrolled, false);
+        let (_, _, root_display_list) = builder.finalize();
+
+        let mut builder = DisplayListBuilder::new(outside, size);
+        let bounds = LayoutRect::new(LayoutPoint::new(0.0, 0.0), LayoutSize::new(10.0, 10.0));
+        let outside_space = SpaceAndClipInfo {
+            spatial_id: SpatialId::root_reference_frame(outside),
+            clip_id: ClipId::root(outside),
+        };
+        builder.push_iframe(bounds, bounds, &outside_space, nested, false);
+        let (_, _, outside_display_list) = builder.finalize();
+
+        let mut scene = Scene::new();
+        scene.set_root_pipeline_id(root);
+        for &(pipeline_id, ref display_list) in &[
+            (root, root_display_list),
+            (inside, BuiltDisplayList::default()),
+            (outside, outside_display_list),
+            (scrolled, BuiltDisplayList::default()),
+            (nested, BuiltDisplayList::default()),
+        ] {
+            scene.set_display_list(pipeline_id, Epoch(0), display_list.clone(), None, size, size);
+        }
+
+        let dirty = LayoutRect::new(LayoutPoint::new(10.0, 10.0), LayoutSize::new(20.0, 20.0));
+        assert_eq!(scene.pipelines_intersecting(dirty), vec![root, inside, scrolled]);
+
+        let dirty = LayoutRect::new(LayoutPoint::new(55.0, 55.0), LayoutSize::new(2.0, 2.0));
+        assert_eq!(scene.pipelines_intersecting(dirty), vec![root, outside, nested, scrolled]);
+
+        let dirty = LayoutRect::new(LayoutPoint::new(200.0, 200.0), LayoutSize::new(2.0, 2.0));
+        assert_eq!(scene.pipelines_intersecting(dirty), vec![scrolled]);
+    }
+
+    #[test]
+    fn map_filter_amounts() {
+        let key = PropertyBindingKey::new(1);
+        let matrix = [0.5; 20];
+        let filters = [
+            FilterOp::Blur(2.0),
+            FilterOp::Sepia(0.5),
+            FilterOp::Opacity(PropertyBinding::Binding(key, 0.5), 0.5),
+            FilterOp::ColorMatrix(matrix),
+            FilterOp::Blur(MAX_BLUR_RADIUS),
+        ];
+        with_stacking_context(&filters, &[], &[], |sc, input_filters, _, _| {
+            let mapped = sc.map_filter_amounts(input_filters, |amount| amount * 0.5);
+            assert_eq!(mapped, vec![
+                Filter::Blur(1.0, None),
+                Filter::Sepia(0.25),
+                Filter::Opacity(PropertyBinding::Binding(key, 0.25), 0.25),
+                Filter::ColorMatrix(Box::new(matrix)),
+                Filter::Blur(MAX_BLUR_RADIUS * 0.5, None),
+            ]);
+
+            let mapped = sc.map_filter_amounts(input_filters, |amount| amount * 4.0);
+            assert_eq!(mapped[4], Filter::Blur(MAX_BLUR_RADIUS, None));
+        });
+    }
+
+    #[test]
+    fn contains_pipeline_and_epoch_of() {
+        let pipeline_id = PipelineId(0, 1);
+        let mut scene = Scene::new();
+        scene.set_root_pipeline_id(pipeline_id);
+        assert!(!scene.contains_pipeline(pipeline_id));
+        assert_eq!(scene.epoch_of(pipeline_id), None);
+
+        let size = LayoutSize::new(100.0, 100.0);
+        scene.set_display_list(pipeline_id, Epoch(3), BuiltDisplayList::default(), None, size, size);
+        assert!(scene.contains_pipeline(pipeline_id));
+        assert_eq!(scene.epoch_of(pipeline_id), Some(Epoch(3)));
+    }
+
+    #[test]
+    fn resolve_mix_blend_mode_binding() {
+        let key = PropertyBindingKey::new(1);
+        let stacking_context = StackingContext {
+            mix_blend_mode: MixBlendMode::Multiply,
+            mix_blend_mode_binding: Some(key),
+            ..StackingContext::default()
+        };
+        let mut properties = SceneProperties::new();
+        let set_mode = |properties: &mut SceneProperties, value| {
+            properties.set_properties(DynamicProperties {
+                ints: vec![PropertyValue { key, value }],
+                ..DynamicProperties::default()
+            });
+            properties.flush_pending_updates();
+        };
+
+        assert_eq!(
+            stacking_context.resolve_mix_blend_mode_for_compositing(&properties),
+            Some(MixBlendMode::Multiply),
+        );
+
+        set_mode(&mut properties, MixBlendMode::Screen as i32);
+        assert_eq!(
+            stacking_context.resolve_mix_blend_mode_for_compositing(&properties),
+            Some(MixBlendMode::Screen),
+        );
+        assert_eq!(stacking_context.mix_blend_mode_for_compositing(), Some(MixBlendMode::Multiply));
+
+        set_mode(&mut properties, MixBlendMode::Normal as i32);
+        assert_eq!(stacking_context.resolve_mix_blend_mode_for_compositing(&properties), None);
+
+        set_mode(&mut properties, 100);
+        assert_eq!(
+            stacking_context.resolve_mix_blend_mode_for_compositing(&properties),
+            Some(MixBlendMode::Multiply),
+        );
+    }
+
+    #[cfg(all(feature = "capture", feature = "replay"))]
+    #[test]
+    fn versioned_serialization() {
+        let pipeline_id = PipelineId(0, 1);
+        let mut scene = Scene::new();
+        scene.set_root_pipeline_id(pipeline_id);
+        scene.add_pipeline(test_pipeline(pipeline_id), Epoch(0));
+        scene.update_epoch(pipeline_id, Epoch(2));
+
+        let mut bytes = scene.serialize_versioned().unwrap();
+        let read = Scene::deserialize_versioned(&bytes).unwrap();
+        assert_eq!(read.root_pipeline_id, Some(pipeline_id));
+        assert_eq!(read.epoch_of(pipeline_id), Some(Epoch(2)));
+        assert!(read.contains_pipeline(pipeline_id));
+
+        bytes[.. 4].copy_from_slice(&(SCENE_SCHEMA_VERSION + 1).to_le_bytes());
+        match Scene::deserialize_versioned(&bytes) {
+            Err(err @ SceneSchemaError::UnsupportedVersion(..)) => {
+                assert_eq!(
+                    err.to_string(),
+                    format!(
+                        "expected schema version 1 to {}, found version {}",
+                        SCENE_SCHEMA_VERSION,
+                        SCENE_SCHEMA_VERSION + 1,
+                    ),
+                );
+            }
+            _ => panic!("expected an unsupported version error"),
+        }
+
+        assert!(Scene::deserialize_versioned(&bytes[.. 2]).is_err());
+    }
+
+    /// Build a pipeline holding an iframe for each of `children`.
+    fn iframe_pipeline(pipeline_id: PipelineId, children: &[PipelineId]) -> ScenePipeline {
+        let bounds = LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(10.0, 10.0));
+        let mut builder = DisplayListBuilder::new(pipeline_id, LayoutSize::new(100.0, 100.0));
+        for child_id in children {
+            builder.push_iframe(bounds, bounds, &SpaceAndClipInfo::root_scroll(pipeline_id), *child_id, false);
+        }
+        let (_, _, display_list) = builder.finalize();
+
+        let mut pipeline = test_pipeline(pipeline_id);
+        pipeline.set_display_list(display_list);
+        pipeline
+    }
+
+    #[test]
+    fn max_nesting_depth() {
+        let root = PipelineId(0, 1);
+        let a = PipelineId(0, 2);
+        let b = PipelineId(0, 3);
+        let c = PipelineId(0, 4);
+        let mut scene = Scene::new();
+        scene.set_root_pipeline_id(root);
+        assert_eq!(scene.max_nesting_depth(), Ok(0));
+
+        scene.add_pipeline(iframe_pipeline(root, &[a, c]), Epoch(0));
+        scene.add_pipeline(iframe_pipeline(a, &[b]), Epoch(0));
+        scene.add_pipeline(iframe_pipeline(b, &[]), Epoch(0));
+        scene.add_pipeline(iframe_pipeline(c, &[]), Epoch(0));
+        assert_eq!(scene.max_nesting_depth(), Ok(2));
+
+        scene.add_pipeline(iframe_pipeline(b, &[a]), Epoch(0));
+        assert!(scene.max_nesting_depth().is_err());
+    }
+
+    #[test]
+    fn springs() {
+        let key = PropertyBindingKey::new(1);
+        let binding = PropertyBinding::Binding(key, 0.0);
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key, value: 0.25 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+
+        properties.set_spring_target(key.id, 1.0, 100.0, 20.0);
+        assert_eq!(properties.resolve_float(&binding), 0.25);
+
+        assert!(properties.advance_springs(1.0 / 60.0));
+        let value = properties.resolve_float(&binding);
+        assert!(value > 0.25 && value < 1.0, "{}", value);
+
+        // The spring settles at its target, and then stops changing.
+        for _ in 0 .. 600 {
+            properties.advance_springs(1.0 / 60.0);
+        }
+        assert_eq!(properties.resolve_float(&binding), 1.0);
+        assert!(!properties.advance_springs(1.0 / 60.0));
+
+        // Springs override values flushed later, until cleared.
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key, value: 0.5 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        assert_eq!(properties.resolve_float(&binding), 1.0);
+        properties.clear_spring(key.id);
+        assert_eq!(properties.resolve_float(&binding), 0.5);
+    }
+
+    #[test]
+    fn collapse_filter_stack_levels() {
+        let pipeline_id = PipelineId(0, 1);
+        let spatial_id = SpatialId::root_reference_frame(pipeline_id);
+        let outer = StackingContext {
+            mix_blend_mode: MixBlendMode::Multiply,
+            ..StackingContext::default()
+        };
+        let plain = StackingContext::default();
+        let clipped = StackingContext {
+            clip_id: Some(ClipId::root(pipeline_id)),
+            ..StackingContext::default()
+        };
+        let level = |stacking_context, filter| FilterStackLevel {
+            stacking_context,
+            spatial_id,
+            filters: vec![filter],
+            child_count: 1,
+        };
+
+        let collapsed = collapse_filter_stack(&[
+            level(&outer, Filter::Blur(1.0, None)),
+            level(&plain, Filter::Sepia(0.5)),
+            level(&plain, Filter::Invert(0.5)),
+        ]);
+        assert_eq!(collapsed, CollapsedFilterStack {
+            levels: 3,
+            filters: FilterChain::new(vec![Filter::Invert(0.5), Filter::Sepia(0.5), Filter::Blur(1.0, None)]),
+            mix_blend_mode: Some(MixBlendMode::Multiply),
+        });
+
+        let collapsed = collapse_filter_stack(&[
+            level(&outer, Filter::Blur(1.0, None)),
+            level(&clipped, Filter::Sepia(0.5)),
+            level(&plain, Filter::Invert(0.5)),
+        ]);
+        assert_eq!(collapsed.levels, 1);
+        assert_eq!(collapsed.filters, FilterChain::new(vec![Filter::Blur(1.0, None)]));
+    }
+
+    #[test]
+    fn map_pipeline_ids() {
+        let root = PipelineId(0, 1);
+        let child = PipelineId(0, 2);
+        let remap = |pipeline_id: PipelineId| PipelineId(7, pipeline_id.1);
+        let mut scene = Scene::new();
+        scene.set_root_pipeline_id(root);
+        scene.add_pipeline(iframe_pipeline(root, &[child]), Epoch(0));
+        scene.add_pipeline(iframe_pipeline(child, &[]), Epoch(0));
+        scene.update_epoch(root, Epoch(1));
+        scene.update_epoch(child, Epoch(2));
+
+        scene.map_pipeline_ids(remap);
+
+        assert_eq!(scene.root_pipeline_id, Some(remap(root)));
+        assert_eq!(scene.epoch_of(remap(root)), Some(Epoch(1)));
+        assert_eq!(scene.epoch_of(remap(child)), Some(Epoch(2)));
+        assert!(!scene.contains_pipeline(root));
+        assert_eq!(scene.pipelines[&remap(child)].pipeline_id, remap(child));
+
+        let root_pipeline = &scene.pipelines[&remap(root)];
+        assert_eq!(root_pipeline.pipeline_id, remap(root));
+        let mut iframes = 0;
+        let mut iter = root_pipeline.display_list.iter();
+        while let Some(item) = iter.next() {
+            if let DisplayItem::Iframe(ref info) = *item.item() {
+                assert_eq!(info.pipeline_id, remap(child));
+                assert_eq!(info.space_and_clip.spatial_id.pipeline_id(), remap(root));
+                assert_eq!(info.space_and_clip.clip_id.pipeline_id(), remap(root));
+                iframes += 1;
+            }
+        }
+        assert_eq!(iframes, 1);
+        assert_eq!(scene.max_nesting_depth(), Ok(1));
+    }
+
+    #[test]
+    fn flood_feeds_blend_primitive() {
+        let key = PropertyBindingKey::new(1);
+        let flood = FilterPrimitive {
+            kind: FilterPrimitiveKind::Flood(FloodPrimitive {
+                color: ColorF::BLACK,
+                color_binding: Some(key),
+            }),
+            color_space: ColorSpace::Srgb,
+        };
+        let blend = FilterPrimitive {
+            kind: FilterPrimitiveKind::Blend(BlendPrimitive {
+                input1: FilterPrimitiveInput::OutputOfPrimitiveIndex(0),
+                input2: FilterPrimitiveInput::Original,
+                mode: MixBlendMode::Multiply,
+            }),
+            color_space: ColorSpace::Srgb,
+        };
+
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            colors: vec![PropertyValue { key, value: ColorF::new(1.5, 0.5, 0.25, 1.0) }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+
+        with_stacking_context(&[], &[], &[flood, blend], |sc, _, _, prims| {
+            let primitives = sc.filter_primitives_for_compositing(prims);
+            assert_eq!(primitives, vec![flood, blend]);
+
+            match primitives[0].kind {
+                FilterPrimitiveKind::Flood(ref flood) => {
+                    assert_eq!(properties.resolve_flood_color(flood), ColorF::new(1.0, 0.5, 0.25, 1.0));
+                }
+                _ => panic!("expected a flood primitive"),
+            }
+        });
+
+        // Without a binding the static color is used.
+        let unbound = FloodPrimitive { color: ColorF::WHITE, color_binding: None };
+        assert_eq!(properties.resolve_flood_color(&unbound), ColorF::WHITE);
+    }
+
+    #[test]
+    fn property_changes_invalidate_scene() {
+        let opacity = PropertyBindingKey::new(1);
+        let color = PropertyBindingKey::new(2);
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key: opacity, value: 0.5 }],
+            colors: vec![PropertyValue { key: color, value: ColorF::WHITE }],
+            ..DynamicProperties::default()
+        });
+        assert!(!properties.flush_pending_updates().is_empty());
+
+        let mut scene = Scene::new();
+        scene.on_properties_changed(properties.last_change_set());
+        assert_eq!(scene.property_generation(), 1);
+
+        // Change the float and drop the color.
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key: opacity, value: 1.0 }],
+            ..DynamicProperties::default()
+        });
+        assert!(!properties.flush_pending_updates().is_empty());
+        let changed = properties.last_change_set();
+        assert!(changed.bindings.contains(&opacity.id));
+        assert!(changed.bindings.contains(&color.id));
+        scene.on_properties_changed(changed);
+        assert_eq!(scene.property_generation(), 2);
+
+        scene.on_properties_changed(&PropertyChangeSet::default());
+        assert_eq!(scene.property_generation(), 2);
+    }
+
+    #[test]
+    fn resolve_border_radius() {
+        let key = PropertyBindingKey::new(1);
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key, value: 12.0 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+
+        let radii = [
+            PropertyBinding::Binding(key, 0.0),
+            PropertyBinding::Value(4.0),
+            PropertyBinding::Value(1.0),
+            PropertyBinding::Value(2.0),
+            PropertyBinding::Value(-3.0),
+            PropertyBinding::Value(3.0),
+            PropertyBinding::Value(200.0),
+            PropertyBinding::Binding(PropertyBindingKey::new(2), 5.0),
+        ];
+        let radius = properties.resolve_border_radius(&radii);
+        assert_eq!(radius.top_left, LayoutSize::new(12.0, 4.0));
+        assert_eq!(radius.top_right, LayoutSize::new(1.0, 2.0));
+        assert_eq!(radius.bottom_left, LayoutSize::new(0.0, 3.0));
+        // Overlapping radii aren't normalized.
+        assert_eq!(radius.bottom_right, LayoutSize::new(200.0, 5.0));
+    }
+
+    #[cfg(all(feature = "capture", feature = "replay"))]
+    #[test]
+    fn hot_reload_pipeline() {
+        let root = PipelineId(0, 1);
+        let child = PipelineId(0, 2);
+        let mut scene = Scene::new();
+        let mut pipeline = test_pipeline(root);
+        pipeline.background_color = Some(ColorF::WHITE);
+        scene.add_pipeline(pipeline, Epoch(0));
+        scene.add_pipeline(test_pipeline(child), Epoch(0));
+        scene.update_epoch(root, Epoch(3));
+
+        let dir = ::std::env::temp_dir();
+        let path = dir.join(format!("wr-hot-reload-{}.ron", ::std::process::id()));
+        let display_list = iframe_pipeline(root, &[child]).display_list;
+        ::std::fs::write(&path, ron::ser::to_string(&display_list).unwrap()).unwrap();
+
+        assert_eq!(scene.hot_reload_pipeline(root, &path).unwrap(), Epoch(4));
+        assert_eq!(scene.epoch_of(root), Some(Epoch(4)));
+        assert_eq!(scene.pipelines[&root].background_color, Some(ColorF::WHITE));
+        assert_eq!(scene.iframe_pipeline_ids(&scene.pipelines[&root]), vec![child]);
+
+        let unknown = PipelineId(0, 3);
+        match scene.hot_reload_pipeline(unknown, &path) {
+            Err(HotReloadError::UnknownPipeline(id)) => assert_eq!(id, unknown),
+            _ => panic!("expected an unknown pipeline error"),
+        }
+
+        ::std::fs::write(&path, "not a display list").unwrap();
+        match scene.hot_reload_pipeline(root, &path) {
+            Err(HotReloadError::Malformed(..)) => {}
+            _ => panic!("expected a malformed file error"),
+        }
+        assert_eq!(scene.epoch_of(root), Some(Epoch(4)));
+
+        ::std::fs::remove_file(&path).unwrap();
+        match scene.hot_reload_pipeline(root, &path) {
+            Err(HotReloadError::Io(..)) => {}
+            _ => panic!("expected an io error"),
+        }
+    }
+
+    #[test]
+    fn replace_root_display_list() {
+        let root = PipelineId(0, 1);
+        let size = LayoutSize::new(50.0, 50.0);
+        let mut scene = Scene::new();
+        assert_eq!(
+            scene.replace_root_display_list(Epoch(1), BuiltDisplayList::default(), None, size, size),
+            Err(NoRootError),
+        );
+        assert!(scene.pipelines.is_empty());
+
+        scene.set_root_pipeline_id(root);
+        assert_eq!(
+            scene.replace_root_display_list(Epoch(1), BuiltDisplayList::default(), Some(ColorF::WHITE), size, size),
+            Ok(()),
+        );
+        assert_eq!(scene.epoch_of(root), Some(Epoch(1)));
+        assert_eq!(scene.pipelines[&root].background_color, Some(ColorF::WHITE));
+        assert_eq!(scene.pipelines[&root].viewport_size, size);
+    }
+
+    #[test]
+    fn flush_reports_dirty_kinds() {
+        let transform = PropertyBindingKey::new(1);
+        let opacity = PropertyBindingKey::new(2);
+        let color = PropertyBindingKey::new(3);
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key: transform, value: LayoutTransform::identity() }],
+            floats: vec![PropertyValue { key: opacity, value: 0.5 }],
+            ..DynamicProperties::default()
+        });
+        assert_eq!(
+            properties.flush_pending_updates(),
+            PropertyDirtyFlags::TRANSFORMS | PropertyDirtyFlags::FLOATS,
+        );
+
+        // Accumulate over the flushes of a frame.
+        let mut dirty = PropertyDirtyFlags::empty();
+        properties.set_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key: transform, value: LayoutTransform::identity() }],
+            floats: vec![PropertyValue { key: opacity, value: 0.5 }],
+            colors: vec![PropertyValue { key: color, value: ColorF::WHITE }],
+            ..DynamicProperties::default()
+        });
+        dirty |= properties.flush_pending_updates();
+        assert_eq!(dirty, PropertyDirtyFlags::COLORS);
+
+        properties.set_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key: transform, value: LayoutTransform::identity() }],
+            colors: vec![PropertyValue { key: color, value: ColorF::WHITE }],
+            ..DynamicProperties::default()
+        });
+        dirty |= properties.flush_pending_updates();
+        assert!(dirty.contains(PropertyDirtyFlags::COLORS | PropertyDirtyFlags::FLOATS));
+        assert!(!dirty.contains(PropertyDirtyFlags::TRANSFORMS));
+
+        assert!(properties.flush_pending_updates().is_empty());
+    }
+
+    #[test]
+    fn iframe_children() {
+        let root = PipelineId(0, 1);
+        let child = PipelineId(0, 2);
+        let grandchild = PipelineId(0, 3);
+        let missing = PipelineId(0, 4);
+        let mut scene = Scene::new();
+        scene.add_pipeline(iframe_pipeline(root, &[child, missing, child]), Epoch(0));
+        scene.add_pipeline(iframe_pipeline(child, &[grandchild]), Epoch(0));
+        scene.add_pipeline(iframe_pipeline(grandchild, &[]), Epoch(0));
+
+        assert_eq!(scene.iframe_children(root), vec![child, missing]);
+        assert_eq!(scene.iframe_children(child), vec![grandchild]);
+        assert!(scene.iframe_children(grandchild).is_empty());
+        assert!(scene.iframe_children(missing).is_empty());
+    }
+
+    #[cfg(feature = "debug_properties")]
+    #[test]
+    fn resolve_float_changed_per_marker() {
+        let key = PropertyBindingKey::new(1);
+        let binding = PropertyBinding::Binding(key, 0.0);
+        let set_opacity = |properties: &mut SceneProperties, value| {
+            properties.set_properties(DynamicProperties {
+                floats: vec![PropertyValue { key, value }],
+                ..DynamicProperties::default()
+            });
+            properties.flush_pending_updates();
+        };
+
+        let mut properties = SceneProperties::new();
+        let mut a = 0;
+        let mut b = 0;
+        assert_eq!(properties.resolve_float_changed(&mut a, &binding), (0.0, false));
+
+        set_opacity(&mut properties, 0.5);
+        assert_eq!(properties.resolve_float_changed(&mut a, &binding), (0.5, true));
+        assert_eq!(properties.resolve_float_changed(&mut a, &binding), (0.5, false));
+
+        // Flushing the same value again isn't a change.
+        set_opacity(&mut properties, 0.5);
+        assert_eq!(properties.resolve_float_changed(&mut a, &binding), (0.5, false));
+
+        // Each marker sees every change since its own last use.
+        set_opacity(&mut properties, 0.75);
+        assert_eq!(properties.resolve_float_changed(&mut a, &binding), (0.75, true));
+        assert_eq!(properties.resolve_float_changed(&mut b, &binding), (0.75, true));
+        assert_eq!(properties.resolve_float_changed(&mut b, &PropertyBinding::Value(1.0)), (1.0, false));
+
+        // Removals and springs are changes too.
+        properties.set_properties(DynamicProperties::default());
+        properties.flush_pending_updates();
+        assert_eq!(properties.resolve_float_changed(&mut a, &binding), (0.0, true));
+
+        set_opacity(&mut properties, 0.25);
+        assert_eq!(properties.resolve_float_changed(&mut a, &binding), (0.25, true));
+        properties.set_spring_target(key.id, 1.0, 100.0, 10.0);
+        properties.advance_springs(0.1);
+        let (value, changed) = properties.resolve_float_changed(&mut a, &binding);
+        assert!(value > 0.25 && changed);
+        assert_eq!(properties.resolve_float_changed(&mut a, &binding), (value, false));
+    }
+
+    #[cfg(all(feature = "capture", feature = "replay"))]
+    #[test]
+    fn structure_serialization() {
+        let root = PipelineId(0, 1);
+        let child = PipelineId(0, 2);
+        let mut scene = Scene::new();
+        scene.set_root_pipeline_id(root);
+        let mut pipeline = iframe_pipeline(root, &[child]);
+        pipeline.background_color = Some(ColorF::WHITE);
+        scene.add_pipeline(pipeline, Epoch(0));
+        scene.add_pipeline(test_pipeline(child), Epoch(0));
+        scene.update_epoch(root, Epoch(2));
+        scene.update_epoch(child, Epoch(5));
+
+        let mut bytes = Vec::new();
+        scene.serialize_structure(&mut bytes).unwrap();
+        let mut again = Vec::new();
+        scene.serialize_structure(&mut again).unwrap();
+        assert_eq!(bytes, again);
+
+        let read = Scene::deserialize_structure(&mut &bytes[..]).unwrap();
+        assert_eq!(read.root_pipeline_id, Some(root));
+        assert_eq!(read.epoch_of(child), Some(Epoch(5)));
+        assert_eq!(read.pipelines[&root].background_color, Some(ColorF::WHITE));
+        assert_eq!(read.pipelines[&child].viewport_size, LayoutSize::new(100.0, 100.0));
+        assert!(read.pipelines[&root].display_list.iter().next().is_none());
+
+        assert!(Scene::deserialize_structure(&mut &b"garbage"[..]).is_err());
+    }
+
+    #[cfg(feature = "debug_properties")]
+    #[test]
+    fn rewind_property_history() {
+        let key = PropertyBindingKey::new(1);
+        let binding = PropertyBinding::Binding(key, 0.0);
+        let mut properties = SceneProperties::new();
+        assert_eq!(properties.history_len(), 0);
+
+        for i in 0 .. PROPERTY_HISTORY_CAPACITY + 2 {
+            properties.set_properties(DynamicProperties {
+                floats: vec![PropertyValue { key, value: i as f32 }],
+                ..DynamicProperties::default()
+            });
+            properties.flush_pending_updates();
+        }
+        let latest = (PROPERTY_HISTORY_CAPACITY + 1) as f32;
+        assert_eq!(properties.history_len(), PROPERTY_HISTORY_CAPACITY);
+
+        assert!(properties.rewind(3));
+        assert_eq!(properties.resolve_float(&binding), latest - 3.0);
+        assert!(properties.rewind(0));
+        assert_eq!(properties.resolve_float(&binding), latest);
+        assert!(!properties.rewind(PROPERTY_HISTORY_CAPACITY));
+        assert_eq!(properties.resolve_float(&binding), latest);
+
+        // The pending update replaces the rewound state at the next flush.
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key, value: 100.0 }],
+            ..DynamicProperties::default()
+        });
+        assert!(properties.rewind(1));
+        assert_eq!(properties.resolve_float(&binding), latest - 1.0);
+        properties.flush_pending_updates();
+        assert_eq!(properties.resolve_float(&binding), 100.0);
+    }
+
+    #[test]
+    fn render_order_follows_display_lists() {
+        let root = PipelineId(0, 1);
+        let a = PipelineId(0, 2);
+        let b = PipelineId(0, 3);
+        let c = PipelineId(0, 4);
+        assert!(Scene::new().render_order().is_empty());
+
+        let scene = SceneTestBuilder::new()
+            .root(root)
+            .pipeline(root).display_list(iframe_pipeline(root, &[b, a, b]).display_list).add()
+            .pipeline(a).display_list(iframe_pipeline(a, &[c, root]).display_list).add()
+            .pipeline(b).display_list(iframe_pipeline(b, &[PipelineId(0, 5)]).display_list).add()
+            .pipeline(c).add()
+            .build()
+            .unwrap();
+
+        assert_eq!(scene.render_order(), vec![root, b, a, c, b]);
+    }
+
+    #[test]
+    fn resolve_layout_transform_snapped() {
+        let properties = SceneProperties::new();
+        let snapped = |transform: LayoutTransform| {
+            properties.resolve_layout_transform_snapped(&PropertyBinding::Value(transform), 2.0)
+        };
+
+        let translation = LayoutTransform::create_translation(10.2, 5.8, 0.0);
+        let result = snapped(translation);
+        assert_eq!((result.m41, result.m42), (10.0, 6.0));
+
+        let scaled = LayoutTransform::create_scale(3.0, 0.5, 1.0).post_translate(LayoutVector2D::new(1.3, 2.7).to_3d());
+        let result = snapped(scaled);
+        assert_eq!((result.m11, result.m22), (3.0, 0.5));
+        assert_eq!((result.m41, result.m42), (1.5, 2.5));
+
+        let rotated = LayoutTransform::create_rotation(0.0, 0.0, 1.0, euclid::Angle::radians(0.3))
+            .post_translate(LayoutVector2D::new(1.3, 2.7).to_3d());
+        assert_eq!(snapped(rotated), rotated);
+
+        let perspective = LayoutTransform::create_perspective(100.0).post_translate(LayoutVector2D::new(1.3, 2.7).to_3d());
+        assert_eq!(snapped(perspective), perspective);
+    }
+
+    #[test]
+    fn scene_test_builder() {
+        let root = PipelineId(0, 1);
+        let size = LayoutSize::new(20.0, 30.0);
+        let scene = SceneTestBuilder::new()
+            .root(root)
+            .pipeline(root)
+            .viewport(size)
+            .content(size * 2.0)
+            .background(ColorF::WHITE)
+            .epoch(Epoch(3))
+            .add()
+            .build()
+            .unwrap();
+
+        assert_eq!(scene.root_pipeline_id, Some(root));
+        assert_eq!(scene.epoch_of(root), Some(Epoch(3)));
+        let pipeline = &scene.pipelines[&root];
+        assert_eq!(pipeline.viewport_size, size);
+        assert_eq!(pipeline.content_size, size * 2.0);
+        assert_eq!(pipeline.background_color, Some(ColorF::WHITE));
+
+        let missing_root = SceneTestBuilder::new()
+            .root(PipelineId(0, 2))
+            .pipeline(root)
+            .add()
+            .build();
+        assert_eq!(missing_root.err(), Some(PipelineId(0, 2)));
+    }
+
+    #[test]
+    fn resolves_to_invisible() {
+        let key = PropertyBindingKey::new(1);
+        let bound = FilterOp::Opacity(PropertyBinding::Binding(key, 1.0), 1.0);
+        let mut properties = SceneProperties::new();
+
+        with_stacking_context(&[FilterOp::Blur(2.0), bound], &[], &[], |sc, filters, _, _| {
+            assert!(!sc.resolves_to_invisible(filters, &properties));
+        });
+
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key, value: 0.001 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        with_stacking_context(&[FilterOp::Blur(2.0), bound], &[], &[], |sc, filters, _, _| {
+            assert!(sc.resolves_to_invisible(filters, &properties));
+        });
+
+        let transparent = FilterOp::Opacity(PropertyBinding::Value(0.0), 0.0);
+        with_stacking_context(&[transparent, FilterOp::Grayscale(0.5)], &[], &[], |sc, filters, _, _| {
+            assert!(sc.resolves_to_invisible(filters, &properties));
+        });
+
+        // A flood after the opacity paints over the transparent content.
+        with_stacking_context(&[transparent, FilterOp::Flood(ColorF::WHITE)], &[], &[], |sc, filters, _, _| {
+            assert!(!sc.resolves_to_invisible(filters, &properties));
+        });
+    }
+
+    #[cfg(all(feature = "capture", feature = "replay"))]
+    #[test]
+    fn migrate_v2_ignores_item_counts() {
+        let root = PipelineId(0, 1);
+        let mut scene = Scene::new();
+        scene.set_root_pipeline_id(root);
+        scene.add_pipeline(iframe_pipeline(root, &[PipelineId(0, 2)]), Epoch(0));
+
+        // A version 2 payload is the current one with item counts.
+        let payload = ron::ser::to_string(&scene).unwrap();
+        assert!(!payload.contains("item_count"));
+        let payload = payload.replace("display_list:", "item_count:1,display_list:");
+        let mut bytes = 2u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(payload.as_bytes());
+
+        let read = Scene::deserialize_versioned(&bytes).unwrap();
+        assert_eq!(read.pipelines()[&root].display_list().data(), scene.pipelines()[&root].display_list().data());
+    }
+
+    #[test]
+    fn resolve_gradient_stops_resorts_crossed_stops() {
+        let moving = PropertyBindingKey::<f32>::new(1);
+        let stops = [
+            GradientStopBinding {
+                offset: PropertyBinding::Binding(moving, 0.0),
+                color: PropertyBinding::Value(ColorF::WHITE),
+            },
+            GradientStopBinding {
+                offset: PropertyBinding::Value(0.3),
+                color: PropertyBinding::Value(ColorF::BLACK),
+            },
+            GradientStopBinding {
+                offset: PropertyBinding::Value(0.6),
+                color: PropertyBinding::Binding(PropertyBindingKey::new(2), ColorF::BLACK),
+            },
+        ];
+
+        let mut properties = SceneProperties::new();
+        let resolved = properties.resolve_gradient_stops(&stops);
+        let offsets: Vec<f32> = resolved.iter().map(|stop| stop.offset).collect();
+        assert_eq!(offsets, vec![0.0, 0.3, 0.6]);
+
+        // Animate the first stop past both of the others, and the color of
+        // the last one.
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key: moving, value: 0.8 }],
+            colors: vec![PropertyValue { key: PropertyBindingKey::new(2), value: ColorF::WHITE }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        let resolved = properties.resolve_gradient_stops(&stops);
+        let offsets: Vec<f32> = resolved.iter().map(|stop| stop.offset).collect();
+        assert_eq!(offsets, vec![0.3, 0.6, 0.8]);
+        assert_eq!(resolved[0].color, ColorF::BLACK);
+        assert_eq!(resolved[1].color, ColorF::WHITE);
+        assert_eq!(resolved[2].color, ColorF::WHITE);
+
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key: moving, value: 1.5 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        assert_eq!(properties.resolve_gradient_stops(&stops)[2].offset, 1.0);
+    }
+
+    #[test]
+    fn split_into_documents_by_reachability() {
+        let root = PipelineId(0, 1);
+        let shared = PipelineId(0, 2);
+        let orphan = PipelineId(0, 3);
+        let looped = [PipelineId(0, 5), PipelineId(0, 4)];
+        let scene = SceneTestBuilder::new()
+            .root(root)
+            .pipeline(root).display_list(iframe_pipeline(root, &[shared]).display_list).add()
+            .pipeline(shared).add()
+            .pipeline(orphan).display_list(iframe_pipeline(orphan, &[shared]).display_list).add()
+            .pipeline(looped[0]).display_list(iframe_pipeline(looped[0], &[looped[1]]).display_list).add()
+            .pipeline(looped[1]).display_list(iframe_pipeline(looped[1], &[looped[0]]).display_list).add()
+            .build()
+            .unwrap();
+
+        let documents = scene.split_into_documents();
+        let roots: Vec<_> = documents.iter().map(|document| document.root_pipeline_id).collect();
+        assert_eq!(roots, vec![Some(root), Some(orphan), Some(looped[1])]);
+
+        assert_eq!(documents[0].pipelines.len(), 2);
+        assert!(Arc::ptr_eq(&documents[0].pipelines[&shared], &documents[1].pipelines[&shared]));
+        assert!(documents[1].contains_pipeline(orphan));
+        assert_eq!(documents[2].pipelines.len(), 2);
+        assert!(documents[2].contains_pipeline(looped[0]));
+    }
+
+    #[test]
+    fn resolve_float_stepped_buckets() {
+        let properties = SceneProperties::new();
+        let stepped = |value: f32, steps: u32| {
+            properties.resolve_float_stepped(&PropertyBinding::Value(value), steps)
+        };
+
+        // Four buckets, resolving to 0, 1/3, 2/3 and 1.
+        assert_eq!(stepped(0.0, 4), 0.0);
+        assert_eq!(stepped(0.24, 4), 0.0);
+        assert_eq!(stepped(0.25, 4), 1.0 / 3.0);
+        assert_eq!(stepped(0.6, 4), 2.0 / 3.0);
+        assert_eq!(stepped(0.99, 4), 1.0);
+        assert_eq!(stepped(1.0, 4), 1.0);
+
+        assert_eq!(stepped(-0.5, 4), 0.0);
+        assert_eq!(stepped(7.0, 4), 1.0);
+        assert_eq!(stepped(0.7, 1), 0.0);
+        assert_eq!(stepped(0.7, 0), 0.7);
+        assert_eq!(stepped(1.5, 0), 1.0);
+    }
+
+    #[test]
+    fn pipeline_bounds_in_root_space() {
+        let root = PipelineId(0, 1);
+        let a = PipelineId(0, 2);
+        let b = PipelineId(0, 3);
+        let scale = PropertyBindingKey::new(1);
+        let size = LayoutSize::new(10.0, 10.0);
+
+        // The root embeds `a` in a scaled reference frame, below a stacking
+        // context offset, and `b` directly. `a` embeds `b` as well.
+        let mut builder = DisplayListBuilder::new(root, LayoutSize::new(100.0, 100.0));
+        let spatial_id = builder.push_reference_frame(
+            LayoutPoint::new(10.0, 0.0),
+            SpatialId::root_reference_frame(root),
+            TransformStyle::Flat,
+            PropertyBinding::Binding(scale, LayoutTransform::identity()),
+            ReferenceFrameKind::Transform,
+        );
+        builder.push_simple_stacking_context(LayoutPoint::new(5.0, 5.0), spatial_id, true);
+        let space_and_clip = SpaceAndClipInfo {
+            spatial_id,
+            clip_id: ClipId::root(root),
+        };
+        let bounds = LayoutRect::new(LayoutPoint::zero(), size);
+        builder.push_iframe(bounds, bounds, &space_and_clip, a, false);
+        builder.pop_stacking_context();
+        builder.pop_reference_frame();
+        let bounds = LayoutRect::new(LayoutPoint::new(50.0, 50.0), size);
+        builder.push_iframe(bounds, bounds, &SpaceAndClipInfo::root_scroll(root), b, false);
+        let (_, _, display_list) = builder.finalize();
+
+        let scene = SceneTestBuilder::new()
+            .root(root)
+            .pipeline(root).viewport(LayoutSize::new(100.0, 80.0)).display_list(display_list).add()
+            .pipeline(a).display_list(iframe_pipeline(a, &[b]).display_list).add()
+            .pipeline(b).add()
+            .build()
+            .unwrap();
+
+        let mut properties = SceneProperties::new();
+        let bounds = scene.pipeline_bounds(&properties);
+        assert_eq!(bounds[&root], LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(100.0, 80.0)));
+        assert_eq!(bounds[&a], LayoutRect::new(LayoutPoint::new(15.0, 5.0), size));
+
+        properties.set_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key: scale, value: LayoutTransform::create_scale(2.0, 2.0, 1.0) }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        let bounds = scene.pipeline_bounds(&properties);
+        assert_eq!(bounds.len(), 3);
+        assert_eq!(bounds[&a], LayoutRect::new(LayoutPoint::new(20.0, 10.0), LayoutSize::new(20.0, 20.0)));
+        // `b` is the union of its direct iframe and the one inside `a`.
+        assert_eq!(bounds[&b], LayoutRect::new(LayoutPoint::new(20.0, 10.0), LayoutSize::new(40.0, 50.0)));
+    }
+
+    #[test]
+    fn epoch_policy_allows_resets() {
+        let pipeline_id = PipelineId(0, 1);
+        let size = LayoutSize::new(10.0, 10.0);
+        let mut scene = Scene::new();
+        scene.update_epoch(pipeline_id, Epoch(3));
+        scene.set_display_list(pipeline_id, Epoch(2), BuiltDisplayList::default(), None, size, size);
+        assert_eq!(scene.epoch_of(pipeline_id), Some(Epoch(2)));
+
+        scene.set_epoch_policy(EpochPolicy::RejectStale);
+        scene.update_epoch(pipeline_id, Epoch(3));
+        assert!(!scene.update_epoch(pipeline_id, Epoch(2)));
+        scene.reset_epoch(pipeline_id, Epoch(1));
+        scene.set_display_list(pipeline_id, Epoch(1), BuiltDisplayList::default(), None, size, size);
+        assert_eq!(scene.epoch_of(pipeline_id), Some(Epoch(1)));
+    }
+
+    #[test]
+    fn resolve_transform_chain_order() {
+        let key = PropertyBindingKey::new(1);
+        let chain = [
+            PropertyBinding::Value(LayoutTransform::create_translation(10.0, 0.0, 0.0)),
+            PropertyBinding::Binding(key, LayoutTransform::identity()),
+        ];
+
+        let mut properties = SceneProperties::new();
+        assert_eq!(properties.resolve_transform_chain(&[]), LayoutTransform::identity());
+        assert_eq!(
+            properties.resolve_transform_chain(&chain),
+            LayoutTransform::create_translation(10.0, 0.0, 0.0),
+        );
+
+        properties.set_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key, value: LayoutTransform::create_scale(2.0, 2.0, 1.0) }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+
+        // Translate after scale: the point is scaled first.
+        let point = properties.resolve_transform_chain(&chain).transform_point2d(&LayoutPoint::new(1.0, 0.0));
+        assert_eq!(point, Some(LayoutPoint::new(12.0, 0.0)));
+    }
+
+    #[test]
+    fn scene_observer_notifications() {
+        use std::sync::Mutex;
+
+        #[derive(Debug, PartialEq)]
+        enum Event {
+            Added(PipelineId, Epoch),
+            Removed(PipelineId, Epoch),
+            Updated(PipelineId, Epoch),
+            Root(Option<PipelineId>),
+        }
+
+        struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+        impl SceneObserver for Recorder {
+            fn on_pipeline_added(&self, pipeline_id: PipelineId, epoch: Epoch) {
+                self.0.lock().unwrap().push(Event::Added(pipeline_id, epoch));
+            }
+            fn on_pipeline_removed(&self, pipeline_id: PipelineId, epoch: Epoch) {
+                self.0.lock().unwrap().push(Event::Removed(pipeline_id, epoch));
+            }
+            fn on_pipeline_updated(&self, pipeline_id: PipelineId, epoch: Epoch) {
+                self.0.lock().unwrap().push(Event::Updated(pipeline_id, epoch));
+            }
+            fn on_root_changed(&self, root_pipeline_id: Option<PipelineId>) {
+                self.0.lock().unwrap().push(Event::Root(root_pipeline_id));
+            }
+        }
+
+        struct Silent;
+        impl SceneObserver for Silent {}
+
+        let root = PipelineId(0, 1);
+        let size = LayoutSize::new(10.0, 10.0);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut scene = Scene::new();
+        scene.set_observer(Box::new(Recorder(Arc::clone(&events))));
+
+        scene.set_root_pipeline_id(root);
+        scene.set_root_pipeline_id(root);
+        scene.set_display_list(root, Epoch(1), BuiltDisplayList::default(), None, size, size);
+        scene.set_display_list(root, Epoch(2), BuiltDisplayList::default(), None, size, size);
+        scene.remove_pipeline(root);
+        scene.remove_pipeline(root);
+
+        assert_eq!(*events.lock().unwrap(), vec![
+            Event::Root(Some(root)),
+            Event::Added(root, Epoch(1)),
+            Event::Updated(root, Epoch(2)),
+            Event::Removed(root, Epoch(2)),
+            Event::Root(None),
+        ]);
+
+        // Clones don't notify the observer.
+        let mut clone = scene.clone();
+        clone.set_display_list(root, Epoch(3), BuiltDisplayList::default(), None, size, size);
+        assert_eq!(events.lock().unwrap().len(), 5);
+
+        scene.set_observer(Box::new(Silent));
+        scene.set_display_list(root, Epoch(3), BuiltDisplayList::default(), None, size, size);
+        assert_eq!(events.lock().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn prune_epochs_older_than() {
+        let live = PipelineId(0, 1);
+        let fresh = PipelineId(0, 2);
+        let removed = PipelineId(0, 3);
+        let orphan_epoch = PipelineId(0, 4);
+        let size = LayoutSize::new(10.0, 10.0);
+
+        let mut scene = Scene::new();
+        scene.set_display_list(live, Epoch(1), BuiltDisplayList::default(), None, size, size);
+        scene.set_display_list(fresh, Epoch(5), BuiltDisplayList::default(), None, size, size);
+        scene.set_display_list(removed, Epoch(2), BuiltDisplayList::default(), None, size, size);
+        scene.remove_pipeline(removed);
+        scene.update_epoch(orphan_epoch, Epoch(1));
+
+        assert_eq!(scene.prune_epochs_older_than(Epoch(3)), vec![live]);
+        assert!(scene.contains_pipeline(live));
+        assert_eq!(scene.epoch_of(live), Some(Epoch(1)));
+        assert_eq!(scene.epoch_of(orphan_epoch), None);
+    }
+
+    #[test]
+    fn resolve_and_transform_points_matches_single_points() {
+        let key = PropertyBindingKey::new(1);
+        let binding = PropertyBinding::Binding(key, LayoutTransform::identity());
+        let transform = LayoutTransform::create_rotation(0.0, 0.0, 1.0, euclid::Angle::radians(0.5))
+            .post_translate(euclid::TypedVector3D::new(3.0, -2.0, 0.0))
+            .pre_mul(&LayoutTransform::create_perspective(100.0));
+
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key, value: transform }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+
+        let points: Vec<LayoutPoint> = (0 .. 9)
+            .map(|i| LayoutPoint::new(i as f32 * 1.5, 10.0 - i as f32))
+            .collect();
+        let mut out = vec![LayoutPoint::zero(); points.len()];
+        assert!(properties.resolve_and_transform_points(&binding, &points, &mut out));
+        for (point, out) in points.iter().zip(&out) {
+            assert_eq!(transform.transform_point2d(point), Some(*out));
+        }
+
+        // A point behind the viewer.
+        let behind = LayoutTransform::row_major(
+            1.0, 0.0, 0.0, -1.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        let points = [LayoutPoint::new(0.5, 0.0), LayoutPoint::new(2.0, 0.0)];
+        let mut out = [LayoutPoint::zero(); 2];
+        assert!(!properties.resolve_and_transform_points(&PropertyBinding::Value(behind), &points, &mut out));
+        assert_eq!(behind.transform_point2d(&points[0]), Some(out[0]));
+    }
+
+    #[test]
+    fn effective_transform_pivots_about_bound_origin() {
+        use euclid::Angle;
+
+        let key = PropertyBindingKey::new(1);
+        let rotation = LayoutTransform::create_rotation(0.0, 0.0, 1.0, Angle::degrees(90.0));
+        let stacking_context = StackingContext {
+            transform: Some(StackingContextTransform {
+                transform: PropertyBinding::Value(rotation),
+                origin: PropertyBinding::Binding(key, LayoutPoint::zero()),
+            }),
+            ..StackingContext::default()
+        };
+        let mut properties = SceneProperties::new();
+        let transformed = |properties: &SceneProperties, point| {
+            stacking_context.effective_transform(properties).transform_point2d(&point).unwrap()
+        };
+        let rotated = |point| rotation.transform_point2d(&point).unwrap();
+        let close = |a: LayoutPoint, b: LayoutPoint| (a - b).length() < 1e-4;
+
+        // About the default origin, the transform is the plain rotation.
+        let point = LayoutPoint::new(10.0, 0.0);
+        assert!(close(transformed(&properties, point), rotated(point)));
+        assert!(!close(transformed(&properties, point), point));
+
+        // Moving the origin onto (10, 0) makes it the fixed point, and
+        // everything else turns about it.
+        properties.set_properties(DynamicProperties {
+            points: vec![PropertyValue { key, value: point }],
+            ..DynamicProperties::default()
+        });
+        assert_eq!(properties.flush_pending_updates(), PropertyDirtyFlags::POINTS);
+        assert!(close(transformed(&properties, point), point));
+        assert!(close(
+            transformed(&properties, LayoutPoint::zero()),
+            point + rotated(LayoutPoint::new(-10.0, 0.0)).to_vector(),
+        ));
+
+        assert_eq!(StackingContext::default().effective_transform(&properties), LayoutTransform::identity());
+    }
+
+    #[test]
+    fn referenced_bindings_per_document() {
+        let opacity = PropertyBindingKey::new(1);
+        let transform = PropertyBindingKey::new(2);
+        let unused = PropertyBindingKey::<f32>::new(3);
+
+        // Two unrelated documents: one root with a child whose opacity is
+        // bound, and one standalone pipeline with a bound transform.
+        let root = PipelineId(0, 1);
+        let child = PipelineId(0, 2);
+        let other = PipelineId(0, 3);
+
+        let mut builder = DisplayListBuilder::new(child, LayoutSize::new(100.0, 100.0));
+        builder.push_simple_stacking_context_with_filters(
+            LayoutPoint::zero(),
+            SpatialId::root_reference_frame(child),
+            true,
+            &[FilterOp::Opacity(PropertyBinding::Binding(opacity, 1.0), 1.0)],
+            &[],
+            &[],
+        );
+        builder.pop_stacking_context();
+        let (_, _, child_list) = builder.finalize();
+
+        let mut builder = DisplayListBuilder::new(other, LayoutSize::new(100.0, 100.0));
+        builder.push_reference_frame(
+            LayoutPoint::zero(),
+            SpatialId::root_reference_frame(other),
+            TransformStyle::Flat,
+            PropertyBinding::Binding(transform, LayoutTransform::identity()),
+            ReferenceFrameKind::Transform,
+        );
+        builder.pop_reference_frame();
+        let (_, _, other_list) = builder.finalize();
+
+        let scene = SceneTestBuilder::new()
+            .root(root)
+            .pipeline(root).display_list(iframe_pipeline(root, &[child]).display_list).add()
+            .pipeline(child).display_list(child_list).add()
+            .pipeline(other).display_list(other_list).add()
+            .build()
+            .unwrap();
+        let documents = scene.split_into_documents();
+        assert_eq!(documents.len(), 2);
+
+        // Each document only references the bindings of its own pipelines.
+        let referenced: Vec<Vec<PropertyBindingId>> = documents
+            .iter()
+            .map(|document| document.referenced_bindings().into_iter().collect())
+            .collect();
+        assert_eq!(referenced, vec![vec![opacity.id], vec![transform.id]]);
+
+        let referenced = scene.referenced_bindings();
+        assert!(referenced.contains(&opacity.id));
+        assert!(referenced.contains(&transform.id));
+        assert!(!referenced.contains(&unused.id));
+    }
+
+    #[test]
+    fn pipeline_bindings_follow_pipeline_replacement() {
+        let pipeline_id = PipelineId(0, 1);
+        let opacity = PropertyBindingKey::new(1);
+        let mut scene = Scene::new();
+        scene.add_pipeline(test_pipeline(pipeline_id), Epoch(0));
+        assert!(scene.pipeline_bindings(pipeline_id).unwrap().is_empty());
+
+        let mut builder = DisplayListBuilder::new(pipeline_id, LayoutSize::new(100.0, 100.0));
+        builder.push_simple_stacking_context_with_filters(
+            LayoutPoint::zero(),
+            SpatialId::root_reference_frame(pipeline_id),
+            true,
+            &[FilterOp::Opacity(PropertyBinding::Binding(opacity, 1.0), 1.0)],
+            &[],
+            &[],
+        );
+        builder.pop_stacking_context();
+        let (_, _, display_list) = builder.finalize();
+        let size = LayoutSize::new(100.0, 100.0);
+        scene.set_display_list(pipeline_id, Epoch(0), display_list, None, size, size);
+
+        assert!(scene.pipeline_bindings(pipeline_id).unwrap().contains(&opacity.id));
+        assert!(scene.pipeline_bindings(PipelineId(0, 2)).is_none());
+        assert_eq!(scene.referenced_bindings().into_iter().collect::<Vec<_>>(), vec![opacity.id]);
+    }
+
+    #[test]
+    fn snapshot_survives_later_flushes() {
+        let key = PropertyBindingKey::new(1);
+        let binding = PropertyBinding::Binding(key, 0.0);
+        let mut properties = SceneProperties::new();
+        let set_opacity = |properties: &mut SceneProperties, value| {
+            properties.set_properties(DynamicProperties {
+                floats: vec![PropertyValue { key, value }],
+                ..DynamicProperties::default()
+            });
+            properties.flush_pending_updates();
+        };
+        set_opacity(&mut properties, 0.25);
+
+        let snapshot = properties.snapshot_arc();
+        let reader = {
+            let snapshot = Arc::clone(&snapshot);
+            ::std::thread::spawn(move || snapshot.resolve_float(&binding))
+        };
+
+        set_opacity(&mut properties, 0.75);
+        assert_eq!(properties.resolve_float(&binding), 0.75);
+        assert_eq!(snapshot.resolve_float(&binding), 0.25);
+        assert_eq!(reader.join().unwrap(), 0.25);
+
+        // Snapshots taken between changes share the same maps.
+        drop(snapshot);
+        assert!(Arc::ptr_eq(&properties.snapshot_arc(), &properties.snapshot_arc()));
+    }
+
+    #[test]
+    fn merge_scenes_under_each_policy() {
+        let root = PipelineId(0, 1);
+        let shared = PipelineId(0, 2);
+        let ui = PipelineId(1, 1);
+        let ours = || {
+            SceneTestBuilder::new()
+                .root(root)
+                .pipeline(root).display_list(iframe_pipeline(root, &[shared]).display_list).add()
+                .pipeline(shared).epoch(Epoch(2)).add()
+                .build()
+                .unwrap()
+        };
+        let theirs = |shared_epoch| {
+            SceneTestBuilder::new()
+                .root(ui)
+                .pipeline(ui).display_list(iframe_pipeline(ui, &[shared]).display_list).add()
+                .pipeline(shared).epoch(shared_epoch).background(ColorF::WHITE).add()
+                .build()
+                .unwrap()
+        };
+
+        // Without collisions every pipeline is kept, along with our root.
+        let mut scene = ours();
+        let ui_only = SceneTestBuilder::new().root(ui).pipeline(ui).add().build().unwrap();
+        assert_eq!(scene.merge(ui_only, MergePolicy::Error), Ok(Vec::new()));
+        assert_eq!(scene.pipelines.len(), 3);
+        assert_eq!(scene.root_pipeline_id, Some(root));
+        assert!(scene.contains_pipeline(ui));
+
+        // Error fails without changing anything.
+        let mut scene = ours();
+        assert_eq!(scene.merge(theirs(Epoch(5)), MergePolicy::Error), Err(PipelineCollision(shared)));
+        assert_eq!(scene.pipelines.len(), 2);
+        assert!(!scene.contains_pipeline(ui));
+
+        // PreferNewer keeps whichever side has the higher epoch.
+        let mut scene = ours();
+        scene.merge(theirs(Epoch(1)), MergePolicy::PreferNewer).unwrap();
+        assert_eq!(scene.epoch_of(shared), Some(Epoch(2)));
+        assert_eq!(scene.pipelines[&shared].background_color, None);
+        assert!(scene.contains_pipeline(ui));
+        let mut scene = ours();
+        scene.merge(theirs(Epoch(5)), MergePolicy::PreferNewer).unwrap();
+        assert_eq!(scene.epoch_of(shared), Some(Epoch(5)));
+        assert_eq!(scene.pipelines[&shared].background_color, Some(ColorF::WHITE));
+
+        // PreferIncoming only differs on ties, which go to the incoming side.
+        let mut scene = ours();
+        scene.merge(theirs(Epoch(2)), MergePolicy::PreferIncoming).unwrap();
+        assert_eq!(scene.pipelines[&shared].background_color, Some(ColorF::WHITE));
+        assert_eq!(scene.root_pipeline_id, Some(root));
+        let mut scene = ours();
+        scene.merge(theirs(Epoch(1)), MergePolicy::PreferIncoming).unwrap();
+        assert_eq!(scene.epoch_of(shared), Some(Epoch(2)));
+        assert_eq!(scene.pipelines[&shared].background_color, None);
+
+        // Rename keeps both, and points the incoming iframe at the new id.
+        let mut scene = ours();
+        let renamed = PipelineId(0, 3);
+        assert_eq!(scene.merge(theirs(Epoch(5)), MergePolicy::Rename), Ok(vec![(shared, renamed)]));
+        assert_eq!(scene.pipelines.len(), 4);
+        assert_eq!(scene.epoch_of(shared), Some(Epoch(2)));
+        assert_eq!(scene.epoch_of(renamed), Some(Epoch(5)));
+        assert_eq!(scene.pipelines[&renamed].pipeline_id, renamed);
+        assert_eq!(iframe_references(&scene.pipelines[&ui].display_list), vec![renamed]);
+        assert_eq!(iframe_references(&scene.pipelines[&root].display_list), vec![shared]);
+    }
+
+    #[test]
+    fn resolve_anisotropic_blur_primitive() {
+        let key = PropertyBindingKey::new(1);
+        let blur = FilterPrimitive {
+            kind: FilterPrimitiveKind::Blur(BlurPrimitive {
+                input: FilterPrimitiveInput::Original,
+                radius: 1.0,
+                radius_y: Some(3.0),
+                radius_x_binding: Some(key),
+                radius_y_binding: None,
+            }),
+            color_space: ColorSpace::Srgb,
+        };
+        let mut properties = SceneProperties::new();
+        let deviations = |properties: &SceneProperties| {
+            let mut deviations = None;
+            with_stacking_context(&[], &[], &[blur], |sc, _, _, prims| {
+                let primitives = sc.resolve_filter_primitives_for_compositing(prims, properties);
+                match primitives[0].kind {
+                    FilterPrimitiveKind::Blur(ref blur) => deviations = Some(blur.std_deviation()),
+                    _ => panic!("expected a blur primitive"),
+                }
+            });
+            deviations.unwrap()
+        };
+
+        // Before the binding has a value, the static deviations are used.
+        assert_eq!(deviations(&properties), (1.0, 3.0));
+
+        // Animating x leaves y where it is.
+        for &(x, expected) in &[(2.0, (2.0, 3.0)), (8.0, (8.0, 3.0)), (-4.0, (0.0, 3.0))] {
+            properties.set_properties(DynamicProperties {
+                floats: vec![PropertyValue { key, value: x }],
+                ..DynamicProperties::default()
+            });
+            properties.flush_pending_updates();
+            assert_eq!(deviations(&properties), expected);
+        }
+    }
+
+    #[test]
+    fn touch_pipeline_bumps_epoch() {
+        let root = PipelineId(0, 1);
+        let child = PipelineId(0, 2);
+        let mut scene = Scene::new();
+        let set = |scene: &mut Scene, epoch, pipeline: ScenePipeline| {
+            scene.set_display_list(
+                root,
+                Epoch(epoch),
+                pipeline.display_list,
+                None,
+                pipeline.viewport_size,
+                pipeline.content_size,
+            );
+        };
+
+        set(&mut scene, 1, iframe_pipeline(root, &[child]));
+        let pipeline = Arc::clone(&scene.pipelines()[&root]);
+
+        // Touching bumps the epoch but keeps the content.
+        assert_eq!(scene.touch_pipeline(root), Some(Epoch(2)));
+        assert_eq!(scene.epoch_of(root), Some(Epoch(2)));
+        assert!(Arc::ptr_eq(&scene.pipelines()[&root], &pipeline));
+
+        assert_eq!(scene.touch_pipeline(child), None);
+    }
+
+    #[test]
+    fn global_transform_is_not_in_resolved_transforms() {
+        let key = PropertyBindingKey::<LayoutTransform>::new(1);
+        let binding = PropertyBinding::Binding(key, LayoutTransform::identity());
+        let translation = LayoutTransform::create_translation(10.0, 20.0, 0.0);
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key, value: translation }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+
+        let scale = LayoutTransform::create_scale(2.0, 2.0, 1.0);
+        properties.set_global_transform(scale);
+        assert_eq!(properties.flush_pending_updates(), PropertyDirtyFlags::TRANSFORMS);
+        assert_eq!(properties.global_transform(), scale);
+        assert_eq!(properties.resolve_layout_transform(&binding), translation);
+
+        // Setting the same transform again changes nothing.
+        properties.set_global_transform(scale);
+        assert!(properties.flush_pending_updates().is_empty());
+
+        properties.set_global_transform(LayoutTransform::identity());
+        assert_eq!(properties.flush_pending_updates(), PropertyDirtyFlags::TRANSFORMS);
+        assert_eq!(properties.global_transform(), LayoutTransform::identity());
+    }
+
+    #[test]
+    fn resolve_float_wrapped() {
+        let key = PropertyBindingKey::<f32>::new(1);
+        let binding = PropertyBinding::Binding(key, 0.0);
+        let mut properties = SceneProperties::new();
+        let mut resolve = |value, period| {
+            properties.set_properties(DynamicProperties {
+                floats: vec![PropertyValue { key, value }],
+                ..DynamicProperties::default()
+            });
+            properties.flush_pending_updates();
+            properties.resolve_float_wrapped(&binding, period)
+        };
+
+        assert_eq!(resolve(450.0, 360.0), 90.0);
+        assert_eq!(resolve(360.0, 360.0), 0.0);
+        assert_eq!(resolve(-90.0, 360.0), 270.0);
+        assert_eq!(resolve(-720.0, 360.0), 0.0);
+        assert!(resolve(-1.0e-6, 360.0) < 360.0);
+
+        // Without a positive period, the value is returned as is.
+        assert_eq!(resolve(450.0, 0.0), 450.0);
+        assert_eq!(resolve(-450.0, -360.0), -450.0);
+    }
+
+    #[test]
+    fn flatten_nested_iframes() {
+        let root = PipelineId(0, 1);
+        let child = PipelineId(0, 2);
+        let grandchild = PipelineId(0, 3);
+        let rect = LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(5.0, 5.0));
+        let iframe_bounds = |x, y| LayoutRect::new(LayoutPoint::new(x, y), LayoutSize::new(50.0, 50.0));
+        let build = |pipeline_id, iframe: Option<(PipelineId, LayoutRect)>| {
+            let space_and_clip = SpaceAndClipInfo::root_scroll(pipeline_id);
+            let mut builder = DisplayListBuilder::new(pipeline_id, LayoutSize::new(100.0, 100.0));
+            builder.push_rect(&CommonItemProperties::new(rect, space_and_clip), ColorF::WHITE);
+            if let Some((iframe_id, bounds)) = iframe {
+                builder.push_iframe(bounds, bounds, &space_and_clip, iframe_id, false);
+            }
+            builder.finalize().2
+        };
+
+        let scene = SceneTestBuilder::new()
+            .root(root)
+            .pipeline(root)
+            .display_list(build(root, Some((child, iframe_bounds(10.0, 10.0)))))
+            .add()
+            .pipeline(child)
+            .display_list(build(child, Some((grandchild, iframe_bounds(5.0, 5.0)))))
+            .add()
+            .pipeline(grandchild)
+            .display_list(build(grandchild, None))
+            .add()
+            .build()
+            .unwrap();
+
+        let flat = scene.flatten().unwrap();
+        assert_eq!(flat.pipeline_id, root);
+        assert!(iframe_references(&flat.display_list).is_empty());
+
+        // Find where each rect ends up, following the reference frames.
+        let mut offsets = FastHashMap::default();
+        offsets.insert(SpatialId::root_reference_frame(root), LayoutVector2D::zero());
+        offsets.insert(SpatialId::root_scroll_node(root), LayoutVector2D::zero());
+        let mut rects = Vec::new();
+        let mut iter = flat.display_list.iter();
+        while let Some(item) = iter.next() {
+            match *item.item() {
+                DisplayItem::PushReferenceFrame(ref info) => {
+                    let offset = offsets[&info.parent_spatial_id] + info.origin.to_vector();
+                    offsets.insert(info.reference_frame.id, offset);
+                }
+                DisplayItem::ScrollFrame(ref info) => {
+                    let offset = offsets[&info.parent_space_and_clip.spatial_id];
+                    offsets.insert(info.scroll_frame_id, offset);
+                }
+                DisplayItem::Rectangle(ref info) => {
+                    assert_eq!(info.common.spatial_id.pipeline_id(), root);
+                    rects.push(info.common.clip_rect.origin + offsets[&info.common.spatial_id]);
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(rects, vec![
+            LayoutPoint::new(0.0, 0.0),
+            LayoutPoint::new(10.0, 10.0),
+            LayoutPoint::new(15.0, 15.0),
+        ]);
+
+        // Without a root pipeline there is nothing to flatten.
+        assert!(Scene::new().flatten().is_none());
+    }
+
+    #[test]
+    fn flatten_gives_iframe_scroll_frames_unique_ids() {
+        let root = PipelineId(0, 1);
+        let child = PipelineId(0, 2);
+        let bounds = LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(50.0, 50.0));
+
+        // The root has a scroll frame with the id the flattener would give
+        // the child's root scroll node, and embeds the child twice.
+        let mut builder = DisplayListBuilder::new(root, LayoutSize::new(100.0, 100.0));
+        builder.define_scroll_frame(
+            &SpaceAndClipInfo::root_scroll(root),
+            Some(ExternalScrollId(0, child)),
+            bounds,
+            bounds,
+            None,
+            None,
+            ScrollSensitivity::ScriptAndInputEvents,
+            LayoutVector2D::zero(),
+        );
+        builder.push_iframe(bounds, bounds, &SpaceAndClipInfo::root_scroll(root), child, false);
+        builder.push_iframe(bounds, bounds, &SpaceAndClipInfo::root_scroll(root), child, false);
+        let root_display_list = builder.finalize().2;
+
+        let scene = SceneTestBuilder::new()
+            .root(root)
+            .pipeline(root).display_list(root_display_list).add()
+            .pipeline(child).add()
+            .build()
+            .unwrap();
+
+        let flat = scene.flatten().unwrap();
+        let mut scroll_ids = Vec::new();
+        let mut iter = flat.display_list.iter();
+        while let Some(item) = iter.next() {
+            if let DisplayItem::ScrollFrame(ref info) = *item.item() {
+                scroll_ids.push(info.external_id.unwrap());
+            }
+        }
+        assert_eq!(scroll_ids, vec![
+            ExternalScrollId(0, child),
+            ExternalScrollId(1, child),
+            ExternalScrollId(2, child),
+        ]);
+    }
+
+    #[test]
+    fn needs_surface() {
+        let opacity = PropertyBindingKey::new(1);
+        let blend = PropertyBindingKey::new(2);
+        let bound_opacity = FilterOp::Opacity(PropertyBinding::Binding(opacity, 1.0), 1.0);
+        let mut properties = SceneProperties::new();
+
+        // A flat, normal stacking context with noop filters is trivial.
+        with_stacking_context(&[bound_opacity, FilterOp::Blur(0.0), FilterOp::Sepia(0.0)], &[], &[], |sc, filters, _, _| {
+            assert!(!sc.needs_surface(filters, &properties));
+        });
+
+        // Each of these needs isolation.
+        let filters = [
+            FilterOp::Opacity(PropertyBinding::Value(0.5), 0.5),
+            FilterOp::Blur(2.0),
+            FilterOp::Identity,
+            FilterOp::Flood(ColorF::WHITE),
+        ];
+        for filter in &filters {
+            with_stacking_context(&[*filter], &[], &[], |sc, filters, _, _| {
+                assert!(sc.needs_surface(filters, &properties), "{:?}", filter);
+            });
+        }
+
+        with_stacking_context(&[], &[], &[], |sc, filters, _, _| {
+            assert!(!sc.needs_surface(filters, &properties));
+            let variants = [
+                StackingContext { mix_blend_mode: MixBlendMode::Multiply, ..*sc },
+                StackingContext { transform_style: TransformStyle::Preserve3D, ..*sc },
+                StackingContext { cache_tiles: true, ..*sc },
+                StackingContext { raster_space: RasterSpace::Local(1.0), ..*sc },
+            ];
+            for variant in &variants {
+                assert!(variant.needs_surface(filters, &properties), "{:?}", variant);
+            }
+        });
+
+        // Bindings are resolved: a bound opacity below 1 and a bound blend
+        // mode that isn't normal both need a surface.
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key: opacity, value: 0.5 }],
+            ints: vec![PropertyValue { key: blend, value: MixBlendMode::Screen as i32 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        with_stacking_context(&[bound_opacity], &[], &[], |sc, filters, _, _| {
+            assert!(sc.needs_surface(filters, &properties));
+        });
+        with_stacking_context(&[], &[], &[], |sc, filters, _, _| {
+            let bound_blend = StackingContext { mix_blend_mode_binding: Some(blend), ..*sc };
+            assert!(bound_blend.needs_surface(filters, &properties));
+        });
+    }
+
+    #[cfg(all(feature = "capture", feature = "replay"))]
+    #[test]
+    fn import_capture_with_offsets() {
+        let root = PipelineId(0, 1);
+        let child = PipelineId(0, 2);
+        let scene = SceneTestBuilder::new()
+            .root(root)
+            .pipeline(root)
+            .display_list(iframe_pipeline(root, &[child]).display_list)
+            .add()
+            .pipeline(child)
+            .epoch(Epoch(3))
+            .add()
+            .build()
+            .unwrap();
+        let capture = scene.serialize_versioned().unwrap();
+
+        let first = Scene::import_capture(&mut &capture[..], 10).unwrap();
+        let second = Scene::import_capture(&mut &capture[..], 20).unwrap();
+        for (imported, offset) in &[(&first, 10), (&second, 20)] {
+            let root = PipelineId(0, 1 + offset);
+            let child = PipelineId(0, 2 + offset);
+            assert_eq!(imported.root_pipeline_id, Some(root));
+            assert_eq!(imported.iframe_children(root), vec![child]);
+            assert_eq!(imported.epoch_of(child), Some(Epoch(3)));
+            assert_eq!(imported.pipelines[&child].pipeline_id, child);
+            assert_eq!(imported.render_order(), vec![root, child]);
+        }
+        assert!(first.pipelines.keys().all(|pipeline_id| !second.pipelines.contains_key(pipeline_id)));
+
+        // Imports that would overflow an id, or from a newer schema, fail.
+        match Scene::import_capture(&mut &capture[..], u32::max_value()) {
+            Err(CaptureError::IdOverflow(pipeline_id)) => assert!(pipeline_id.1 > 0),
+            _ => panic!("expected an overflow"),
+        }
+        let mut future = capture.clone();
+        future[.. 4].copy_from_slice(&(SCENE_SCHEMA_VERSION + 1).to_le_bytes());
+        let err = Scene::import_capture(&mut &future[..], 10).err().unwrap();
+        assert!(err.to_string().contains(&format!("version {}", SCENE_SCHEMA_VERSION + 1)));
+    }
+
+    #[test]
+    fn resolve_color_from_added_properties() {
+        let background = PropertyBindingKey::<ColorF>::new(1);
+        let border = PropertyBindingKey::<ColorF>::new(2);
+        let missing = PropertyBindingKey::<ColorF>::new(3);
+        let red = ColorF::new(1.0, 0.0, 0.0, 1.0);
+        let mut properties = SceneProperties::new();
+
+        // Colors from several add_properties calls accumulate until the
+        // flush.
+        properties.add_properties(DynamicProperties {
+            colors: vec![PropertyValue { key: background, value: red }],
+            ..DynamicProperties::default()
+        });
+        properties.add_properties(DynamicProperties {
+            colors: vec![PropertyValue { key: border, value: ColorF::WHITE }],
+            ..DynamicProperties::default()
+        });
+        assert_eq!(properties.flush_pending_updates(), PropertyDirtyFlags::COLORS);
+
+        let resolve = |key| properties.resolve_color(&PropertyBinding::Binding(key, ColorF::BLACK));
+        assert_eq!(resolve(background), red);
+        assert_eq!(resolve(border), ColorF::WHITE);
+        assert_eq!(resolve(missing), ColorF::BLACK);
+        assert_eq!(properties.resolve_color(&PropertyBinding::Value(red)), red);
+    }
+
+    #[test]
+    fn flush_pending_updates_detailed() {
+        let moved = PropertyBindingKey::<LayoutTransform>::new(1);
+        let still = PropertyBindingKey::<LayoutTransform>::new(2);
+        let faded = PropertyBindingKey::<f32>::new(3);
+        let mut properties = SceneProperties::new();
+        let set = |properties: &mut SceneProperties, x: f32, floats| {
+            properties.set_properties(DynamicProperties {
+                transforms: vec![
+                    PropertyValue { key: moved, value: LayoutTransform::create_translation(x, 0.0, 0.0) },
+                    PropertyValue { key: still, value: LayoutTransform::identity() },
+                ],
+                floats,
+                ..DynamicProperties::default()
+            });
+            properties.flush_pending_updates_detailed()
+        };
+
+        let changed = set(&mut properties, 1.0, vec![PropertyValue { key: faded, value: 0.5 }]);
+        assert_eq!(changed.kinds, PropertyDirtyFlags::TRANSFORMS | PropertyDirtyFlags::FLOATS);
+        assert_eq!(changed.change_set.bindings.len(), 3);
+
+        // Only the transform that moved and the float that was removed are
+        // reported; the transform set to the same value isn't.
+        let changed = set(&mut properties, 2.0, vec![]);
+        assert_eq!(changed.kinds, PropertyDirtyFlags::TRANSFORMS | PropertyDirtyFlags::FLOATS);
+        let expected: FastHashSet<_> = [moved.id, faded.id].iter().cloned().collect();
+        assert_eq!(changed.change_set.bindings, expected);
+        assert_eq!(properties.last_change_set(), &changed.change_set);
+
+        let changed = set(&mut properties, 2.0, vec![]);
+        assert!(changed.is_empty());
+        assert!(changed.change_set.is_empty());
+    }
+
+    #[test]
+    fn merge_by_id_replaces_and_appends() {
+        let value = |id, value| PropertyValue { key: PropertyBindingKey::new(id), value };
+        let mut into = vec![value(1, 0.0), value(2, 0.0)];
+        merge_by_id(&mut into, vec![value(2, 1.0), value(3, 1.0), value(3, 2.0)], |property| property.key.id);
+        assert_eq!(into, vec![value(1, 0.0), value(2, 1.0), value(3, 2.0)]);
+    }
+
+    #[test]
+    fn add_properties_replaces_pending_values() {
+        let transform = PropertyBindingKey::<LayoutTransform>::new(1);
+        let opacity = PropertyBindingKey::<f32>::new(2);
+        let mut properties = SceneProperties::new();
+        for frame in 0 .. 100 {
+            properties.add_properties(DynamicProperties {
+                transforms: vec![PropertyValue {
+                    key: transform,
+                    value: LayoutTransform::create_translation(frame as f32, 0.0, 0.0),
+                }],
+                floats: vec![PropertyValue { key: opacity, value: frame as f32 / 100.0 }],
+                ..DynamicProperties::default()
+            });
+        }
+
+        {
+            let pending = properties.pending_properties.as_ref().unwrap();
+            assert_eq!(pending.transforms.len(), 1);
+            assert_eq!(pending.floats.len(), 1);
+        }
+
+        properties.flush_pending_updates();
+        assert_eq!(
+            properties.resolve_layout_transform(&PropertyBinding::Binding(transform, LayoutTransform::identity())),
+            LayoutTransform::create_translation(99.0, 0.0, 0.0),
+        );
+        assert_eq!(properties.resolve_float(&PropertyBinding::Binding(opacity, 1.0)), 0.99);
+    }
+
+    #[test]
+    fn reachable_pipelines() {
+        let root = PipelineId(0, 1);
+        let a = PipelineId(0, 2);
+        let b = PipelineId(0, 3);
+        let orphan = PipelineId(0, 4);
+        let missing = PipelineId(0, 5);
+        let mut scene = Scene::new();
+        assert!(scene.reachable_pipelines().is_empty());
+
+        // b embeds its ancestor a, and a pipeline that isn't in the scene.
+        scene.set_root_pipeline_id(root);
+        assert!(scene.reachable_pipelines().is_empty());
+        scene.add_pipeline(iframe_pipeline(root, &[a]), Epoch(0));
+        scene.add_pipeline(iframe_pipeline(a, &[b]), Epoch(0));
+        scene.add_pipeline(iframe_pipeline(b, &[a, missing]), Epoch(0));
+        scene.add_pipeline(iframe_pipeline(orphan, &[root]), Epoch(0));
+
+        let expected: FastHashSet<_> = [root, a, b].iter().cloned().collect();
+        assert_eq!(scene.reachable_pipelines(), expected);
+
+        // Dropping the iframe leaves its subtree unreachable.
+        scene.add_pipeline(test_pipeline(root), Epoch(0));
+        let expected: FastHashSet<_> = [root].iter().cloned().collect();
+        assert_eq!(scene.reachable_pipelines(), expected);
+    }
+
+    #[test]
+    fn malformed_filter_data() {
+        let table = FilterData {
+            func_r_type: ComponentTransferFuncType::Table,
+            r_values: vec![0.0, 1.0],
+            func_g_type: ComponentTransferFuncType::Identity,
+            g_values: Vec::new(),
+            func_b_type: ComponentTransferFuncType::Identity,
+            b_values: Vec::new(),
+            func_a_type: ComponentTransferFuncType::Identity,
+            a_values: Vec::new(),
+        };
+        with_stacking_context(&[FilterOp::ComponentTransfer], &[table.clone()], &[], |sc, _, datas, _| {
+            assert_eq!(sc.filter_datas_for_compositing(datas).unwrap(), vec![table.clone()]);
+
+            // A table missing its function types is reported rather than
+            // indexed into.
+            let malformed = [datas[0], TempFilterData {
+                func_types: ItemRange::default(),
+                r_values: ItemRange::default(),
+                g_values: ItemRange::default(),
+                b_values: ItemRange::default(),
+                a_values: ItemRange::default(),
+            }];
+            assert_eq!(
+                sc.filter_datas_for_compositing(&malformed),
+                Err(FilterDataError { index: 1, func_type_count: 0 }),
+            );
+        });
+    }
+
+    #[test]
+    fn transform_and_current_properties() {
+        let key = PropertyBindingKey::new(1);
+        let transform = LayoutTransform::create_translation(10.0, 0.0, 0.0);
+        let properties = DynamicProperties {
+            transforms: vec![PropertyValue { key, value: transform }],
+            floats: vec![PropertyValue { key: PropertyBindingKey::new(2), value: 0.5 }],
+            ..DynamicProperties::default()
+        };
+
+        let mut scene_properties = SceneProperties::new();
+        scene_properties.set_properties(properties.clone());
+        assert!(scene_properties.transform_properties().is_empty());
+        assert_eq!(scene_properties.current_properties(), &DynamicProperties::default());
+
+        scene_properties.flush_pending_updates();
+        assert_eq!(scene_properties.transform_properties().len(), 1);
+        assert_eq!(scene_properties.transform_properties().get(&key.id), Some(&transform));
+        assert_eq!(scene_properties.current_properties(), &properties);
+    }
+
+    #[test]
+    fn report_memory() {
+        let small = PipelineId(0, 1);
+        let large = PipelineId(0, 2);
+        let removed = PipelineId(0, 3);
+        let size = LayoutSize::new(10.0, 10.0);
+        let display_list = |count| {
+            let mut builder = DisplayListBuilder::new(large, size);
+            for _ in 0 .. count {
+                builder.push_rect(
+                    &CommonItemProperties::new(
+                        LayoutRect::new(LayoutPoint::zero(), size),
+                        SpaceAndClipInfo::root_scroll(large),
+                    ),
+                    ColorF::WHITE,
+                );
+            }
+            builder.finalize().2
+        };
+
+        let mut scene = Scene::new();
+        scene.set_display_list(small, Epoch(1), display_list(1), None, size, size);
+        scene.set_display_list(large, Epoch(1), display_list(10), None, size, size);
+        scene.set_display_list(removed, Epoch(1), display_list(1), None, size, size);
+        scene.remove_pipeline(removed);
+        scene.update_epoch(removed, Epoch(2));
+
+        let report = scene.report_memory();
+        assert_eq!(report.pipelines.len(), 3);
+        assert!(report.pipelines[&large] > report.pipelines[&small]);
+        assert!(report.pipelines[&removed] < report.pipelines[&small]);
+        assert_eq!(report.total_bytes, report.pipelines.values().sum::<usize>());
+    }
+
+    #[test]
+    fn get_pipeline_and_epoch() {
+        let pipeline_id = PipelineId(0, 1);
+        let size = LayoutSize::new(10.0, 10.0);
+        let mut scene = Scene::new();
+        assert!(scene.get_pipeline(pipeline_id).is_none());
+        assert!(scene.get_pipeline_and_epoch(pipeline_id).is_none());
+
+        scene.set_display_list(pipeline_id, Epoch(3), BuiltDisplayList::default(), None, size, size);
+        let pipeline = scene.get_pipeline(pipeline_id).unwrap();
+        assert!(Arc::ptr_eq(pipeline, &scene.pipelines[&pipeline_id]));
+        let (pipeline, epoch) = scene.get_pipeline_and_epoch(pipeline_id).unwrap();
+        assert_eq!(pipeline.pipeline_id, pipeline_id);
+        assert_eq!(epoch, Epoch(3));
+    }
+
+    #[test]
+    fn reject_stale_epochs() {
+        let pipeline_id = PipelineId(0, 1);
+        let size = LayoutSize::new(10.0, 10.0);
+        let mut scene = Scene::new();
+        scene.set_epoch_policy(EpochPolicy::RejectStale);
+
+        assert_eq!(
+            scene.set_display_list(pipeline_id, Epoch(2), BuiltDisplayList::default(), None, size, size),
+            PipelineUpdate::Applied,
+        );
+        let pipeline = Arc::clone(&scene.pipelines[&pipeline_id]);
+
+        // Older epochs are ignored.
+        assert_eq!(
+            scene.set_display_list(pipeline_id, Epoch(1), BuiltDisplayList::default(), None, size, size),
+            PipelineUpdate::RejectedStale,
+        );
+        assert!(Arc::ptr_eq(&pipeline, &scene.pipelines[&pipeline_id]));
+
+        // The current epoch can be resubmitted.
+        assert_eq!(
+            scene.set_display_list(pipeline_id, Epoch(2), BuiltDisplayList::default(), Some(ColorF::BLACK), size, size),
+            PipelineUpdate::Applied,
+        );
+        assert!(!scene.update_epoch(pipeline_id, Epoch(1)));
+        assert_eq!(scene.epoch_of(pipeline_id), Some(Epoch(2)));
+
+        assert!(scene.update_epoch(pipeline_id, Epoch(3)));
+        assert_eq!(
+            scene.set_display_list(pipeline_id, Epoch(4), BuiltDisplayList::default(), Some(ColorF::WHITE), size, size),
+            PipelineUpdate::Applied,
+        );
+        assert_eq!(scene.epoch_of(pipeline_id), Some(Epoch(4)));
+
+        // Other policies install every epoch.
+        scene.set_epoch_policy(EpochPolicy::Free);
+        assert!(scene.update_epoch(pipeline_id, Epoch(1)));
+        assert_eq!(scene.epoch_of(pipeline_id), Some(Epoch(1)));
+    }
+
+    #[test]
+    fn filter_iterators() {
+        let filters = [FilterOp::Blur(2.0), FilterOp::Opacity(PropertyBinding::Value(0.5), 0.5)];
+        let valid = [
+            blur_primitive(FilterPrimitiveInput::Original),
+            blur_primitive(FilterPrimitiveInput::OutputOfPrimitiveIndex(0)),
+        ];
+        with_stacking_context(&filters, &[], &valid, |sc, filters, _, prims| {
+            assert_eq!(sc.filter_ops_iter(filters).size_hint(), (2, Some(2)));
+            assert_eq!(sc.filter_ops_iter(filters).collect::<Vec<_>>(), sc.filter_ops_for_compositing(filters));
+            assert_eq!(sc.filter_primitives_iter(prims).collect::<Vec<_>>(), valid.to_vec());
+        });
+
+        // A primitive that reads its own output makes the graph malformed.
+        let malformed = [blur_primitive(FilterPrimitiveInput::OutputOfPrimitiveIndex(0))];
+        with_stacking_context(&[], &[], &malformed, |sc, filters, _, prims| {
+            assert_eq!(sc.filter_ops_iter(filters).next(), None);
+            assert_eq!(sc.filter_primitives_iter(prims).size_hint(), (0, Some(0)));
+            assert_eq!(sc.filter_primitives_iter(prims).next(), None);
+        });
+    }
+
+    #[test]
+    fn retain_referenced() {
+        let live = PropertyBindingKey::new(1);
+        let dead = PropertyBindingKey::new(2);
+        let dead_float = PropertyBindingKey::new(3);
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            transforms: vec![
+                PropertyValue { key: live, value: LayoutTransform::identity() },
+                PropertyValue { key: dead, value: LayoutTransform::identity() },
+            ],
+            floats: vec![PropertyValue { key: dead_float, value: 0.5 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        properties.set_spring_target(dead_float.id, 1.0, 100.0, 10.0);
+        assert_eq!(properties.transform_properties().len(), 2);
+        assert_eq!(properties.float_properties().len(), 1);
+
+        let snapshot = properties.snapshot_arc();
+        let mut live_ids = FastHashSet::default();
+        live_ids.insert(live.id);
+        properties.retain_referenced(&live_ids);
+        assert_eq!(properties.transform_properties().keys().collect::<Vec<_>>(), vec![&live.id]);
+        assert!(properties.float_properties().is_empty());
+        assert_eq!(properties.current_properties().transforms.len(), 1);
+        #[cfg(feature = "debug_properties")]
+        {
+            assert!(properties.last_changed_generation(live.id).is_some());
+            assert_eq!(properties.last_changed_generation(dead.id), None);
+        }
+
+        // Outstanding snapshots keep the values they were taken with.
+        assert_eq!(snapshot.resolve_float(&PropertyBinding::Binding(dead_float, 0.0)), 0.5);
+    }
+
+    #[test]
+    fn resolve_slices_of_bindings() {
+        let bound = PropertyBindingKey::new(1);
+        let unbound = PropertyBindingKey::new(2);
+        let bound_transform = PropertyBindingKey::new(3);
+        let translation = LayoutTransform::create_translation(5.0, 0.0, 0.0);
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key: bound_transform, value: translation }],
+            floats: vec![PropertyValue { key: bound, value: 0.25 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+
+        let floats = [
+            PropertyBinding::Value(0.5),
+            PropertyBinding::Binding(bound, 1.0),
+            PropertyBinding::Binding(unbound, 0.75),
+        ];
+        let mut out = vec![9.0; 5];
+        properties.resolve_floats(&floats, &mut out);
+        assert_eq!(out, floats.iter().map(|binding| properties.resolve_float(binding)).collect::<Vec<_>>());
+        assert_eq!(out, vec![0.5, 0.25, 0.75]);
+
+        let scale = LayoutTransform::create_scale(2.0, 2.0, 1.0);
+        let transforms = [
+            PropertyBinding::Binding(bound_transform, LayoutTransform::identity()),
+            PropertyBinding::Value(scale),
+        ];
+        let mut out = Vec::new();
+        properties.resolve_layout_transforms(&transforms, &mut out);
+        assert_eq!(out, vec![translation, scale]);
+    }
+
+    #[test]
+    fn validate_pipeline_references() {
+        let root = PipelineId(0, 1);
+        let a = PipelineId(0, 2);
+        let missing = PipelineId(0, 3);
+        let ignored = PipelineId(0, 4);
+        let mut scene = Scene::new();
+        assert_eq!(scene.validate(), Ok(()));
+
+        scene.set_root_pipeline_id(root);
+        assert_eq!(scene.validate(), Err(vec![SceneValidationError::MissingRoot(root)]));
+
+        scene.add_pipeline(iframe_pipeline(root, &[a]), Epoch(0));
+        scene.add_pipeline(iframe_pipeline(a, &[missing]), Epoch(0));
+        assert_eq!(scene.validate(), Err(vec![
+            SceneValidationError::MissingIframePipeline { parent: a, child: missing },
+        ]));
+
+        // Iframes that opt in to missing pipelines are not reported.
+        let bounds = LayoutRect::new(LayoutPoint::zero(), LayoutSize::new(10.0, 10.0));
+        let mut builder = DisplayListBuilder::new(a, LayoutSize::new(100.0, 100.0));
+        builder.push_iframe(bounds, bounds, &SpaceAndClipInfo::root_scroll(a), ignored, true);
+        let mut pipeline = test_pipeline(a);
+        pipeline.set_display_list(builder.finalize().2);
+        scene.add_pipeline(pipeline, Epoch(0));
+        assert_eq!(scene.validate(), Ok(()));
+    }
+
+    #[test]
+    fn set_identical_display_list() {
+        let pipeline_id = PipelineId(0, 1);
+        let size = LayoutSize::new(10.0, 10.0);
+        let display_list = || {
+            let mut builder = DisplayListBuilder::new(pipeline_id, size);
+            builder.push_rect(
+                &CommonItemProperties::new(
+                    LayoutRect::new(LayoutPoint::zero(), size),
+                    SpaceAndClipInfo::root_scroll(pipeline_id),
+                ),
+                ColorF::WHITE,
+            );
+            builder.finalize().2
+        };
+
+        let mut scene = Scene::new();
+        assert_eq!(
+            scene.set_display_list(pipeline_id, Epoch(1), display_list(), None, size, size),
+            PipelineUpdate::Applied,
+        );
+        let pipeline = Arc::clone(&scene.pipelines[&pipeline_id]);
+
+        // Re-sending the same content only bumps the epoch.
+        assert_eq!(
+            scene.set_display_list(pipeline_id, Epoch(2), display_list(), None, size, size),
+            PipelineUpdate::Unchanged,
+        );
+        assert!(Arc::ptr_eq(&pipeline, &scene.pipelines[&pipeline_id]));
+        assert_eq!(scene.epoch_of(pipeline_id), Some(Epoch(2)));
+
+        // Any difference in the sizes or background replaces the pipeline.
+        let larger = LayoutSize::new(20.0, 20.0);
+        assert_eq!(
+            scene.set_display_list(pipeline_id, Epoch(3), display_list(), None, larger, size),
+            PipelineUpdate::Applied,
+        );
+        assert!(!Arc::ptr_eq(&pipeline, &scene.pipelines[&pipeline_id]));
+        assert_eq!(
+            scene.set_display_list(pipeline_id, Epoch(4), display_list(), Some(ColorF::BLACK), larger, size),
+            PipelineUpdate::Applied,
+        );
+        assert_eq!(
+            scene.set_display_list(pipeline_id, Epoch(5), BuiltDisplayList::default(), Some(ColorF::BLACK), larger, size),
+            PipelineUpdate::Applied,
+        );
+        assert_eq!(scene.epoch_of(pipeline_id), Some(Epoch(5)));
+    }
+
+    #[test]
+    fn snapshot_and_restore_properties() {
+        let key = PropertyBindingKey::new(1);
+        let binding = PropertyBinding::Binding(key, 0.0);
+        let opacity = |value| DynamicProperties {
+            floats: vec![PropertyValue { key, value }],
+            ..DynamicProperties::default()
+        };
+        let mut properties = SceneProperties::new();
+        properties.set_properties(opacity(0.25));
+        properties.flush_pending_updates();
+        properties.set_properties(opacity(0.5));
+        let snapshot = properties.snapshot();
+
+        // Later flushes and spring steps don't affect the snapshot.
+        properties.flush_pending_updates();
+        properties.set_spring_target(key.id, 1.0, 100.0, 10.0);
+        properties.advance_springs(0.1);
+        properties.set_properties(opacity(0.75));
+        assert_ne!(properties.resolve_float(&binding), 0.25);
+
+        properties.restore(snapshot.clone());
+        assert_eq!(properties.resolve_float(&binding), 0.25);
+        assert_eq!(properties.current_properties(), &opacity(0.25));
+        assert!(!properties.flush_pending_updates().is_empty());
+        assert_eq!(properties.resolve_float(&binding), 0.5);
+
+        // A snapshot can be restored more than once.
+        properties.restore(snapshot);
+        assert_eq!(properties.resolve_float(&binding), 0.25);
+    }
+
+    #[test]
+    fn add_pipeline_from_builder() {
+        let pipeline_id = PipelineId(0, 1);
+        let viewport_size = LayoutSize::new(10.0, 20.0);
+        let content_size = LayoutSize::new(30.0, 40.0);
+        let mut builder = DisplayListBuilder::new(pipeline_id, content_size);
+        builder.push_rect(
+            &CommonItemProperties::new(
+                LayoutRect::new(LayoutPoint::zero(), content_size),
+                SpaceAndClipInfo::root_scroll(pipeline_id),
+            ),
+            ColorF::WHITE,
+        );
+        let display_list = builder.finalize().2;
+
+        let pipeline = ScenePipelineBuilder::new(pipeline_id)
+            .viewport_size(viewport_size)
+            .content_size(content_size)
+            .background_color(Some(ColorF::BLACK))
+            .display_list(display_list.clone())
+            .build();
+        assert_eq!(pipeline.viewport_size, viewport_size);
+        assert_eq!(pipeline.content_size, content_size);
+
+        let mut scene = Scene::new();
+        assert_eq!(scene.add_pipeline(pipeline, Epoch(1)), PipelineUpdate::Applied);
+        assert_eq!(scene.epoch_of(pipeline_id), Some(Epoch(1)));
+        assert_eq!(scene.pipelines[&pipeline_id].background_color, Some(ColorF::BLACK));
+
+        // The positional form builds an identical pipeline.
+        let update = scene.set_display_list(
+            pipeline_id,
+            Epoch(2),
+            display_list,
+            Some(ColorF::BLACK),
+            viewport_size,
+            content_size,
+        );
+        assert_eq!(update, PipelineUpdate::Unchanged);
+        assert_eq!(scene.epoch_of(pipeline_id), Some(Epoch(2)));
+
+        // Unset fields take their defaults.
+        let empty = ScenePipelineBuilder::new(pipeline_id).build();
+        assert_eq!(empty.viewport_size, LayoutSize::zero());
+        assert_eq!(empty.background_color, None);
+        assert_eq!(scene.add_pipeline(empty, Epoch(3)), PipelineUpdate::Applied);
+    }
+
+    #[test]
+    fn remove_pipeline_subtree() {
+        let root = PipelineId(0, 1);
+        let frame = PipelineId(0, 2);
+        let child = PipelineId(0, 3);
+        let grandchild = PipelineId(0, 4);
+        let shared = PipelineId(0, 5);
+        let orphan = PipelineId(0, 6);
+        let orphan_child = PipelineId(0, 7);
+        let mut scene = Scene::new();
+        scene.set_root_pipeline_id(root);
+        scene.add_pipeline(iframe_pipeline(root, &[frame, shared]), Epoch(0));
+        // The subtree loops back to the removed pipeline.
+        scene.add_pipeline(iframe_pipeline(frame, &[child, shared]), Epoch(0));
+        scene.add_pipeline(iframe_pipeline(child, &[grandchild, frame]), Epoch(0));
+        scene.add_pipeline(test_pipeline(grandchild), Epoch(0));
+        scene.add_pipeline(test_pipeline(shared), Epoch(0));
+        // A pipeline outside the root's tree keeps its children alive.
+        scene.add_pipeline(iframe_pipeline(orphan, &[orphan_child]), Epoch(0));
+        scene.add_pipeline(iframe_pipeline(orphan_child, &[]), Epoch(0));
+
+        assert!(scene.remove_pipeline_subtree(PipelineId(0, 99)).is_empty());
+
+        let expected: FastHashSet<_> = [frame, child, grandchild].iter().cloned().collect();
+        assert_eq!(scene.remove_pipeline_subtree(frame), expected);
+        let mut remaining: Vec<_> = scene.pipelines.keys().cloned().collect();
+        remaining.sort_by_key(|id| (id.0, id.1));
+        assert_eq!(remaining, vec![root, shared, orphan, orphan_child]);
+
+        // Removing the root takes everything only it kept alive.
+        let expected: FastHashSet<_> = [root, shared].iter().cloned().collect();
+        assert_eq!(scene.remove_pipeline_subtree(root), expected);
+        assert_eq!(scene.root_pipeline_id, None);
+        assert_eq!(scene.pipelines.len(), 2);
+    }
+
+    #[test]
+    fn update_properties() {
+        let opacity = PropertyBindingKey::new(1);
+        let other_opacity = PropertyBindingKey::new(2);
+        let transform = PropertyBindingKey::new(3);
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key: transform, value: LayoutTransform::identity() }],
+            floats: vec![
+                PropertyValue { key: opacity, value: 0.5 },
+                PropertyValue { key: other_opacity, value: 0.25 },
+            ],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+
+        // Only the mentioned binding changes, and the ones not mentioned
+        // in this transaction are kept.
+        properties.update_properties(DynamicProperties {
+            floats: vec![PropertyValue { key: opacity, value: 1.0 }],
+            ..DynamicProperties::default()
+        });
+        let translation = LayoutTransform::create_translation(1.0, 0.0, 0.0);
+        properties.update_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key: transform, value: translation }],
+            ..DynamicProperties::default()
+        });
+        assert_eq!(
+            properties.flush_pending_updates(),
+            PropertyDirtyFlags::TRANSFORMS | PropertyDirtyFlags::FLOATS,
+        );
+        assert_eq!(properties.resolve_float(&PropertyBinding::Binding(opacity, 0.0)), 1.0);
+        assert_eq!(properties.resolve_float(&PropertyBinding::Binding(other_opacity, 0.0)), 0.25);
+        assert_eq!(
+            properties.resolve_layout_transform(&PropertyBinding::Binding(transform, LayoutTransform::identity())),
+            translation,
+        );
+
+        // Updating a binding to the value it already has isn't a change.
+        properties.update_properties(DynamicProperties {
+            floats: vec![PropertyValue { key: opacity, value: 1.0 }],
+            ..DynamicProperties::default()
+        });
+        assert!(properties.flush_pending_updates().is_empty());
+
+        // Bindings dropped earlier in the transaction, by a full list or by
+        // an update, aren't brought back by a later update.
+        properties.set_properties(DynamicProperties {
+            floats: vec![PropertyValue { key: opacity, value: 1.0 }],
+            ..DynamicProperties::default()
+        });
+        properties.update_properties(DynamicProperties {
+            removed: vec![opacity.id],
+            ..DynamicProperties::default()
+        });
+        properties.update_properties(DynamicProperties {
+            floats: vec![PropertyValue { key: other_opacity, value: 0.75 }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        assert_eq!(properties.resolve_float(&PropertyBinding::Binding(opacity, 0.0)), 0.0);
+        assert_eq!(properties.resolve_float(&PropertyBinding::Binding(other_opacity, 0.0)), 0.75);
+        assert_eq!(
+            properties.resolve_layout_transform(&PropertyBinding::Binding(transform, LayoutTransform::identity())),
+            LayoutTransform::identity(),
+        );
+    }
+
+    #[test]
+    fn try_resolve_layout_transform() {
+        let bound = PropertyBindingKey::new(1);
+        let unbound = PropertyBindingKey::new(2);
+        let translation = LayoutTransform::create_translation(1.0, 2.0, 0.0);
+        let scale = LayoutTransform::create_scale(2.0, 2.0, 1.0);
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key: bound, value: translation }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+
+        assert_eq!(
+            properties.try_resolve_layout_transform(&PropertyBinding::Binding(bound, scale)),
+            (translation, true),
+        );
+        assert_eq!(
+            properties.try_resolve_layout_transform(&PropertyBinding::Binding(unbound, scale)),
+            (scale, false),
+        );
+        assert_eq!(
+            properties.try_resolve_layout_transform(&PropertyBinding::Value(scale)),
+            (scale, true),
+        );
+        assert_eq!(properties.resolve_layout_transform(&PropertyBinding::Binding(unbound, scale)), scale);
+    }
+
+    #[test]
+    fn collect_filter_pipeline() {
+        let table = FilterData {
+            func_r_type: ComponentTransferFuncType::Identity,
+            r_values: Vec::new(),
+            func_g_type: ComponentTransferFuncType::Identity,
+            g_values: Vec::new(),
+            func_b_type: ComponentTransferFuncType::Identity,
+            b_values: Vec::new(),
+            func_a_type: ComponentTransferFuncType::Table,
+            a_values: vec![1.0, 0.0],
+        };
+        let filters = [
+            FilterOp::Opacity(PropertyBinding::Value(0.5), 0.5),
+            FilterOp::Opacity(PropertyBinding::Value(0.25), 0.25),
+            FilterOp::ComponentTransfer,
+        ];
+        let primitives = [blur_primitive(FilterPrimitiveInput::Original)];
+        with_stacking_context(&filters, &[table.clone()], &primitives, |sc, filters, datas, prims| {
+            let pipeline = sc.collect_filter_pipeline(filters, datas, prims).unwrap();
+            assert!(!pipeline.is_empty());
+            assert_eq!(pipeline.filters, sc.filter_ops_for_compositing(filters));
+            assert_eq!(pipeline.filters.len(), 3);
+            assert_eq!(pipeline.filter_datas, vec![table.clone()]);
+            assert_eq!(pipeline.filter_primitives, sc.filter_primitives_for_compositing(prims));
+        });
+        with_stacking_context(&[], &[], &[], |sc, filters, datas, prims| {
+            assert!(sc.collect_filter_pipeline(filters, datas, prims).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn flush_updates_resolved_maps_in_place() {
+        let t1 = PropertyBindingKey::new(1);
+        let t2 = PropertyBindingKey::new(2);
+        let f1 = PropertyBindingKey::new(3);
+        let f2 = PropertyBindingKey::new(4);
+        let f3 = PropertyBindingKey::new(5);
+        let c1 = PropertyBindingKey::new(6);
+        let translation = |x| LayoutTransform::create_translation(x, 0.0, 0.0);
+        let list = |transforms: &[(PropertyBindingKey<LayoutTransform>, f32)],
+                    floats: &[(PropertyBindingKey<f32>, f32)]| DynamicProperties {
+            transforms: transforms
+                .iter()
+                .map(|&(key, x)| PropertyValue { key, value: translation(x) })
+                .collect(),
+            floats: floats.iter().map(|&(key, value)| PropertyValue { key, value }).collect(),
+            colors: vec![PropertyValue { key: c1, value: ColorF::WHITE }],
+            ..DynamicProperties::default()
+        };
+
+        // `rebuilt` always takes the clear-and-rebuild path, against which
+        // the maps updated in place are checked.
+        let mut properties = SceneProperties::new();
+        let mut rebuilt = SceneProperties::new();
+        let flush = |properties: &mut SceneProperties, rebuilt: &mut SceneProperties, list: DynamicProperties| {
+            properties.set_properties(list.clone());
+            rebuilt.set_properties(list);
+            rebuilt.resolved_in_place.set(false);
+            assert_eq!(properties.flush_pending_updates(), rebuilt.flush_pending_updates());
+            let (a, b) = (properties.resolved(), rebuilt.resolved());
+            assert_eq!(a.transforms, b.transforms);
+            assert_eq!(a.floats, b.floats);
+            assert_eq!(a.colors, b.colors);
+            assert_eq!(a.ints, b.ints);
+            assert_eq!(a.points, b.points);
+        };
+
+        flush(&mut properties, &mut rebuilt, list(&[(t1, 1.0), (t2, 2.0)], &[(f1, 0.5), (f2, 0.25)]));
+
+        // Changing values is done in place.
+        flush(&mut properties, &mut rebuilt, list(&[(t1, 1.0), (t2, 3.0)], &[(f1, 0.75), (f2, 0.25)]));
+        assert!(properties.resolved_in_place.get());
+
+        // So are appending bindings, listing a binding twice, removing
+        // bindings and reordering them.
+        flush(&mut properties, &mut rebuilt, list(&[(t1, 1.0), (t2, 3.0)], &[(f1, 0.75), (f2, 0.25), (f3, 1.0)]));
+        flush(&mut properties, &mut rebuilt, list(&[(t1, 1.0), (t2, 3.0), (t1, 4.0)], &[(f1, 0.75)]));
+        assert!(properties.resolved_in_place.get());
+        flush(&mut properties, &mut rebuilt, list(&[(t2, 3.0), (t1, 4.0)], &[(f1, 0.75)]));
+        flush(&mut properties, &mut rebuilt, list(&[(t2, 3.0), (t1, 5.0)], &[(f1, 0.5)]));
+
+        // Springs still override the values that are updated in place.
+        properties.set_spring_target(f1.id, 1.0, 100.0, 10.0);
+        rebuilt.set_spring_target(f1.id, 1.0, 100.0, 10.0);
+        properties.advance_springs(0.1);
+        rebuilt.advance_springs(0.1);
+        flush(&mut properties, &mut rebuilt, list(&[(t2, 3.0), (t1, 5.0)], &[(f1, 0.25)]));
+    }
+
+    #[test]
+    fn plugin_blend_mode_falls_back() {
+        let key = PropertyBindingKey::new(1);
+        let stacking_context = StackingContext {
+            mix_blend_mode: MixBlendMode::Multiply,
+            mix_blend_mode_binding: Some(key),
+            ..StackingContext::default()
+        };
+        let mut properties = SceneProperties::new();
+        properties.set_properties(DynamicProperties {
+            ints: vec![PropertyValue { key, value: PluginBlendMode(3).index() }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+
+        // Plugin modes aren't implemented, so the static mode is used.
+        assert_eq!(
+            stacking_context.resolve_mix_blend_mode_for_compositing(&properties),
+            Some(MixBlendMode::Multiply),
+        );
+
+        // Plugin indices follow the standard ones.
+        assert_eq!(PluginBlendMode::from_index(MixBlendMode::Luminosity as i32), None);
+        assert_eq!(MixBlendMode::from_index(PluginBlendMode(0).index()), None);
+        for &mode in &[PluginBlendMode(0), PluginBlendMode(255)] {
+            assert_eq!(PluginBlendMode::from_index(mode.index()), Some(mode));
+        }
+        assert_eq!(PluginBlendMode::from_index(PluginBlendMode(255).index() + 1), None);
+    }
+
+    #[test]
+    fn pipeline_bounds_follow_stacking_context_transform() {
+        let root = PipelineId(0, 1);
+        let a = PipelineId(0, 2);
+        let scale = PropertyBindingKey::new(1);
+        let size = LayoutSize::new(10.0, 10.0);
+
+        // The root embeds `a` at the origin of a stacking context at
+        // (100, 0) that is scaled about its own origin.
+        let mut builder = DisplayListBuilder::new(root, LayoutSize::new(200.0, 200.0));
+        let space_and_clip = SpaceAndClipInfo::root_scroll(root);
+        builder.push_transformed_stacking_context(
+            LayoutPoint::new(100.0, 0.0),
+            space_and_clip.spatial_id,
+            true,
+            StackingContextTransform {
+                transform: PropertyBinding::Binding(scale, LayoutTransform::identity()),
+                origin: PropertyBinding::Value(LayoutPoint::new(100.0, 0.0)),
+            },
+        );
+        let bounds = LayoutRect::new(LayoutPoint::zero(), size);
+        builder.push_iframe(bounds, bounds, &space_and_clip, a, false);
+        builder.pop_stacking_context();
+        let (_, _, display_list) = builder.finalize();
+
+        let scene = SceneTestBuilder::new()
+            .root(root)
+            .pipeline(root).display_list(display_list).add()
+            .pipeline(a).add()
+            .build()
+            .unwrap();
+
+        let mut properties = SceneProperties::new();
+        assert_eq!(scene.pipeline_bounds(&properties)[&a], LayoutRect::new(LayoutPoint::new(100.0, 0.0), size));
+
+        properties.set_properties(DynamicProperties {
+            transforms: vec![PropertyValue { key: scale, value: LayoutTransform::create_scale(2.0, 2.0, 1.0) }],
+            ..DynamicProperties::default()
+        });
+        properties.flush_pending_updates();
+        assert_eq!(
+            scene.pipeline_bounds(&properties)[&a],
+            LayoutRect::new(LayoutPoint::new(100.0, 0.0), LayoutSize::new(20.0, 20.0)),
+        );
+    }
+
+    #[test]
+    fn pipeline_bounds_with_unbalanced_pops() {
+        let root = PipelineId(0, 1);
+        let a = PipelineId(0, 2);
+        let size = LayoutSize::new(10.0, 10.0);
+
+        let mut builder = DisplayListBuilder::new(root, LayoutSize::new(200.0, 200.0));
+        let space_and_clip = SpaceAndClipInfo::root_scroll(root);
+        builder.pop_stacking_context();
+        builder.pop_stacking_context();
+        builder.pop_reference_frame();
+        builder.pop_reference_frame();
+        let bounds = LayoutRect::new(LayoutPoint::new(5.0, 5.0), size);
+        builder.push_iframe(bounds, bounds, &space_and_clip, a, false);
+        let (_, _, display_list) = builder.finalize();
+
+        let scene = SceneTestBuilder::new()
+            .root(root)
+            .pipeline(root).display_list(display_list).add()
+            .pipeline(a).add()
+            .build()
+            .unwrap();
+
+        assert_eq!(scene.pipeline_bounds(&SceneProperties::new())[&a], bounds);
     }
 }
@@ -3,7 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use api::{AsyncBlobImageRasterizer, BlobImageRequest, BlobImageParams, BlobImageResult};
-use api::{DocumentId, PipelineId, ApiMsg, FrameMsg, ResourceUpdate, ExternalEvent, Epoch};
+use api::{DocumentId, PipelineId, PropertyBindingId, ApiMsg, FrameMsg, ResourceUpdate, ExternalEvent, Epoch};
 use api::{BuiltDisplayList, ColorF, NotificationRequest, Checkpoint, IdNamespace};
 use api::{ClipIntern, FilterDataIntern, MemoryReport, PrimitiveKeyKind};
 use api::channel::MsgSender;
@@ -27,7 +27,7 @@ use crate::prim_store::text_run::TextRun;
 use crate::resource_cache::{AsyncBlobImageInfo, FontInstanceMap};
 use crate::render_backend::DocumentView;
 use crate::renderer::{PipelineInfo, SceneBuilderHooks};
-use crate::scene::Scene;
+use crate::scene::{EpochPolicy, Scene, SceneProperties, ScenePropertiesSnapshot};
 use std::iter;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::mem::replace;
@@ -115,6 +115,9 @@ pub struct SceneRequest {
     pub view: DocumentView,
     pub font_instances: FontInstanceMap,
     pub output_pipelines: FastHashSet<PipelineId>,
+    /// The document's properties, for the bindings that are resolved when
+    /// the scene is built rather than each frame.
+    pub properties: ScenePropertiesSnapshot,
 }
 
 #[cfg(feature = "replay")]
@@ -133,6 +136,10 @@ pub struct BuiltScene {
     pub scene: Scene,
     pub frame_builder: FrameBuilder,
     pub clip_scroll_tree: ClipScrollTree,
+    /// The property bindings referenced by the scene's display lists,
+    /// collected here rather than on the render backend thread so that
+    /// the display lists that didn't change aren't walked again.
+    pub referenced_bindings: FastHashSet<PropertyBindingId>,
 }
 
 // Message from render backend to scene builder.
@@ -256,7 +263,10 @@ struct Document {
 }
 
 impl Document {
-    fn new(scene: Scene) -> Self {
+    fn new(mut scene: Scene) -> Self {
+        // Transactions can be built out of order, so a display list from
+        // an older transaction must not replace a newer one.
+        scene.set_epoch_policy(EpochPolicy::RejectStale);
         Document {
             scene,
             interners: Interners::default(),
@@ -400,8 +410,11 @@ impl SceneBuilder {
                 let mut clip_scroll_tree = ClipScrollTree::new();
                 let mut new_scene = Scene::new();
 
+                // Captures don't record the properties, so the bindings
+                // resolved at scene build time get their defaults.
                 let frame_builder = DisplayListFlattener::create_frame_builder(
                     &item.scene,
+                    &SceneProperties::new(),
                     &mut clip_scroll_tree,
                     item.font_instances,
                     &item.view,
@@ -420,15 +433,15 @@ impl SceneBuilder {
                     scene: new_scene,
                     frame_builder,
                     clip_scroll_tree,
+                    referenced_bindings: item.scene.referenced_bindings(),
                 });
             }
 
             self.documents.insert(
                 item.document_id,
                 Document {
-                    scene: item.scene,
                     interners: item.interners,
-                    doc_stats: DocumentStats::empty(),
+                    ..Document::new(item.scene)
                 },
             );
 
@@ -494,9 +507,12 @@ impl SceneBuilder {
             if let Some(request) = txn.request_scene_build.take() {
                 let mut clip_scroll_tree = ClipScrollTree::new();
                 let mut new_scene = Scene::new();
+                let mut properties = SceneProperties::new();
+                properties.restore(request.properties);
 
                 let frame_builder = DisplayListFlattener::create_frame_builder(
                     &scene,
+                    &properties,
                     &mut clip_scroll_tree,
                     request.font_instances,
                     &request.view,
@@ -519,6 +535,7 @@ impl SceneBuilder {
                     scene: new_scene,
                     frame_builder,
                     clip_scroll_tree,
+                    referenced_bindings: scene.referenced_bindings(),
                 });
             }
         }
@@ -563,7 +580,7 @@ impl SceneBuilder {
                             .filter(|txn| txn.built_scene.is_some())
                             .map(|txn| {
                                 txn.built_scene.as_ref().unwrap()
-                                    .scene.pipeline_epochs.iter()
+                                    .scene.pipeline_epochs().iter()
                                     .zip(iter::repeat(txn.document_id))
                                     .map(|((&pipeline_id, &epoch), document_id)| ((pipeline_id, document_id), epoch))
                             }).flatten().collect(),
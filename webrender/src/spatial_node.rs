@@ -162,6 +162,7 @@ impl SpatialNode {
             source_transform,
             kind,
             origin_in_parent_reference_frame,
+            transform_origin: None,
             invertible: true,
         };
         Self::new(pipeline_id, parent_index, SpatialNodeType::ReferenceFrame(info))
@@ -292,9 +293,20 @@ impl SpatialNode {
 
                 if info.invertible {
                     // Resolve the transform against any property bindings.
-                    let source_transform = LayoutFastTransform::from(
-                        scene_properties.resolve_layout_transform(&info.source_transform)
-                    );
+                    let source_transform = LayoutFastTransform::from(match info.transform_origin {
+                        Some(ref origin) => {
+                            scene_properties.resolve_transform_about_origin(&info.source_transform, origin)
+                        }
+                        None => scene_properties.resolve_layout_transform(&info.source_transform),
+                    });
+
+                    // The global transform is applied once, to the root.
+                    let source_transform = match self.parent {
+                        Some(..) => source_transform,
+                        None => source_transform.post_mul(
+                            &LayoutFastTransform::from(scene_properties.global_transform())
+                        ),
+                    };
 
                     // Do a change-basis operation on the perspective matrix using
                     // the scroll offset.
@@ -756,6 +768,11 @@ pub struct ReferenceFrameInfo {
     /// we also store it here to properly transform the viewport for sticky positioning.
     pub origin_in_parent_reference_frame: LayoutVector2D,
 
+    /// If set, the source transform is applied about this point rather
+    /// than the origin of the reference frame. This is used for the
+    /// implicit reference frames of transformed stacking contexts.
+    pub transform_origin: Option<PropertyBinding<LayoutPoint>>,
+
     /// True if the resolved transform is invertible.
     pub invertible: bool,
 }
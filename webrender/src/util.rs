@@ -4,7 +4,7 @@
 
 use api::BorderRadius;
 use api::units::*;
-use euclid::{TypedPoint2D, TypedRect, TypedSize2D, Vector2D};
+use euclid::{Rotation3D, TypedPoint2D, TypedRect, TypedSize2D, Vector2D, Vector3D};
 use euclid::{TypedTransform2D, TypedTransform3D, TypedVector2D, TypedScale};
 use malloc_size_of::{MallocShallowSizeOf, MallocSizeOf, MallocSizeOfOps};
 use plane_split::{Clipper, Polygon};
@@ -254,6 +254,100 @@ impl ScaleOffset {
     }
 }
 
+// Tolerance used when decomposing transforms. Rows of the upper 3x3
+// matrix must be orthogonal to within this amount (after normalization),
+// the perspective components must be within this amount of the identity,
+// and each axis scale must be larger than this.
+const DECOMPOSE_EPSILON: f32 = 1.0e-4;
+
+/// A transform split into separate translation, rotation and scale
+/// components. The original matrix is equivalent to applying the
+/// scale first, followed by the rotation and then the translation.
+#[derive(Debug, Clone, Copy)]
+pub struct DecomposedTransform {
+    pub translation: LayoutVector3D,
+    pub rotation: Rotation3D<f32>,
+    pub scale: LayoutVector3D,
+}
+
+impl DecomposedTransform {
+    /// Decompose a transform into translation, rotation and scale. Returns
+    /// None if the matrix has a perspective component, shear, or a (near)
+    /// zero scale on any axis, since those can't be represented. Matrices
+    /// that are within DECOMPOSE_EPSILON of satisfying these conditions are
+    /// decomposed, which avoids numerical instability near degenerate input.
+    pub fn from_transform(m: &LayoutTransform) -> Option<Self> {
+        if m.m14.abs() > DECOMPOSE_EPSILON ||
+           m.m24.abs() > DECOMPOSE_EPSILON ||
+           m.m34.abs() > DECOMPOSE_EPSILON ||
+           (m.m44 - 1.0).abs() > DECOMPOSE_EPSILON {
+            return None;
+        }
+
+        let mut rows = [
+            Vector3D::new(m.m11, m.m12, m.m13),
+            Vector3D::new(m.m21, m.m22, m.m23),
+            Vector3D::new(m.m31, m.m32, m.m33),
+        ];
+
+        let mut scale = [rows[0].length(), rows[1].length(), rows[2].length()];
+        if scale.iter().any(|s| *s < DECOMPOSE_EPSILON) {
+            return None;
+        }
+        for (row, s) in rows.iter_mut().zip(scale.iter()) {
+            *row = *row / *s;
+        }
+
+        if rows[0].dot(rows[1]).abs() > DECOMPOSE_EPSILON ||
+           rows[0].dot(rows[2]).abs() > DECOMPOSE_EPSILON ||
+           rows[1].dot(rows[2]).abs() > DECOMPOSE_EPSILON {
+            return None;
+        }
+
+        // A negative determinant means the matrix contains a reflection,
+        // which is folded into the x scale so the remaining part is a
+        // proper rotation.
+        if rows[0].dot(rows[1].cross(rows[2])) < 0.0 {
+            scale[0] = -scale[0];
+            rows[0] = -rows[0];
+        }
+
+        let (m11, m12, m13) = (rows[0].x, rows[0].y, rows[0].z);
+        let (m21, m22, m23) = (rows[1].x, rows[1].y, rows[1].z);
+        let (m31, m32, m33) = (rows[2].x, rows[2].y, rows[2].z);
+
+        // Convert the rotation matrix to a quaternion, picking the largest
+        // component as the divisor to keep the conversion stable.
+        let trace = m11 + m22 + m33;
+        let rotation = if trace > 0.0 {
+            let s = 2.0 * (1.0 + trace).sqrt();
+            Rotation3D::unit_quaternion((m23 - m32) / s, (m31 - m13) / s, (m12 - m21) / s, 0.25 * s)
+        } else if m11 > m22 && m11 > m33 {
+            let s = 2.0 * (1.0 + m11 - m22 - m33).sqrt();
+            Rotation3D::unit_quaternion(0.25 * s, (m12 + m21) / s, (m31 + m13) / s, (m23 - m32) / s)
+        } else if m22 > m33 {
+            let s = 2.0 * (1.0 + m22 - m11 - m33).sqrt();
+            Rotation3D::unit_quaternion((m12 + m21) / s, 0.25 * s, (m23 + m32) / s, (m31 - m13) / s)
+        } else {
+            let s = 2.0 * (1.0 + m33 - m11 - m22).sqrt();
+            Rotation3D::unit_quaternion((m31 + m13) / s, (m23 + m32) / s, 0.25 * s, (m12 - m21) / s)
+        };
+
+        Some(DecomposedTransform {
+            translation: LayoutVector3D::new(m.m41, m.m42, m.m43),
+            rotation,
+            scale: LayoutVector3D::new(scale[0], scale[1], scale[2]),
+        })
+    }
+
+    /// Recompose the components into a single matrix.
+    pub fn to_transform(&self) -> LayoutTransform {
+        LayoutTransform::create_scale(self.scale.x, self.scale.y, self.scale.z)
+            .post_mul(&LayoutTransform::from_untyped(&self.rotation.to_transform()))
+            .post_translate(self.translation)
+    }
+}
+
 // TODO: Implement these in euclid!
 pub trait MatrixHelpers<Src, Dst> {
     /// A port of the preserves2dAxisAlignment function in Skia.
@@ -484,7 +578,7 @@ pub fn extract_inner_rect_safe<U>(
 pub mod test {
     use super::*;
     use euclid::{Point2D, Angle, Transform3D};
-    use std::f32::consts::PI;
+    use std::f32::consts::{FRAC_1_SQRT_2, PI};
 
     #[test]
     fn inverse_project() {
@@ -567,6 +661,50 @@ pub mod test {
         validate_accumulate(&x0, &x1);
     }
 
+    fn validate_decompose(xref: &LayoutTransform) {
+        let decomposed = DecomposedTransform::from_transform(xref).unwrap();
+        let x = decomposed.to_transform();
+        assert!(xref.approx_eq(&x), "{:?}\n{:?}", xref, x);
+    }
+
+    #[test]
+    fn decompose_transform() {
+        validate_decompose(&LayoutTransform::identity());
+
+        let xref = LayoutTransform::create_translation(130.0, 200.0, 7.0);
+        validate_decompose(&xref);
+
+        let xref = LayoutTransform::create_scale(3.0, 0.5, 1.0)
+            .post_rotate(0.0, 0.0, 1.0, Angle::radians(PI / 3.0))
+            .post_translate(LayoutVector3D::new(10.0, 20.0, 0.0));
+        validate_decompose(&xref);
+
+        let xref = LayoutTransform::create_rotation(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0, Angle::radians(PI * 0.9));
+        validate_decompose(&xref);
+
+        let xref = LayoutTransform::create_scale(-2.0, 1.0, 1.0);
+        validate_decompose(&xref);
+
+        let decomposed = DecomposedTransform::from_transform(
+            &LayoutTransform::create_scale(2.0, 4.0, 1.0)
+                .post_translate(LayoutVector3D::new(5.0, 6.0, 0.0))
+        ).unwrap();
+        assert_eq!(decomposed.scale, LayoutVector3D::new(2.0, 4.0, 1.0));
+        assert_eq!(decomposed.translation, LayoutVector3D::new(5.0, 6.0, 0.0));
+    }
+
+    #[test]
+    fn decompose_transform_failure() {
+        let skew = LayoutTransform::create_skew(Angle::radians(0.5), Angle::radians(0.0));
+        assert!(DecomposedTransform::from_transform(&skew).is_none());
+
+        let perspective = LayoutTransform::create_perspective(100.0);
+        assert!(DecomposedTransform::from_transform(&perspective).is_none());
+
+        let degenerate = LayoutTransform::create_scale(1.0, 0.0, 1.0);
+        assert!(DecomposedTransform::from_transform(&degenerate).is_none());
+    }
+
     #[test]
     fn inverse_project_2d_origin() {
         let mut m = Transform3D::identity();
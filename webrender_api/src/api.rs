@@ -288,6 +288,30 @@ impl Transaction {
         self.frame_ops.push(FrameMsg::AppendDynamicProperties(properties));
     }
 
+    /// Animate a float property towards `target` with a damped spring. The
+    /// render backend advances the spring each time it generates a frame,
+    /// and the spring's value overrides the value supplied for the binding
+    /// in the dynamic properties until `clear_spring` is called.
+    pub fn set_spring_target(
+        &mut self,
+        key: PropertyBindingKey<f32>,
+        target: f32,
+        stiffness: f32,
+        damping: f32,
+    ) {
+        self.frame_ops.push(FrameMsg::SetSpringTarget {
+            id: key.id,
+            target,
+            stiffness,
+            damping,
+        });
+    }
+
+    /// Stop animating a float property with a spring.
+    pub fn clear_spring(&mut self, key: PropertyBindingKey<f32>) {
+        self.frame_ops.push(FrameMsg::ClearSpring(key.id));
+    }
+
     /// Consumes this object and just returns the frame ops.
     pub fn get_frame_ops(self) -> Vec<FrameMsg> {
         self.frame_ops
@@ -629,6 +653,13 @@ pub enum FrameMsg {
     AppendDynamicProperties(DynamicProperties),
     SetPinchZoom(ZoomFactor),
     SetIsTransformPinchZooming(bool, PropertyBindingId),
+    SetSpringTarget {
+        id: PropertyBindingId,
+        target: f32,
+        stiffness: f32,
+        damping: f32,
+    },
+    ClearSpring(PropertyBindingId),
 }
 
 impl fmt::Debug for SceneMsg {
@@ -658,6 +689,8 @@ impl fmt::Debug for FrameMsg {
             FrameMsg::AppendDynamicProperties(..) => "FrameMsg::AppendDynamicProperties",
             FrameMsg::SetPinchZoom(..) => "FrameMsg::SetPinchZoom",
             FrameMsg::SetIsTransformPinchZooming(..) => "FrameMsg::SetIsTransformPinchZooming",
+            FrameMsg::SetSpringTarget { .. } => "FrameMsg::SetSpringTarget",
+            FrameMsg::ClearSpring(..) => "FrameMsg::ClearSpring",
         })
     }
 }
@@ -933,6 +966,7 @@ pub struct MemoryReport {
     pub gpu_cache_cpu_mirror: usize,
     pub render_tasks: usize,
     pub hit_testers: usize,
+    pub display_lists: usize,
     pub fonts: usize,
     pub images: usize,
     pub rasterized_blobs: usize,
@@ -1510,6 +1544,16 @@ pub struct PropertyValue<T> {
 pub struct DynamicProperties {
     pub transforms: Vec<PropertyValue<LayoutTransform>>,
     pub floats: Vec<PropertyValue<f32>>,
+    #[serde(default)]
+    pub colors: Vec<PropertyValue<ColorF>>,
+    #[serde(default)]
+    pub ints: Vec<PropertyValue<i32>>,
+    #[serde(default)]
+    pub points: Vec<PropertyValue<LayoutPoint>>,
+    /// Bindings to remove when this list is merged into the current one.
+    /// See `SceneProperties::update_properties`.
+    #[serde(default)]
+    pub removed: Vec<PropertyBindingId>,
 }
 
 pub trait RenderNotifier: Send {
@@ -7,7 +7,7 @@ use peek_poke::PeekPoke;
 use std::ops::Not;
 // local imports
 use crate::font;
-use crate::api::{PipelineId, PropertyBinding};
+use crate::api::{PipelineId, PropertyBinding, PropertyBindingKey};
 use crate::color::ColorF;
 use crate::image::{ColorDepth, ImageKey};
 use crate::units::*;
@@ -583,6 +583,14 @@ pub struct GradientStop {
     pub color: ColorF,
 }
 
+/// A gradient stop whose offset and color may be animated. Resolve these
+/// with `SceneProperties::resolve_gradient_stops`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GradientStopBinding {
+    pub offset: PropertyBinding<f32>,
+    pub color: PropertyBinding<ColorF>,
+}
+
 #[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
 pub struct RadialGradient {
     pub center: LayoutPoint,
@@ -652,8 +660,26 @@ pub struct StackingContext {
     pub raster_space: RasterSpace,
     /// True if picture caching should be used on this stacking context.
     pub cache_tiles: bool,
+    /// If set, an int binding holding the index of the `MixBlendMode` to
+    /// use, which overrides `mix_blend_mode` when it resolves to a valid
+    /// index. It may also hold a `PluginBlendMode::index`, which isn't
+    /// supported and leaves `mix_blend_mode` in effect. The binding is
+    /// resolved when the scene is built, so a new value takes effect at
+    /// the next scene build.
+    pub mix_blend_mode_binding: Option<PropertyBindingKey<i32>>,
+    /// If set, a transform applied to the contents of the stacking
+    /// context about a bindable origin.
+    pub transform: Option<StackingContextTransform>,
 } // IMPLICIT: filters: Vec<FilterOp>, filter_datas: Vec<FilterData>, filter_primitives: Vec<FilterPrimitive>
 
+/// A transform applied to a stacking context about a transform origin,
+/// in the same space as the stacking context's origin.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct StackingContextTransform {
+    pub transform: PropertyBinding<LayoutTransform>,
+    pub origin: PropertyBinding<LayoutPoint>,
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize, PeekPoke)]
 pub enum TransformStyle {
@@ -711,6 +737,61 @@ pub enum MixBlendMode {
     Luminosity = 15,
 }
 
+impl MixBlendMode {
+    /// Get the blend mode with the given discriminant, if there is one.
+    pub fn from_index(index: i32) -> Option<MixBlendMode> {
+        Some(match index {
+            0 => MixBlendMode::Normal,
+            1 => MixBlendMode::Multiply,
+            2 => MixBlendMode::Screen,
+            3 => MixBlendMode::Overlay,
+            4 => MixBlendMode::Darken,
+            5 => MixBlendMode::Lighten,
+            6 => MixBlendMode::ColorDodge,
+            7 => MixBlendMode::ColorBurn,
+            8 => MixBlendMode::HardLight,
+            9 => MixBlendMode::SoftLight,
+            10 => MixBlendMode::Difference,
+            11 => MixBlendMode::Exclusion,
+            12 => MixBlendMode::Hue,
+            13 => MixBlendMode::Saturation,
+            14 => MixBlendMode::Color,
+            15 => MixBlendMode::Luminosity,
+            _ => return None,
+        })
+    }
+}
+
+/// A blend mode implemented outside of WebRender. These don't have
+/// `MixBlendMode` variants: they can only be selected through a
+/// `StackingContext::mix_blend_mode_binding`, which holds a
+/// `PluginBlendMode::index`. WebRender's shaders only implement the
+/// standard blend modes, so a binding that resolves to a plugin mode falls
+/// back to the stacking context's `mix_blend_mode`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize)]
+pub struct PluginBlendMode(pub u8);
+
+impl PluginBlendMode {
+    /// The index of `PluginBlendMode(0)`, after those of the standard
+    /// blend modes.
+    pub const FIRST_INDEX: i32 = MixBlendMode::Luminosity as i32 + 1;
+
+    /// Get the index a blend mode binding holds to select this mode.
+    pub fn index(self) -> i32 {
+        PluginBlendMode::FIRST_INDEX + self.0 as i32
+    }
+
+    /// Get the plugin blend mode with the given index, if there is one.
+    pub fn from_index(index: i32) -> Option<PluginBlendMode> {
+        let id = index - PluginBlendMode::FIRST_INDEX;
+        if id >= 0 && id <= u8::max_value() as i32 {
+            Some(PluginBlendMode(id as u8))
+        } else {
+            None
+        }
+    }
+}
+
 /// An input to a SVG filter primitive.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize, PeekPoke)]
@@ -754,9 +835,20 @@ pub struct BlendPrimitive {
 #[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
 pub struct FloodPrimitive {
     pub color: ColorF,
+    /// If set, a color binding that overrides `color` once resolved.
+    pub color_binding: Option<PropertyBindingKey<ColorF>>,
 }
 
 impl FloodPrimitive {
+    /// Get the flood color as a property binding, with `color` as the
+    /// value to use until the binding resolves.
+    pub fn color_property(&self) -> PropertyBinding<ColorF> {
+        match self.color_binding {
+            Some(key) => PropertyBinding::Binding(key, self.color),
+            None => PropertyBinding::Value(self.color),
+        }
+    }
+
     pub fn sanitize(&mut self) {
         self.color.r = self.color.r.min(1.0).max(0.0);
         self.color.g = self.color.g.min(1.0).max(0.0);
@@ -769,12 +861,45 @@ impl FloodPrimitive {
 #[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
 pub struct BlurPrimitive {
     pub input: FilterPrimitiveInput,
+    /// The standard deviation along x, and along y too unless `radius_y`
+    /// is set.
     pub radius: f32,
+    /// If set, the standard deviation along y, for an anisotropic blur.
+    pub radius_y: Option<f32>,
+    /// If set, a float binding that overrides the deviation along x once
+    /// resolved.
+    pub radius_x_binding: Option<PropertyBindingKey<f32>>,
+    /// If set, a float binding that overrides the deviation along y once
+    /// resolved.
+    pub radius_y_binding: Option<PropertyBindingKey<f32>>,
 }
 
 impl BlurPrimitive {
+    /// Get the standard deviations along x and y.
+    pub fn std_deviation(&self) -> (f32, f32) {
+        (self.radius, self.radius_y.unwrap_or(self.radius))
+    }
+
+    /// Get the deviation along x as a property binding, with its current
+    /// value as the value to use until the binding resolves.
+    pub fn radius_x_property(&self) -> PropertyBinding<f32> {
+        match self.radius_x_binding {
+            Some(key) => PropertyBinding::Binding(key, self.std_deviation().0),
+            None => PropertyBinding::Value(self.std_deviation().0),
+        }
+    }
+
+    /// Get the deviation along y as a property binding, as for x.
+    pub fn radius_y_property(&self) -> PropertyBinding<f32> {
+        match self.radius_y_binding {
+            Some(key) => PropertyBinding::Binding(key, self.std_deviation().1),
+            None => PropertyBinding::Value(self.std_deviation().1),
+        }
+    }
+
     pub fn sanitize(&mut self) {
         self.radius = self.radius.min(MAX_BLUR_RADIUS);
+        self.radius_y = self.radius_y.map(|radius| radius.min(MAX_BLUR_RADIUS));
     }
 }
 
@@ -890,6 +1015,12 @@ pub enum FilterOp {
     /// debug purposes only.
     Identity,
     Blur(f32),
+    /// A blur whose radius is bound to an animated property, along with
+    /// the radius to use until the binding has a value. The radius is
+    /// resolved from the binding each frame. The binding also tells
+    /// WebRender that the blur is animating, so that it can draw it at a
+    /// lower quality while the radius is changing.
+    AnimatedBlur(PropertyBinding<f32>, f32),
     Brightness(f32),
     Contrast(f32),
     Grayscale(f32),
@@ -262,11 +262,212 @@ impl BuiltDisplayList {
         self.descriptor.total_spatial_nodes
     }
 
+    /// Get a copy of this display list with every pipeline id in it
+    /// replaced by `f(pipeline_id)`. This covers the pipeline ids embedded
+    /// in spatial, clip, clip chain and external scroll ids, as well as
+    /// the pipelines referenced by iframes.
+    pub fn map_pipeline_ids<F>(&self, f: F) -> BuiltDisplayList
+    where
+        F: Fn(PipelineId) -> PipelineId,
+    {
+        use crate::display_item::DisplayItem as Real;
+
+        let map_common = |common: &mut di::CommonItemProperties| {
+            common.spatial_id = map_spatial_id(common.spatial_id, &f);
+            common.clip_id = map_clip_id(common.clip_id, &f);
+        };
+
+        let mut data = Vec::with_capacity(self.data.len());
+        let mut temp = Vec::new();
+        let mut traversal = self.iter();
+        while let Some(item) = traversal.next_raw() {
+            let mut real = *item.item();
+            match real {
+                Real::Rectangle(ref mut v) => map_common(&mut v.common),
+                Real::ClearRectangle(ref mut v) => map_common(&mut v.common),
+                Real::HitTest(ref mut v) => map_common(&mut v.common),
+                Real::Line(ref mut v) => map_common(&mut v.common),
+                Real::Border(ref mut v) => map_common(&mut v.common),
+                Real::BoxShadow(ref mut v) => map_common(&mut v.common),
+                Real::Gradient(ref mut v) => map_common(&mut v.common),
+                Real::RadialGradient(ref mut v) => map_common(&mut v.common),
+                Real::Image(ref mut v) => map_common(&mut v.common),
+                Real::YuvImage(ref mut v) => map_common(&mut v.common),
+                Real::Text(ref mut v) => {
+                    map_common(&mut v.common);
+                    DisplayListBuilder::push_iter_impl(&mut temp, item.iter.cur_glyphs.iter());
+                }
+                Real::PushShadow(ref mut v) => {
+                    v.space_and_clip = map_space_and_clip(v.space_and_clip, &f);
+                }
+                Real::Clip(ref mut v) => {
+                    v.id = map_clip_id(v.id, &f);
+                    v.parent_space_and_clip = map_space_and_clip(v.parent_space_and_clip, &f);
+                    DisplayListBuilder::push_iter_impl(&mut temp, item.iter.cur_complex_clip.iter());
+                }
+                Real::ClipChain(ref mut v) => {
+                    v.id = map_clip_chain_id(v.id, &f);
+                    v.parent = v.parent.map(|parent| map_clip_chain_id(parent, &f));
+                    let clip_ids: Vec<di::ClipId> = item.iter.cur_clip_chain_items
+                        .iter()
+                        .map(|clip_id| map_clip_id(clip_id, &f))
+                        .collect();
+                    DisplayListBuilder::push_iter_impl(&mut temp, clip_ids);
+                }
+                Real::ScrollFrame(ref mut v) => {
+                    v.clip_id = map_clip_id(v.clip_id, &f);
+                    v.scroll_frame_id = map_spatial_id(v.scroll_frame_id, &f);
+                    v.parent_space_and_clip = map_space_and_clip(v.parent_space_and_clip, &f);
+                    v.external_id = v.external_id.map(|id| map_external_scroll_id(id, &f));
+                    DisplayListBuilder::push_iter_impl(&mut temp, item.iter.cur_complex_clip.iter());
+                }
+                Real::StickyFrame(ref mut v) => {
+                    v.id = map_spatial_id(v.id, &f);
+                    v.parent_spatial_id = map_spatial_id(v.parent_spatial_id, &f);
+                }
+                Real::Iframe(ref mut v) => {
+                    v.space_and_clip = map_space_and_clip(v.space_and_clip, &f);
+                    v.pipeline_id = f(v.pipeline_id);
+                }
+                Real::PushReferenceFrame(ref mut v) => {
+                    v.parent_spatial_id = map_spatial_id(v.parent_spatial_id, &f);
+                    v.reference_frame.id = map_spatial_id(v.reference_frame.id, &f);
+                    if let di::ReferenceFrameKind::Perspective { ref mut scrolling_relative_to } = v.reference_frame.kind {
+                        *scrolling_relative_to = scrolling_relative_to.map(|id| map_external_scroll_id(id, &f));
+                    }
+                }
+                Real::PushStackingContext(ref mut v) => {
+                    v.spatial_id = map_spatial_id(v.spatial_id, &f);
+                    v.stacking_context.clip_id = v.stacking_context.clip_id.map(|id| map_clip_id(id, &f));
+                }
+                Real::SetGradientStops => {
+                    DisplayListBuilder::push_iter_impl(&mut temp, item.iter.cur_stops.iter());
+                }
+                Real::SetFilterOps => {
+                    DisplayListBuilder::push_iter_impl(&mut temp, item.iter.cur_filters.iter());
+                }
+                Real::SetFilterData => {
+                    let filter_data = item.iter.cur_filter_data.last().unwrap();
+                    DisplayListBuilder::push_iter_impl(&mut temp, filter_data.func_types.iter());
+                    DisplayListBuilder::push_iter_impl(&mut temp, filter_data.r_values.iter());
+                    DisplayListBuilder::push_iter_impl(&mut temp, filter_data.g_values.iter());
+                    DisplayListBuilder::push_iter_impl(&mut temp, filter_data.b_values.iter());
+                    DisplayListBuilder::push_iter_impl(&mut temp, filter_data.a_values.iter());
+                }
+                Real::SetFilterPrimitives => {
+                    DisplayListBuilder::push_iter_impl(&mut temp, item.iter.cur_filter_primitives.iter());
+                }
+                Real::PopReferenceFrame |
+                Real::PopStackingContext |
+                Real::PopAllShadows => {}
+            }
+            poke_into_vec(&real, &mut data);
+            // the aux data is serialized after the item, hence the temporary
+            data.extend(temp.drain(..));
+        }
+        ensure_red_zone::<di::DisplayItem>(&mut data);
+
+        BuiltDisplayList {
+            data,
+            descriptor: self.descriptor,
+        }
+    }
+
     pub fn iter(&self) -> BuiltDisplayListIter {
         BuiltDisplayListIter::new(self)
     }
 }
 
+fn map_spatial_id<F: Fn(PipelineId) -> PipelineId>(id: di::SpatialId, f: &F) -> di::SpatialId {
+    di::SpatialId::new(id.0, f(id.pipeline_id()))
+}
+
+fn map_clip_chain_id<F: Fn(PipelineId) -> PipelineId>(id: di::ClipChainId, f: &F) -> di::ClipChainId {
+    di::ClipChainId(id.0, f(id.1))
+}
+
+fn map_clip_id<F: Fn(PipelineId) -> PipelineId>(id: di::ClipId, f: &F) -> di::ClipId {
+    match id {
+        di::ClipId::Clip(index, pipeline_id) => di::ClipId::Clip(index, f(pipeline_id)),
+        di::ClipId::ClipChain(clip_chain_id) => di::ClipId::ClipChain(map_clip_chain_id(clip_chain_id, f)),
+    }
+}
+
+fn map_space_and_clip<F: Fn(PipelineId) -> PipelineId>(
+    space_and_clip: di::SpaceAndClipInfo,
+    f: &F,
+) -> di::SpaceAndClipInfo {
+    di::SpaceAndClipInfo {
+        spatial_id: map_spatial_id(space_and_clip.spatial_id, f),
+        clip_id: map_clip_id(space_and_clip.clip_id, f),
+    }
+}
+
+fn map_external_scroll_id<F: Fn(PipelineId) -> PipelineId>(
+    id: di::ExternalScrollId,
+    f: &F,
+) -> di::ExternalScrollId {
+    di::ExternalScrollId(id.0, f(id.1))
+}
+
+/// The ids given by `DisplayListBuilder::push_display_list` to the nodes
+/// of the display list it is appending.
+struct IdRemapping {
+    roots: DisplayListRoots,
+    spatial_ids: HashMap<di::SpatialId, di::SpatialId>,
+    clip_ids: HashMap<di::ClipId, di::ClipId>,
+    clip_chain_ids: HashMap<di::ClipChainId, di::ClipChainId>,
+}
+
+impl IdRemapping {
+    fn new(roots: &DisplayListRoots) -> Self {
+        IdRemapping {
+            roots: *roots,
+            spatial_ids: HashMap::new(),
+            clip_ids: HashMap::new(),
+            clip_chain_ids: HashMap::new(),
+        }
+    }
+
+    /// Ids that aren't defined earlier in the display list, which are
+    /// invalid, fall back to the roots.
+    fn spatial_id(&self, id: di::SpatialId) -> di::SpatialId {
+        if id.is_root_reference_frame() {
+            self.roots.reference_frame
+        } else if id.is_root_scroll_node() {
+            self.roots.scroll_node
+        } else {
+            self.spatial_ids.get(&id).cloned().unwrap_or(self.roots.scroll_node)
+        }
+    }
+
+    fn clip_chain_id(&self, id: di::ClipChainId) -> Option<di::ClipChainId> {
+        self.clip_chain_ids.get(&id).cloned()
+    }
+
+    fn clip_id(&self, id: di::ClipId) -> di::ClipId {
+        if !id.is_valid() {
+            return id;
+        }
+        if id.is_root() {
+            return self.roots.clip;
+        }
+        match id {
+            di::ClipId::Clip(..) => self.clip_ids.get(&id).cloned(),
+            di::ClipId::ClipChain(clip_chain_id) => {
+                self.clip_chain_id(clip_chain_id).map(di::ClipId::ClipChain)
+            }
+        }.unwrap_or(self.roots.clip)
+    }
+
+    fn space_and_clip(&self, space_and_clip: di::SpaceAndClipInfo) -> di::SpaceAndClipInfo {
+        di::SpaceAndClipInfo {
+            spatial_id: self.spatial_id(space_and_clip.spatial_id),
+            clip_id: self.clip_id(space_and_clip.clip_id),
+        }
+    }
+}
+
 /// Returns the byte-range the slice occupied.
 fn skip_slice<'a, T: peek_poke::Peek>(data: &mut &'a [u8]) -> ItemRange<'a, T> {
     let mut skip_offset = 0usize;
@@ -485,31 +686,31 @@ impl<'a, 'b> DisplayItemRef<'a, 'b> {
         &self.iter.cur_item
     }
 
-    pub fn complex_clip(&self) -> ItemRange<di::ComplexClipRegion> {
+    pub fn complex_clip(&self) -> ItemRange<'a, di::ComplexClipRegion> {
         self.iter.cur_complex_clip
     }
 
-    pub fn gradient_stops(&self) -> ItemRange<di::GradientStop> {
+    pub fn gradient_stops(&self) -> ItemRange<'a, di::GradientStop> {
         self.iter.cur_stops
     }
 
-    pub fn glyphs(&self) -> ItemRange<GlyphInstance> {
+    pub fn glyphs(&self) -> ItemRange<'a, GlyphInstance> {
         self.iter.cur_glyphs
     }
 
-    pub fn filters(&self) -> ItemRange<di::FilterOp> {
+    pub fn filters(&self) -> ItemRange<'a, di::FilterOp> {
         self.iter.cur_filters
     }
 
-    pub fn filter_datas(&self) -> &Vec<TempFilterData> {
+    pub fn filter_datas(&self) -> &Vec<TempFilterData<'a>> {
         &self.iter.cur_filter_data
     }
 
-    pub fn filter_primitives(&self) -> ItemRange<di::FilterPrimitive> {
+    pub fn filter_primitives(&self) -> ItemRange<'a, di::FilterPrimitive> {
         self.iter.cur_filter_primitives
     }
 
-    pub fn clip_chain_items(&self) -> ItemRange<di::ClipId> {
+    pub fn clip_chain_items(&self) -> ItemRange<'a, di::ClipId> {
         self.iter.cur_clip_chain_items
     }
 
@@ -739,6 +940,10 @@ impl<'de> Deserialize<'de> for BuiltDisplayList {
             data.extend(temp.drain(..));
         }
 
+        // Add red zone, without which the iterator would stop short of the
+        // last items.
+        ensure_red_zone::<di::DisplayItem>(&mut data);
+
         Ok(BuiltDisplayList {
             data,
             descriptor: BuiltDisplayListDescriptor {
@@ -752,6 +957,30 @@ impl<'de> Deserialize<'de> for BuiltDisplayList {
     }
 }
 
+/// The nodes that replace the root nodes of a display list appended with
+/// `DisplayListBuilder::push_display_list`.
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayListRoots {
+    pub reference_frame: di::SpatialId,
+    pub scroll_node: di::SpatialId,
+    pub clip: di::ClipId,
+    /// The parent of the clip chains that don't have one, or None to
+    /// leave them without one.
+    pub clip_chain_parent: Option<di::ClipChainId>,
+}
+
+impl DisplayListRoots {
+    /// The root nodes of a pipeline's display lists.
+    pub fn of_pipeline(pipeline_id: PipelineId) -> Self {
+        DisplayListRoots {
+            reference_frame: di::SpatialId::root_reference_frame(pipeline_id),
+            scroll_node: di::SpatialId::root_scroll_node(pipeline_id),
+            clip: di::ClipId::root(pipeline_id),
+            clip_chain_parent: None,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SaveState {
     dl_len: usize,
@@ -1226,6 +1455,70 @@ impl DisplayListBuilder {
         filter_primitives: &[di::FilterPrimitive],
         raster_space: di::RasterSpace,
         cache_tiles: bool,
+    ) {
+        let stacking_context = di::StackingContext {
+            transform_style,
+            mix_blend_mode,
+            clip_id,
+            raster_space,
+            cache_tiles,
+            mix_blend_mode_binding: None,
+            transform: None,
+        };
+
+        self.push_stacking_context_item(
+            origin,
+            spatial_id,
+            is_backface_visible,
+            stacking_context,
+            filters,
+            filter_datas,
+            filter_primitives,
+        );
+    }
+
+    /// Push a stacking context whose contents are transformed about
+    /// `transform.origin`. Both the transform and its origin can be bound.
+    pub fn push_transformed_stacking_context(
+        &mut self,
+        origin: LayoutPoint,
+        spatial_id: di::SpatialId,
+        is_backface_visible: bool,
+        transform: di::StackingContextTransform,
+    ) {
+        let stacking_context = di::StackingContext {
+            transform_style: di::TransformStyle::Flat,
+            mix_blend_mode: di::MixBlendMode::Normal,
+            clip_id: None,
+            raster_space: di::RasterSpace::Screen,
+            cache_tiles: false,
+            mix_blend_mode_binding: None,
+            transform: Some(transform),
+        };
+
+        self.push_stacking_context_item(
+            origin,
+            spatial_id,
+            is_backface_visible,
+            stacking_context,
+            &[],
+            &[],
+            &[],
+        );
+    }
+
+    /// Push a stacking context described by `stacking_context`, for the
+    /// fields that the other methods don't take, such as
+    /// `mix_blend_mode_binding`.
+    pub fn push_stacking_context_item(
+        &mut self,
+        origin: LayoutPoint,
+        spatial_id: di::SpatialId,
+        is_backface_visible: bool,
+        stacking_context: di::StackingContext,
+        filters: &[di::FilterOp],
+        filter_datas: &[di::FilterData],
+        filter_primitives: &[di::FilterPrimitive],
     ) {
         if filters.len() > 0 {
             self.push_item(&di::DisplayItem::SetFilterOps);
@@ -1253,13 +1546,7 @@ impl DisplayListBuilder {
             origin,
             spatial_id,
             is_backface_visible,
-            stacking_context: di::StackingContext {
-                transform_style,
-                mix_blend_mode,
-                clip_id,
-                raster_space,
-                cache_tiles,
-            },
+            stacking_context,
         });
 
         self.push_item(&item);
@@ -1446,6 +1733,143 @@ impl DisplayListBuilder {
         self.push_item(&item);
     }
 
+    /// Append the items of a display list, which may have been built for
+    /// another pipeline, as if they had been pushed to this builder. Each
+    /// spatial node, clip and clip chain it defines gets a new id from this
+    /// builder, and its root nodes are replaced by `roots`. Iframes are not
+    /// appended: `push_iframe` is called for each one instead, with its ids
+    /// already replaced, and can push the iframe or something in its place.
+    pub fn push_display_list<F>(
+        &mut self,
+        display_list: &BuiltDisplayList,
+        roots: &DisplayListRoots,
+        mut push_iframe: F,
+    )
+    where
+        F: FnMut(&mut DisplayListBuilder, &di::IframeDisplayItem),
+    {
+        use crate::display_item::DisplayItem as Real;
+
+        let mut ids = IdRemapping::new(roots);
+        let map_common = |ids: &IdRemapping, common: &mut di::CommonItemProperties| {
+            common.spatial_id = ids.spatial_id(common.spatial_id);
+            common.clip_id = ids.clip_id(common.clip_id);
+        };
+
+        let mut traversal = display_list.iter();
+        while let Some(item) = traversal.next_raw() {
+            let mut real = *item.item();
+            match real {
+                Real::Rectangle(ref mut v) => map_common(&ids, &mut v.common),
+                Real::ClearRectangle(ref mut v) => map_common(&ids, &mut v.common),
+                Real::HitTest(ref mut v) => map_common(&ids, &mut v.common),
+                Real::Line(ref mut v) => map_common(&ids, &mut v.common),
+                Real::Border(ref mut v) => map_common(&ids, &mut v.common),
+                Real::BoxShadow(ref mut v) => map_common(&ids, &mut v.common),
+                Real::Gradient(ref mut v) => map_common(&ids, &mut v.common),
+                Real::RadialGradient(ref mut v) => map_common(&ids, &mut v.common),
+                Real::Image(ref mut v) => map_common(&ids, &mut v.common),
+                Real::YuvImage(ref mut v) => map_common(&ids, &mut v.common),
+                Real::Text(ref mut v) => {
+                    map_common(&ids, &mut v.common);
+                    self.push_item(&real);
+                    self.push_iter(item.iter.cur_glyphs.iter());
+                    continue;
+                }
+                Real::PushShadow(ref mut v) => {
+                    v.space_and_clip = ids.space_and_clip(v.space_and_clip);
+                }
+                Real::Clip(ref mut v) => {
+                    v.parent_space_and_clip = ids.space_and_clip(v.parent_space_and_clip);
+                    let id = self.generate_clip_index();
+                    ids.clip_ids.insert(v.id, id);
+                    v.id = id;
+                    self.push_item(&real);
+                    self.push_iter(item.iter.cur_complex_clip.iter());
+                    continue;
+                }
+                Real::ClipChain(ref mut v) => {
+                    v.parent = match v.parent {
+                        Some(parent) => ids.clip_chain_id(parent),
+                        None => roots.clip_chain_parent,
+                    };
+                    let id = self.generate_clip_chain_id();
+                    ids.clip_chain_ids.insert(v.id, id);
+                    v.id = id;
+                    let clip_ids: Vec<di::ClipId> = item.iter.cur_clip_chain_items
+                        .iter()
+                        .map(|clip_id| ids.clip_id(clip_id))
+                        .collect();
+                    self.push_item(&real);
+                    self.push_iter(clip_ids);
+                    continue;
+                }
+                Real::ScrollFrame(ref mut v) => {
+                    v.parent_space_and_clip = ids.space_and_clip(v.parent_space_and_clip);
+                    let clip_id = self.generate_clip_index();
+                    let scroll_frame_id = self.generate_spatial_index();
+                    ids.clip_ids.insert(v.clip_id, clip_id);
+                    ids.spatial_ids.insert(v.scroll_frame_id, scroll_frame_id);
+                    v.clip_id = clip_id;
+                    v.scroll_frame_id = scroll_frame_id;
+                    self.push_item(&real);
+                    self.push_iter(item.iter.cur_complex_clip.iter());
+                    continue;
+                }
+                Real::StickyFrame(ref mut v) => {
+                    v.parent_spatial_id = ids.spatial_id(v.parent_spatial_id);
+                    let id = self.generate_spatial_index();
+                    ids.spatial_ids.insert(v.id, id);
+                    v.id = id;
+                }
+                Real::Iframe(ref mut v) => {
+                    v.space_and_clip = ids.space_and_clip(v.space_and_clip);
+                    push_iframe(self, v);
+                    continue;
+                }
+                Real::PushReferenceFrame(ref mut v) => {
+                    v.parent_spatial_id = ids.spatial_id(v.parent_spatial_id);
+                    let id = self.generate_spatial_index();
+                    ids.spatial_ids.insert(v.reference_frame.id, id);
+                    v.reference_frame.id = id;
+                }
+                Real::PushStackingContext(ref mut v) => {
+                    v.spatial_id = ids.spatial_id(v.spatial_id);
+                    v.stacking_context.clip_id = v.stacking_context.clip_id.map(|id| ids.clip_id(id));
+                }
+                Real::SetGradientStops => {
+                    self.push_item(&real);
+                    self.push_iter(item.iter.cur_stops.iter());
+                    continue;
+                }
+                Real::SetFilterOps => {
+                    self.push_item(&real);
+                    self.push_iter(item.iter.cur_filters.iter());
+                    continue;
+                }
+                Real::SetFilterData => {
+                    let filter_data = item.iter.cur_filter_data.last().unwrap();
+                    self.push_item(&real);
+                    self.push_iter(filter_data.func_types.iter());
+                    self.push_iter(filter_data.r_values.iter());
+                    self.push_iter(filter_data.g_values.iter());
+                    self.push_iter(filter_data.b_values.iter());
+                    self.push_iter(filter_data.a_values.iter());
+                    continue;
+                }
+                Real::SetFilterPrimitives => {
+                    self.push_item(&real);
+                    self.push_iter(item.iter.cur_filter_primitives.iter());
+                    continue;
+                }
+                Real::PopReferenceFrame |
+                Real::PopStackingContext |
+                Real::PopAllShadows => {}
+            }
+            self.push_item(&real);
+        }
+    }
+
     pub fn push_shadow(
         &mut self,
         space_and_clip: &di::SpaceAndClipInfo,
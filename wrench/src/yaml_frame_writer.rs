@@ -288,7 +288,9 @@ fn write_stacking_context(
     for filter in filter_iter {
         match filter {
             FilterOp::Identity => { filters.push(Yaml::String("identity".into())) }
-            FilterOp::Blur(x) => { filters.push(Yaml::String(format!("blur({})", x))) }
+            FilterOp::Blur(x) | FilterOp::AnimatedBlur(_, x) => {
+                filters.push(Yaml::String(format!("blur({})", x)))
+            }
             FilterOp::Brightness(x) => { filters.push(Yaml::String(format!("brightness({})", x))) }
             FilterOp::Contrast(x) => { filters.push(Yaml::String(format!("contrast({})", x))) }
             FilterOp::Grayscale(x) => { filters.push(Yaml::String(format!("grayscale({})", x))) }
@@ -386,6 +388,9 @@ fn write_stacking_context(
                 yaml_node(&mut table, "type", Yaml::String("blur".into()));
                 filter_input_node(&mut table, "in", blur_primitive.input);
                 f32_node(&mut table, "radius", blur_primitive.radius);
+                if let Some(radius_y) = blur_primitive.radius_y {
+                    f32_node(&mut table, "radius-y", radius_y);
+                }
             }
             FilterPrimitiveKind::Opacity(opacity_primitive) => {
                 yaml_node(&mut table, "type", Yaml::String("opacity".into()));
@@ -730,12 +730,16 @@ impl YamlHelper for Yaml {
                 "flood" => {
                     FilterPrimitiveKind::Flood(FloodPrimitive {
                         color: self["color"].as_colorf().unwrap(),
+                        color_binding: None,
                     })
                 }
                 "blur" => {
                     FilterPrimitiveKind::Blur(BlurPrimitive {
                         input: self["in"].as_filter_input().unwrap(),
                         radius: self["radius"].as_f32().unwrap(),
+                        radius_y: self["radius-y"].as_f32(),
+                        radius_x_binding: None,
+                        radius_y_binding: None,
                     })
                 }
                 "opacity" => {